@@ -1,20 +1,40 @@
 use crate::error::ContractError;
+use crate::fixed::Rounding;
 use crate::msg::{CurveFn, CurveType};
 use crate::state::CurveState;
 use cosmwasm_schema::cw_serde;
-use cosmwasm_std::{Coin, Decimal, Deps, StdError, Uint128};
+use cosmwasm_std::{Coin, Decimal, Deps, StdError, Uint128, Uint256};
+
+/// Result of pricing a swap against the curve, including the swap fee.
+#[cw_serde]
+pub struct SwapCalculation {
+    /// token out amount for exact-in swaps, or token in amount for exact-out swaps.
+    /// Already accounts for the swap fee.
+    pub amount: Uint128,
+    /// swap fee charged by the pool. Always denominated in the reserve token.
+    pub fee: Uint128,
+    /// part of token in that was not spent and must be returned to the sender.
+    /// Only non-zero for exact-in buys capped by `max_supply`.
+    pub refund: Uint128,
+    /// curve state after the swap
+    pub state: CurveState,
+}
 
 // SwapExactAmountIn swaps an exact amount of tokens in for as many tokens out as possible.
 /// The amount of tokens out is determined by the current exchange rate and the swap fee.
 /// The user specifies a minimum amount of tokens out, and the transaction will revert if that amount of tokens
 /// is not received.
+///
+/// The swap fee is always charged in the reserve token: on buys it is taken from token in before
+/// it reaches the curve, on sells it is taken from the reserve released by the curve.
 pub fn calc_swap_exact_amount_in(
     token_in: Coin,
     token_out_denom: String,
-    _swap_fee: Decimal,
+    swap_fee: Decimal,
     curve_state: CurveState,
     curve_type: CurveType,
-) -> Result<(Uint128, CurveState), ContractError> {
+) -> Result<SwapCalculation, ContractError> {
+    ensure_valid_swap_fee(swap_fee)?;
     let curve_fn = curve_type.to_curve_fn();
     // if received reserve token, is buy. if received supply token, is sell.
 
@@ -24,14 +44,38 @@ pub fn calc_swap_exact_amount_in(
                 "invalid token out denom",
             )));
         }
-        calc_buy_exact_in(curve_state, curve_fn, token_in.amount)
+        let prev_reserve = curve_state.reserve;
+        let fee = mul_ceil(token_in.amount, swap_fee)?;
+        let payment = token_in.amount.checked_sub(fee)?;
+        let (minted, state) = calc_buy_exact_in(curve_state, curve_fn, payment)?;
+        let spent = state.reserve.checked_sub(prev_reserve)?;
+        // a buy capped by max supply only pays the fee on what was actually spent
+        let fee = if spent < payment {
+            mul_ceil(div_complement_ceil(spent, swap_fee)?, swap_fee)?.min(fee)
+        } else {
+            fee
+        };
+        let refund = token_in.amount.checked_sub(spent)?.checked_sub(fee)?;
+        Ok(SwapCalculation {
+            amount: minted,
+            fee,
+            refund,
+            state,
+        })
     } else if &token_in.denom == &curve_state.supply_denom {
         if token_out_denom != curve_state.reserve_denom {
             return Err(ContractError::Std(StdError::generic_err(
                 "invalid token out denom",
             )));
         }
-        calc_sell_exact_in(curve_state, curve_fn, token_in.amount)
+        let (released, state) = calc_sell_exact_in(curve_state, curve_fn, token_in.amount)?;
+        let fee = mul_ceil(released, swap_fee)?;
+        Ok(SwapCalculation {
+            amount: released.checked_sub(fee)?,
+            fee,
+            refund: Uint128::zero(),
+            state,
+        })
     } else {
         Err(ContractError::Std(StdError::generic_err(
             "invalid token in denom",
@@ -49,7 +93,7 @@ pub struct CalcSwapExactAmountInRequest {
 }
 
 impl CalcSwapExactAmountInRequest {
-    pub fn execute(self) -> Result<(Uint128, CurveState), ContractError> {
+    pub fn execute(self) -> Result<SwapCalculation, ContractError> {
         calc_swap_exact_amount_in(
             self.token_in,
             self.token_out_denom,
@@ -66,12 +110,8 @@ pub fn calc_swap_exact_amount_out(
     swap_fee: Decimal,
     curve_state: CurveState,
     curve_type: CurveType,
-) -> Result<(Uint128, CurveState), ContractError> {
-    if !swap_fee.is_zero() {
-        return Err(ContractError::Std(StdError::generic_err(
-            "swap fee must be zero",
-        )));
-    }
+) -> Result<SwapCalculation, ContractError> {
+    ensure_valid_swap_fee(swap_fee)?;
     let curve_fn = curve_type.to_curve_fn();
     // if received reserve token, is buy. if received supply token, is sell.
 
@@ -82,7 +122,14 @@ pub fn calc_swap_exact_amount_out(
             )));
         }
 
-        calc_buy_exact_out(curve_state, curve_fn, token_out.amount)
+        let (cost, state) = calc_buy_exact_out(curve_state, curve_fn, token_out.amount)?;
+        let amount = div_complement_ceil(cost, swap_fee)?;
+        Ok(SwapCalculation {
+            amount,
+            fee: amount.checked_sub(cost)?,
+            refund: Uint128::zero(),
+            state,
+        })
     } else if &token_in_denom == &curve_state.supply_denom {
         if token_out.denom != curve_state.reserve_denom {
             return Err(ContractError::Std(StdError::generic_err(
@@ -90,7 +137,15 @@ pub fn calc_swap_exact_amount_out(
             )));
         }
 
-        calc_sell_exact_out(curve_state, curve_fn, token_out.amount)
+        // release enough reserve to cover both the requested amount and the fee
+        let release_amount = div_complement_ceil(token_out.amount, swap_fee)?;
+        let (burned, state) = calc_sell_exact_out(curve_state, curve_fn, release_amount)?;
+        Ok(SwapCalculation {
+            amount: burned,
+            fee: release_amount.checked_sub(token_out.amount)?,
+            refund: Uint128::zero(),
+            state,
+        })
     } else {
         Err(ContractError::Std(StdError::generic_err(
             "invalid token in denom",
@@ -108,7 +163,7 @@ pub struct CalcSwapExactAmountOutRequest {
 }
 
 impl CalcSwapExactAmountOutRequest {
-    pub fn execute(self) -> Result<(Uint128, CurveState), ContractError> {
+    pub fn execute(self) -> Result<SwapCalculation, ContractError> {
         calc_swap_exact_amount_out(
             self.token_in_denom,
             self.token_out,
@@ -126,8 +181,8 @@ pub fn calc_spot_price(
     curve_type: CurveType,
 ) -> Result<Decimal, ContractError> {
     let curve_fn = curve_type.to_curve_fn();
-    let curve = curve_fn(curve_state.clone().decimals);
-    let mut spot_price = curve.spot_price(curve_state.supply);
+    let curve = curve_fn(curve_state.clone().decimals)?;
+    let mut spot_price = curve.spot_price(curve_state.supply)?;
 
     // quote denom must not equal base denom.
     if quote_asset_denom == base_asset_denom {
@@ -179,17 +234,29 @@ impl CalcSpotPriceRequest {
     }
 }
 
+// The buy and sell calculations below always round in the pool's favor: up for what the user pays
+// and down for what the user receives. That keeps the reserve at or above
+// `curve.reserve(supply, Rounding::Up)` through any sequence of trades.
 pub fn calc_buy_exact_out(
     mut state: CurveState,
     curve_fn: CurveFn,
     mint_amount: Uint128,
 ) -> Result<(Uint128, CurveState), ContractError> {
-    let curve = curve_fn(state.clone().decimals);
+    let curve = curve_fn(state.clone().decimals)?;
     state.supply = state
         .supply
         .checked_add(mint_amount)
         .map_err(StdError::overflow)?;
-    let new_reserve = curve.reserve(state.supply);
+    if let Some(max_supply) = state.max_supply {
+        if state.supply > max_supply {
+            return Err(ContractError::MaxSupplyExceeded {
+                max_supply,
+                requested: state.supply,
+            });
+        }
+    }
+    // the user pays, round up
+    let new_reserve = curve.reserve(state.supply, Rounding::Up)?;
     let cost = new_reserve
         .checked_sub(state.reserve)
         .map_err(StdError::overflow)?;
@@ -203,12 +270,13 @@ fn calc_sell_exact_out(
     curve_fn: CurveFn,
     release_amount: Uint128,
 ) -> Result<(Uint128, CurveState), ContractError> {
-    let curve = curve_fn(state.clone().decimals);
+    let curve = curve_fn(state.clone().decimals)?;
     state.reserve = state
         .reserve
         .checked_sub(release_amount)
         .map_err(StdError::overflow)?;
-    let new_supply = curve.supply(state.reserve);
+    // the user pays in supply, round the supply that is left down
+    let new_supply = curve.supply(state.reserve, Rounding::Down)?;
     let burned = state
         .supply
         .checked_sub(new_supply)
@@ -222,9 +290,27 @@ pub fn calc_buy_exact_in(
     curve_fn: CurveFn,
     payment: Uint128,
 ) -> Result<(Uint128, CurveState), ContractError> {
-    let curve = curve_fn(state.clone().decimals);
-    state.reserve += payment;
-    let new_supply = curve.supply(state.reserve);
+    let curve = curve_fn(state.clone().decimals)?;
+    if let Some(max_supply) = state.max_supply {
+        if state.supply >= max_supply {
+            return Err(ContractError::SoldOut { max_supply });
+        }
+    }
+    let prev_reserve = state.reserve;
+    state.reserve = state
+        .reserve
+        .checked_add(payment)
+        .map_err(StdError::overflow)?;
+    // the user receives, round down
+    let mut new_supply = curve.supply(state.reserve, Rounding::Down)?;
+    if let Some(max_supply) = state.max_supply {
+        // only fill up to the cap. the unspent payment is left out of the reserve
+        // so that the caller can refund it.
+        if new_supply > max_supply {
+            new_supply = max_supply;
+            state.reserve = std::cmp::max(curve.reserve(max_supply, Rounding::Up)?, prev_reserve);
+        }
+    }
     let minted = new_supply
         .checked_sub(state.supply)
         .map_err(StdError::overflow)?;
@@ -237,12 +323,13 @@ fn calc_sell_exact_in(
     curve_fn: CurveFn,
     amount: Uint128,
 ) -> Result<(Uint128, CurveState), ContractError> {
-    let curve = curve_fn(state.clone().decimals);
+    let curve = curve_fn(state.clone().decimals)?;
     state.supply = state
         .supply
         .checked_sub(amount)
         .map_err(StdError::overflow)?;
-    let new_reserve = curve.reserve(state.supply);
+    // the user receives, round the reserve that is left up
+    let new_reserve = curve.reserve(state.supply, Rounding::Up)?;
     let released = state
         .reserve
         .checked_sub(new_reserve)
@@ -251,11 +338,69 @@ fn calc_sell_exact_in(
     Ok((released, state))
 }
 
+/// The swap fee is a fraction of the traded reserve, so it must be below 100%.
+fn ensure_valid_swap_fee(swap_fee: Decimal) -> Result<(), ContractError> {
+    if swap_fee >= Decimal::one() {
+        return Err(ContractError::InvalidSwapFee { swap_fee });
+    }
+    Ok(())
+}
+
+/// `amount * ratio`, rounded up so the fee never rounds in the trader's favor
+fn mul_ceil(amount: Uint128, ratio: Decimal) -> Result<Uint128, ContractError> {
+    let denominator = Uint256::from(Decimal::one().atomics());
+    let numerator = Uint256::from(amount).checked_mul(Uint256::from(ratio.atomics()))?;
+    ceil_div(numerator, denominator)
+}
+
+/// `amount / (1 - ratio)`, rounded up. Grosses up a net amount to include the fee.
+fn div_complement_ceil(amount: Uint128, ratio: Decimal) -> Result<Uint128, ContractError> {
+    let one = Uint256::from(Decimal::one().atomics());
+    let numerator = Uint256::from(amount).checked_mul(one)?;
+    let denominator = one.checked_sub(Uint256::from(ratio.atomics()))?;
+    ceil_div(numerator, denominator)
+}
+
+fn ceil_div(numerator: Uint256, denominator: Uint256) -> Result<Uint128, ContractError> {
+    let quotient = numerator.checked_div(denominator)?;
+    let quotient = if (quotient * denominator) < numerator {
+        quotient + Uint256::one()
+    } else {
+        quotient
+    };
+    Ok(Uint128::try_from(quotient)?)
+}
+
+/// Liquidity seeding the graduation pool: `reserve_ratio` of the reserve less the
+/// `pool_creation_fee` held back from it, and the supply it buys at the current spot price. Both
+/// round down, so the new pool doesn't open below the curve's final price. Returns
+/// `(reserve, supply)` in base units.
+pub fn calc_graduation_liquidity(
+    state: &CurveState,
+    curve_fn: CurveFn,
+    reserve_ratio: Decimal,
+    pool_creation_fee: Uint128,
+) -> Result<(Uint128, Uint128), ContractError> {
+    let curve = curve_fn(state.decimals.clone())?;
+    let spot_price = curve.spot_price(state.supply)?;
+    let reserve = (state.reserve * reserve_ratio).checked_sub(pool_creation_fee)?;
+    // the spot price is in whole tokens
+    let numerator = Uint256::from(reserve)
+        .checked_mul(Uint256::from(Decimal::one().atomics()))?
+        .checked_mul(Uint256::from(10u128).pow(state.decimals.supply))?;
+    let denominator = Uint256::from(spot_price.atomics())
+        .checked_mul(Uint256::from(10u128).pow(state.decimals.reserve))?;
+    let supply = Uint128::try_from(numerator.checked_div(denominator)?)?;
+    Ok((reserve, supply))
+}
+
 /// CLIENT-SIDE FOCUSED CALCULATIONS
 
 #[cw_serde]
 pub struct Quote {
     pub amount: Uint128,
+    /// swap fee included in `amount`, denominated in the reserve token
+    pub fee: Uint128,
     pub before_spot_price_in_over_out: Decimal,
     pub before_spot_price_out_over_in: Decimal,
     pub after_spot_price_in_over_out: Decimal,
@@ -282,7 +427,12 @@ pub fn get_token_in_by_token_out(
     let before_spot_price_out_over_in =
         Decimal::one().checked_div(before_spot_price_in_over_out)?;
 
-    let (amount_in, out_state) = calc_swap_exact_amount_out(
+    let SwapCalculation {
+        amount: amount_in,
+        fee,
+        state: out_state,
+        ..
+    } = calc_swap_exact_amount_out(
         token_in_denom.clone(),
         token_out.clone(),
         swap_fee,
@@ -308,6 +458,7 @@ pub fn get_token_in_by_token_out(
 
     Ok(Quote {
         amount: amount_in,
+        fee,
         before_spot_price_in_over_out,
         before_spot_price_out_over_in,
         after_spot_price_in_over_out,
@@ -356,14 +507,19 @@ pub fn get_token_out_by_token_in(
     let before_spot_price_out_over_in =
         Decimal::one().checked_div(before_spot_price_in_over_out)?;
 
-    let (amount_out, out_state) = calc_swap_exact_amount_in(
+    let SwapCalculation {
+        amount: amount_out,
+        fee,
+        refund,
+        state: out_state,
+    } = calc_swap_exact_amount_in(
         token_in.clone(),
         token_out_denom.clone(),
         swap_fee,
         curve_state,
         curve_type.clone(),
     )?;
-    let amount_in = token_in.amount;
+    let amount_in = token_in.amount.checked_sub(refund)?;
 
     let after_spot_price_in_over_out = calc_spot_price(
         token_in.denom.clone(),
@@ -382,6 +538,7 @@ pub fn get_token_out_by_token_in(
 
     Ok(Quote {
         amount: amount_out,
+        fee,
         before_spot_price_in_over_out,
         before_spot_price_out_over_in,
         after_spot_price_in_over_out,
@@ -418,7 +575,7 @@ impl GetTokenOutByTokenInRequest {
 mod tests {
     use crate::{
         curves::{Curve, DecimalPlaces},
-        msg::CurveType,
+        msg::{CurveSegment, CurveType},
     };
     use cosmwasm_std::{coin, Decimal as StdDecimal};
 
@@ -441,13 +598,14 @@ mod tests {
             supply_denom: "shirt".to_string(),
             reserve: 15000000000u128.into(),
             supply: 10000u128.into(),
+            max_supply: None,
         };
 
-        let curve = curve_type.to_curve_fn()(state.clone().decimals);
+        let curve = curve_type.to_curve_fn()(state.clone().decimals).unwrap();
 
         assert_eq!(
             StdDecimal::percent(150),
-            curve.spot_price(Uint128::new(123))
+            curve.spot_price(Uint128::new(123)).unwrap()
         );
 
         // FUNCTION: calc_buy_exact_out
@@ -533,6 +691,476 @@ mod tests {
         assert_eq!(out_state.supply.u128(), state.supply.u128() - 1000u128);
     }
 
+    #[test]
+    fn test_piecewise_curve() {
+        // first 100 shirts at 1 osmo, the rest at 2 osmo
+        let curve_type = CurveType::Piecewise {
+            segments: vec![
+                CurveSegment {
+                    start: Uint128::zero(),
+                    curve: CurveType::Constant {
+                        value: 1u128.into(),
+                        scale: 0,
+                    },
+                },
+                CurveSegment {
+                    start: 100u128.into(),
+                    curve: CurveType::Constant {
+                        value: 2u128.into(),
+                        scale: 0,
+                    },
+                },
+            ],
+        };
+        curve_type.validate().unwrap();
+
+        let state = CurveState {
+            decimals: DecimalPlaces::new(0, 6),
+            reserve_denom: "osmo".to_string(),
+            supply_denom: "shirt".to_string(),
+            reserve: 90_000_000u128.into(),
+            supply: 90u128.into(),
+            max_supply: None,
+        };
+
+        // every path prices a trade across the breakpoint the same way
+        let (cost, bought) =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), 20u128.into()).unwrap();
+        assert_eq!(cost.u128(), 30_000_000u128);
+        assert_eq!(bought.supply.u128(), 110u128);
+
+        let (minted, out_state) = calc_buy_exact_in(
+            state.clone(),
+            curve_type.to_curve_fn(),
+            30_000_000u128.into(),
+        )
+        .unwrap();
+        assert_eq!(minted.u128(), 20u128);
+        assert_eq!(out_state, bought);
+
+        let (released, out_state) =
+            calc_sell_exact_in(bought.clone(), curve_type.to_curve_fn(), 20u128.into()).unwrap();
+        assert_eq!(released.u128(), 30_000_000u128);
+        assert_eq!(out_state, state);
+
+        let (burned, out_state) =
+            calc_sell_exact_out(bought, curve_type.to_curve_fn(), 30_000_000u128.into()).unwrap();
+        assert_eq!(burned.u128(), 20u128);
+        assert_eq!(out_state, state);
+    }
+
+    #[test]
+    fn test_piecewise_curve_validation() {
+        let segment = |start: u128| CurveSegment {
+            start: start.into(),
+            curve: CurveType::Constant {
+                value: 1u128.into(),
+                scale: 0,
+            },
+        };
+
+        for segments in [vec![], vec![segment(1)], vec![segment(0), segment(0)]] {
+            let err = CurveType::Piecewise { segments }.validate().unwrap_err();
+            assert!(matches!(err, ContractError::InvalidCurve { .. }));
+        }
+
+        // nested curves are validated as well
+        let nested = CurveType::Piecewise {
+            segments: vec![
+                segment(0),
+                CurveSegment {
+                    start: 10u128.into(),
+                    curve: CurveType::Piecewise { segments: vec![] },
+                },
+            ],
+        };
+        assert!(nested.validate().is_err());
+    }
+
+    #[test]
+    fn test_root_curves_are_power_aliases() {
+        let normalize = DecimalPlaces::new(6, 2);
+        let aliases = [
+            (
+                CurveType::SquareRoot {
+                    slope: 35u128.into(),
+                    scale: 2,
+                },
+                1,
+                2,
+            ),
+            (
+                CurveType::SquareRootCubed {
+                    slope: 35u128.into(),
+                    scale: 2,
+                },
+                3,
+                2,
+            ),
+            (
+                CurveType::CubeRootSquared {
+                    slope: 35u128.into(),
+                    scale: 2,
+                },
+                2,
+                3,
+            ),
+        ];
+        for (alias, numerator, denominator) in aliases {
+            let power = CurveType::Power {
+                slope: 35u128.into(),
+                scale: 2,
+                numerator,
+                denominator,
+            };
+            let alias = alias.to_curve_fn()(normalize.clone()).unwrap();
+            let power = power.to_curve_fn()(normalize.clone()).unwrap();
+            for amount in [0u128, 1, 23, 84058, 235_000_000] {
+                let amount = Uint128::new(amount);
+                assert_eq!(
+                    alias.spot_price(amount).unwrap(),
+                    power.spot_price(amount).unwrap()
+                );
+                assert_eq!(
+                    alias.reserve(amount, Rounding::Down).unwrap(),
+                    power.reserve(amount, Rounding::Down).unwrap()
+                );
+                assert_eq!(
+                    alias.supply(amount, Rounding::Down).unwrap(),
+                    power.supply(amount, Rounding::Down).unwrap()
+                );
+            }
+        }
+
+        let err = CurveType::Power {
+            slope: 35u128.into(),
+            scale: 2,
+            numerator: 1,
+            denominator: 0,
+        }
+        .validate()
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCurve { .. }));
+    }
+
+    #[test]
+    fn test_exponential_and_sigmoid_validation() {
+        let invalid = [
+            CurveType::Exponential {
+                base: 1u128.into(),
+                scale: 0,
+            },
+            CurveType::Exponential {
+                base: 99u128.into(),
+                scale: 2,
+            },
+            CurveType::Sigmoid {
+                max_price: StdDecimal::zero(),
+                midpoint: 500u128.into(),
+                steepness: StdDecimal::percent(1),
+            },
+            CurveType::Sigmoid {
+                max_price: StdDecimal::percent(1000),
+                midpoint: 500u128.into(),
+                steepness: StdDecimal::zero(),
+            },
+        ];
+        for curve_type in invalid {
+            let err = curve_type.validate().unwrap_err();
+            assert!(matches!(err, ContractError::InvalidCurve { .. }));
+        }
+
+        CurveType::Exponential {
+            base: 101u128.into(),
+            scale: 2,
+        }
+        .validate()
+        .unwrap();
+    }
+
+    #[test]
+    fn test_wei_reserve_round_trip() {
+        // an 18 decimal supply token bonded against a bridged 18 decimal (wei) reserve
+        let curve_type = CurveType::SquareRootCubed {
+            slope: 35u128.into(),
+            scale: 2,
+        };
+        let state = CurveState::new(
+            "wei".to_string(),
+            "shirt".to_string(),
+            DecimalPlaces::new(18, 18),
+            None,
+        );
+
+        // 1 million whole tokens cost 0.35 * 2/5 * 10^15 = 1.4 * 10^14 whole reserve tokens
+        let amount = Uint128::new(1_000_000 * 10u128.pow(18));
+        let (cost, bought) =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), amount).unwrap();
+        assert_eq!(cost, Uint128::new(140_000_000_000_000 * 10u128.pow(18)));
+
+        // a single wei of supply on top is still priced
+        let (dust_cost, _) = calc_buy_exact_out(
+            bought.clone(),
+            curve_type.to_curve_fn(),
+            Uint128::new(10u128.pow(9)),
+        )
+        .unwrap();
+        assert!(!dust_cost.is_zero());
+
+        let (released, out_state) =
+            calc_sell_exact_in(bought, curve_type.to_curve_fn(), amount).unwrap();
+        assert_eq!(released, cost);
+        assert_eq!(out_state, state);
+    }
+
+    #[test]
+    fn test_sigmoid_curve_round_trip() {
+        let curve_type = CurveType::Sigmoid {
+            max_price: StdDecimal::percent(1000),
+            midpoint: 500u128.into(),
+            steepness: StdDecimal::percent(1),
+        };
+        let state = CurveState::new(
+            "osmo".to_string(),
+            "shirt".to_string(),
+            DecimalPlaces::new(0, 6),
+            None,
+        );
+
+        // buying through the steep middle and selling back ends where we started
+        let (cost, bought) =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), 600u128.into()).unwrap();
+        // paying the same amount in mints at most one base unit less, as supply rounds down
+        let (minted, _) = calc_buy_exact_in(state.clone(), curve_type.to_curve_fn(), cost).unwrap();
+        assert!(minted.u128() == 600u128 || minted.u128() == 599u128);
+
+        let (released, out_state) =
+            calc_sell_exact_in(bought, curve_type.to_curve_fn(), 600u128.into()).unwrap();
+        assert_eq!(released, cost);
+        assert_eq!(out_state, state);
+    }
+
+    #[test]
+    fn test_curve_overflow_is_an_error() {
+        // doubles in price with every shirt
+        let curve_type = CurveType::Exponential {
+            base: 2u128.into(),
+            scale: 0,
+        };
+        let state = CurveState::new(
+            "osmo".to_string(),
+            "shirt".to_string(),
+            DecimalPlaces::new(0, 6),
+            None,
+        );
+
+        // 2^1000 is out of range for the curve math, the swap fails instead of panicking
+        let err = calc_swap_exact_amount_out(
+            "osmo".to_string(),
+            coin(1000u128, "shirt"),
+            StdDecimal::zero(),
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+
+        let err = calc_spot_price(
+            "osmo".to_string(),
+            "shirt".to_string(),
+            CurveState {
+                supply: 1000u128.into(),
+                ..state
+            },
+            curve_type,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+    }
+
+    #[test]
+    fn test_max_supply() {
+        // spot price is always 1.5 osmo per shirt
+        let curve_type = CurveType::Constant {
+            value: 15u128.into(),
+            scale: 1,
+        };
+        let state = CurveState {
+            decimals: DecimalPlaces::new(0, 6),
+            reserve_denom: "osmo".to_string(),
+            supply_denom: "shirt".to_string(),
+            reserve: 735_000000u128.into(),
+            supply: 490u128.into(),
+            max_supply: Some(500u128.into()),
+        };
+
+        // buying up to the cap is fine
+        let (cost, out_state) =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), 10u128.into()).unwrap();
+        assert_eq!(cost.u128(), 15_000000u128);
+        assert_eq!(out_state.supply.u128(), 500u128);
+        assert_eq!(out_state.remaining_supply(), Some(Uint128::zero()));
+
+        // buying past the cap errors
+        let err =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), 11u128.into()).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSupplyExceeded { .. }));
+
+        // paying for 20 shirts only fills the 10 that are left and leaves the rest for a refund
+        let swap = calc_swap_exact_amount_in(
+            coin(30_000000u128, "osmo"),
+            "shirt".to_string(),
+            Decimal::zero(),
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 10u128);
+        assert_eq!(swap.refund.u128(), 15_000000u128);
+        assert_eq!(swap.state.supply.u128(), 500u128);
+        assert_eq!(swap.state.reserve.u128(), 750_000000u128);
+
+        // buys below the cap are not refunded
+        let swap = calc_swap_exact_amount_in(
+            coin(3_000000u128, "osmo"),
+            "shirt".to_string(),
+            Decimal::zero(),
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 2u128);
+        assert_eq!(swap.refund, Uint128::zero());
+
+        // once sold out, every buy errors
+        let (_, sold_out_state) =
+            calc_buy_exact_out(state.clone(), curve_type.to_curve_fn(), 10u128.into()).unwrap();
+        let err = calc_buy_exact_in(
+            sold_out_state.clone(),
+            curve_type.to_curve_fn(),
+            3_000000u128.into(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SoldOut { .. }));
+        let err =
+            calc_buy_exact_out(sold_out_state, curve_type.to_curve_fn(), 1u128.into()).unwrap_err();
+        assert!(matches!(err, ContractError::MaxSupplyExceeded { .. }));
+
+        // sells are unaffected by the cap
+        let (released, _) =
+            calc_sell_exact_in(state, curve_type.to_curve_fn(), 10u128.into()).unwrap();
+        assert_eq!(released.u128(), 15_000000u128);
+    }
+
+    #[test]
+    fn test_swap_fee() {
+        // spot price is always 1.5 osmo per shirt
+        let curve_type = CurveType::Constant {
+            value: 15u128.into(),
+            scale: 1,
+        };
+        let state = CurveState {
+            decimals: DecimalPlaces::new(0, 6),
+            reserve_denom: "osmo".to_string(),
+            supply_denom: "shirt".to_string(),
+            reserve: 15_000000u128.into(),
+            supply: 10u128.into(),
+            max_supply: Some(20u128.into()),
+        };
+        let swap_fee = Decimal::percent(1);
+
+        // buy exact in: the fee is taken from token in before it reaches the curve
+        let swap = calc_swap_exact_amount_in(
+            coin(3_030303u128, "osmo"),
+            "shirt".to_string(),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.fee.u128(), 30304u128);
+        assert_eq!(swap.amount.u128(), 1u128);
+        assert_eq!(swap.state.reserve.u128(), 15_000000u128 + 2_999999u128);
+
+        // buy exact out: the curve cost is grossed up by the fee
+        let swap = calc_swap_exact_amount_out(
+            "osmo".to_string(),
+            coin(2u128, "shirt"),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 3_030304u128);
+        assert_eq!(swap.fee.u128(), 30304u128);
+        assert_eq!(swap.state.reserve.u128(), 18_000000u128);
+
+        // sell exact in: the fee is taken from the released reserve
+        let swap = calc_swap_exact_amount_in(
+            coin(2u128, "shirt"),
+            "osmo".to_string(),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 2_970000u128);
+        assert_eq!(swap.fee.u128(), 30000u128);
+        assert_eq!(swap.state.reserve.u128(), 12_000000u128);
+
+        // sell exact out: enough reserve is released to cover the fee
+        let swap = calc_swap_exact_amount_out(
+            "shirt".to_string(),
+            coin(2_970000u128, "osmo"),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 2u128);
+        assert_eq!(swap.fee.u128(), 30000u128);
+        assert_eq!(swap.state.reserve.u128(), 12_000000u128);
+
+        // buys capped by max supply only pay the fee on what was spent
+        let swap = calc_swap_exact_amount_in(
+            coin(100_000000u128, "osmo"),
+            "shirt".to_string(),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(swap.amount.u128(), 10u128);
+        assert_eq!(swap.fee.u128(), 151516u128);
+        assert_eq!(
+            swap.refund.u128(),
+            100_000000u128 - 15_000000u128 - 151516u128
+        );
+
+        // quotes include the fee
+        let quote = get_token_in_by_token_out(
+            coin(2u128, "shirt"),
+            "osmo".to_string(),
+            swap_fee,
+            state.clone(),
+            curve_type.clone(),
+        )
+        .unwrap();
+        assert_eq!(quote.amount.u128(), 3_030304u128);
+        assert_eq!(quote.fee.u128(), 30304u128);
+
+        // a 100% fee is rejected
+        let err = calc_swap_exact_amount_in(
+            coin(1_000000u128, "osmo"),
+            "shirt".to_string(),
+            Decimal::one(),
+            state,
+            curve_type,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSwapFee { .. }));
+    }
+
     #[test]
     fn test_get_token_in_by_token_out() {
         let curve_type = CurveType::Linear {
@@ -543,11 +1171,17 @@ mod tests {
         let normalize = DecimalPlaces::new(0, 0);
         let curve_fn = curve_type.to_curve_fn();
         let supply = 10u128;
-        let reserve = curve_fn(normalize.clone()).reserve(supply.into());
-        let supply = curve_fn(normalize.clone()).supply(reserve);
+        let reserve = curve_fn(normalize.clone())
+            .unwrap()
+            .reserve(supply.into(), Rounding::Down)
+            .unwrap();
+        let supply = curve_fn(normalize.clone())
+            .unwrap()
+            .supply(reserve, Rounding::Down)
+            .unwrap();
         assert_eq!(
             reserve,
-            curve_fn(normalize.clone()).reserve(supply),
+            curve_fn(normalize.clone()).unwrap().reserve(supply, Rounding::Down).unwrap(),
             "reserve and calculated reserve should be equal. if not, test configuration for supply is prone to precision errors."
         );
         let curve_state = CurveState {
@@ -556,6 +1190,7 @@ mod tests {
             supply_denom: "shirt".to_string(),
             reserve,
             supply,
+            max_supply: None,
         };
 
         let amount_out = 2u128;
@@ -627,11 +1262,17 @@ mod tests {
         let normalize = DecimalPlaces::new(0, 0);
         let curve_fn = curve_type.to_curve_fn();
         let supply = 10u128;
-        let reserve = curve_fn(normalize.clone()).reserve(supply.into());
-        let supply = curve_fn(normalize.clone()).supply(reserve);
+        let reserve = curve_fn(normalize.clone())
+            .unwrap()
+            .reserve(supply.into(), Rounding::Down)
+            .unwrap();
+        let supply = curve_fn(normalize.clone())
+            .unwrap()
+            .supply(reserve, Rounding::Down)
+            .unwrap();
         assert_eq!(
             reserve,
-            curve_fn(normalize.clone()).reserve(supply),
+            curve_fn(normalize.clone()).unwrap().reserve(supply, Rounding::Down).unwrap(),
             "reserve and calculated reserve should be equal. if not, test configuration for supply is prone to precision errors."
         );
         let curve_state = CurveState {
@@ -640,6 +1281,7 @@ mod tests {
             supply_denom: "shirt".to_string(),
             reserve,
             supply,
+            max_supply: None,
         };
 
         let amount_in = 2u128;
@@ -685,4 +1327,165 @@ mod tests {
             "num_ticks_crossed should be None"
         );
     }
+
+    /// xorshift64, enough to sample trades deterministically
+    struct Sampler(u64);
+
+    impl Sampler {
+        fn next(&mut self, below: u128) -> u128 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 as u128 % below
+        }
+    }
+
+    /// every `CurveType`, with parameters that keep 2000 whole tokens of supply in range
+    fn all_curve_types() -> Vec<CurveType> {
+        vec![
+            CurveType::Constant {
+                value: 15u128.into(),
+                scale: 1,
+            },
+            CurveType::Linear {
+                slope: 1u128.into(),
+                scale: 1,
+            },
+            CurveType::SquareRoot {
+                slope: 35u128.into(),
+                scale: 2,
+            },
+            CurveType::SquareRootCubed {
+                slope: 35u128.into(),
+                scale: 2,
+            },
+            CurveType::CubeRootSquared {
+                slope: 35u128.into(),
+                scale: 2,
+            },
+            CurveType::Power {
+                slope: 2u128.into(),
+                scale: 0,
+                numerator: 5,
+                denominator: 4,
+            },
+            CurveType::Exponential {
+                base: 1001u128.into(),
+                scale: 3,
+            },
+            CurveType::Sigmoid {
+                max_price: StdDecimal::percent(1000),
+                midpoint: 1000u128.into(),
+                steepness: StdDecimal::percent(1),
+            },
+            CurveType::Piecewise {
+                segments: vec![
+                    CurveSegment {
+                        start: Uint128::zero(),
+                        curve: CurveType::SquareRoot {
+                            slope: 35u128.into(),
+                            scale: 2,
+                        },
+                    },
+                    CurveSegment {
+                        start: 500u128.into(),
+                        curve: CurveType::Constant {
+                            value: 15u128.into(),
+                            scale: 1,
+                        },
+                    },
+                ],
+            },
+        ]
+    }
+
+    #[test]
+    fn test_round_trips_never_drain_reserve() {
+        let mut sampler = Sampler(0x5eed);
+        for curve_type in all_curve_types() {
+            for (supply_places, reserve_places) in [(0u8, 6u8), (6, 6), (6, 0), (18, 18)] {
+                let decimals = DecimalPlaces::new(supply_places, reserve_places);
+                // the sigmoid midpoint and piecewise breakpoints are in base units
+                let unit = if supply_places == 0 {
+                    1
+                } else {
+                    10u128.pow(supply_places as u32 - 3)
+                };
+                let curve = curve_type.to_curve_fn()(decimals.clone()).unwrap();
+                let mut state =
+                    CurveState::new("osmo".to_string(), "shirt".to_string(), decimals, None);
+
+                for _ in 0..50 {
+                    let prev = state.clone();
+                    let (paid, received) = match sampler.next(4) {
+                        0 => {
+                            let amount = Uint128::new(1 + sampler.next(100) * unit);
+                            let (cost, next) =
+                                calc_buy_exact_out(state, curve_type.to_curve_fn(), amount)
+                                    .unwrap();
+                            state = next;
+                            (cost, amount)
+                        }
+                        1 => {
+                            let payment = Uint128::new(
+                                1 + sampler.next(10u128.pow(reserve_places as u32 + 2)),
+                            );
+                            let (minted, next) =
+                                calc_buy_exact_in(state, curve_type.to_curve_fn(), payment)
+                                    .unwrap();
+                            state = next;
+                            (payment, minted)
+                        }
+                        2 if !state.supply.is_zero() => {
+                            let amount = Uint128::new(1 + sampler.next(state.supply.u128()));
+                            let (released, next) =
+                                calc_sell_exact_in(state, curve_type.to_curve_fn(), amount)
+                                    .unwrap();
+                            state = next;
+                            (amount, released)
+                        }
+                        3 if !state.reserve.is_zero() => {
+                            let amount = Uint128::new(1 + sampler.next(state.reserve.u128()));
+                            let (burned, next) =
+                                calc_sell_exact_out(state, curve_type.to_curve_fn(), amount)
+                                    .unwrap();
+                            state = next;
+                            (burned, amount)
+                        }
+                        _ => continue,
+                    };
+
+                    // the pool always holds at least what the curve says the supply is worth
+                    let owed = curve.reserve(state.supply, Rounding::Up).unwrap();
+                    assert!(
+                        state.reserve >= owed,
+                        "{curve_type:?} {supply_places}/{reserve_places}: reserve {} below {owed}",
+                        state.reserve
+                    );
+
+                    // buying and selling back right away pays out at most what it cost, plus any
+                    // rounding surplus the pool already held
+                    if state.supply > prev.supply {
+                        let bought = state.supply - prev.supply;
+                        let (released, _) =
+                            calc_sell_exact_in(state.clone(), curve_type.to_curve_fn(), bought)
+                                .unwrap();
+                        let cost = state.reserve - prev.reserve;
+                        let surplus =
+                            prev.reserve - curve.reserve(prev.supply, Rounding::Up).unwrap();
+                        assert!(
+                            released <= cost + surplus,
+                            "{curve_type:?}: {paid} in, {received} out, {released} back"
+                        );
+                    }
+                }
+
+                // selling everything leaves any rounding dust in the pool
+                let (_, emptied) =
+                    calc_sell_exact_in(state.clone(), curve_type.to_curve_fn(), state.supply)
+                        .unwrap();
+                assert!(emptied.supply.is_zero());
+            }
+        }
+    }
 }
@@ -1,34 +1,68 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
-    coin, coins, ensure, to_binary, BankMsg, Binary, Coin, CosmosMsg, Decimal, Deps, DepsMut, Env,
-    MessageInfo, Reply, Response, StdError, StdResult, Uint128,
+    coin, ensure, from_binary, to_binary, Addr, Api, BankMsg, Binary, Coin, CosmosMsg, Decimal,
+    Deps, DepsMut, Empty, Env, Event, MessageInfo, Order, Reply, Response, StdError, StdResult,
+    Storage, SubMsg, SubMsgResult, Timestamp, Uint128,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
+use cw20::Cw20ReceiveMsg;
+use cw_storage_plus::Bound;
 use cw_utils::{must_pay, one_coin};
 
 use crate::calc::{
-    calc_buy_exact_out, calc_spot_price, calc_swap_exact_amount_in, calc_swap_exact_amount_out,
+    calc_buy_exact_out, calc_graduation_liquidity, calc_spot_price, calc_swap_exact_amount_in,
+    calc_swap_exact_amount_out, SwapCalculation,
 };
-use crate::curves::DecimalPlaces;
+use crate::curves::{check_invariants, DecimalPlaces};
 use crate::error::ContractError;
-use crate::helpers::mint_or_send;
+use crate::fixed::Rounding;
+use crate::helpers::{
+    burn_or_receive, create_coin_io_messages, create_graduation_pool_msg, decode_hash,
+    send_reserve, set_denom_metadata_msg, verify_merkle_proof,
+};
 use crate::msg::{
-    BondingPoolState, CalcInAmtGivenOutResponse, CalcOutAmtGivenInResponse, CurveType, ExecuteMsg,
-    GetSwapFeeResponse, GetTotalPoolLiquidityResponse, InstantiateMsg, IsActiveResponse,
-    MigrateMsg, QueryMsg, SimulationMsg, SpotPriceResponse, SudoMsg, SwapExactAmountInResponseData,
-    SwapExactAmountOutResponseData,
+    AllowlistProof, BondingPoolState, CalcInAmtGivenOutResponse, CalcOutAmtGivenInResponse,
+    CheckCurveInvariantsResponse, CircuitBreakerResponse, CrowdfundResponse, CurrentPhaseResponse,
+    CurveType, ExecuteMsg, ExemptWalletsResponse, GetSwapFeeResponse,
+    GetTotalPoolLiquidityResponse, GraduationResponse, InstantiateMsg, IsActiveResponse,
+    MerkleRootResponse, MigrateMsg, QueryMsg, ReceiveMsg, RemainingAllocationResponse,
+    RemainingSupplyResponse, RetirementResponse, SimulationMsg, SpotPriceResponse, SudoMsg,
+    SupplyReconciliationResponse, SwapExactAmountInResponseData, SwapExactAmountOutResponseData,
+    TradesResponse, TwapResponse, WalletPurchasesResponse,
 };
 use crate::state::{
-    CurveState, CURVE_STATE, CURVE_TYPE, DISSOLVED_CURVE_STATE, IS_ACTIVE, IS_SIMULATION_MODE,
-    IS_TEST_MODE,
+    is_retired, load_merkle_allocation, load_pause_state, load_reserve_asset, BreakerWindow,
+    BreakerWindowState, CircuitBreakerConfig, CircuitBreakerTrip, CrowdfundConfig, CrowdfundState,
+    CrowdfundStatus, CurveState, DenomMetadata, FeeConfig, Graduation, GraduationConfig,
+    GraduationPool, MerkleAllocation, Ownership, PauseState, ReserveAsset, RetirePolicy,
+    SaleAccess, SalePhase, Trade, TradeDirection, TradeHistoryConfig, TradeState, WalletPurchase,
+    WalletPurchaseState, ALLOWLIST, ALLOWLIST_PURCHASES, BREAKER_TRIP, BREAKER_WINDOW,
+    CIRCUIT_BREAKER_CONFIG, CONTRIBUTIONS, CROWDFUND_CONFIG, CROWDFUND_STATE, CURVE_STATE,
+    CURVE_TYPE, DENOM_METADATA, DISSOLVED_CURVE_STATE, FEE_CONFIG, GRADUATION, GRADUATION_CONFIG,
+    IS_ACTIVE, IS_RETIRED, IS_SIMULATION_MODE, IS_TEST_MODE, MAX_PER_ADDRESS, MERKLE_ALLOCATIONS,
+    MERKLE_ROOT, OWNERSHIP, PAUSE_STATE, PHASE_PURCHASES, RESERVE_ASSET, RETIRE_POLICY,
+    SALE_PHASES, TRADE_HISTORY_CONFIG, TRADE_IDS,
 };
-use osmosis_std::types::osmosis::tokenfactory::v1beta1::MsgCreateDenom;
+use crate::twap::{arithmetic_twap, record_price, spot_price};
+use osmosis_std::types::osmosis::gamm::poolmodels::balancer::v1beta1::MsgCreateBalancerPoolResponse;
+use osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgChangeAdmin, MsgCreateDenom, MsgMint};
+use semver::Version;
 
 // version info for migration info
 pub const CONTRACT_NAME: &str = "crates.io:cw-bonding-pool";
 pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// upper bound on `QueryMsg::CheckCurveInvariants` samples, keeps the query within gas limits
+pub const MAX_INVARIANT_SAMPLES: u32 = 1000;
+
+/// upper bound on the trades pruned when recording a trade, keeps swaps within gas limits after
+/// the trade history bounds are lowered
+pub const MAX_PRUNED_PER_TRADE: u64 = 10;
+
+/// reply id of the graduation pool creation
+pub const GRADUATION_REPLY_ID: u64 = 1;
+
 /// Handling contract instantiation
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
@@ -41,12 +75,108 @@ pub fn instantiate(
 
     let supply_denom = format!("factory/{}/{}", env.contract.address, msg.supply_subdenom);
     let places = DecimalPlaces::new(msg.supply_decimals, msg.reserve_decimals);
-    let supply = CurveState::new(msg.reserve_denom, supply_denom, places);
+    ensure!(
+        !msg.max_supply.is_zero(),
+        ContractError::InvalidMaxSupply {}
+    );
+    let reserve_asset = msg.reserve_asset.unwrap_or_default();
+    if reserve_asset == ReserveAsset::Cw20 {
+        deps.api.addr_validate(&msg.reserve_denom)?;
+    }
+    RESERVE_ASSET.save(deps.storage, &reserve_asset)?;
+    let supply = CurveState::new(
+        msg.reserve_denom,
+        supply_denom,
+        places,
+        Some(msg.max_supply),
+    );
+
+    let owner = match msg.owner {
+        Some(owner) => deps.api.addr_validate(&owner)?,
+        None => info.sender.clone(),
+    };
+    OWNERSHIP.save(
+        deps.storage,
+        &Ownership {
+            owner: Some(owner.clone()),
+            pending_owner: None,
+        },
+    )?;
+
+    let retire_policy = match msg.retire_policy.unwrap_or_default() {
+        RetirePolicy::SendReserve { recipient } => RetirePolicy::SendReserve {
+            recipient: deps.api.addr_validate(recipient.as_str())?,
+        },
+        policy => policy,
+    };
+    RETIRE_POLICY.save(deps.storage, &retire_policy)?;
+
+    if let Some(graduation) = msg.graduation {
+        let graduation =
+            validate_graduation_config(deps.api, graduation, &reserve_asset, msg.max_supply)?;
+        GRADUATION_CONFIG.save(deps.storage, &graduation)?;
+    }
+    if let Some(crowdfund) = msg.crowdfund {
+        validate_crowdfund_config(deps.storage, &env, &crowdfund, msg.max_supply)?;
+        CROWDFUND_CONFIG.save(deps.storage, &crowdfund)?;
+    }
+    if let Some(sale_phases) = msg.sale_phases {
+        validate_sale_phases(&sale_phases)?;
+        SALE_PHASES.save(deps.storage, &sale_phases)?;
+    }
+    if let Some(circuit_breaker) = msg.circuit_breaker {
+        validate_circuit_breaker(&circuit_breaker)?;
+        CIRCUIT_BREAKER_CONFIG.save(deps.storage, &circuit_breaker)?;
+    }
+    if let Some(max_per_address) = msg.max_per_address {
+        ensure!(
+            !max_per_address.is_zero(),
+            ContractError::InvalidMaxPerAddress {}
+        );
+        MAX_PER_ADDRESS.save(deps.storage, &max_per_address)?;
+    }
+    if let Some(trade_history) = msg.trade_history {
+        validate_trade_history(&trade_history)?;
+        TRADE_HISTORY_CONFIG.save(deps.storage, &trade_history)?;
+    }
+
+    let swap_fee = msg.swap_fee.unwrap_or_default();
+    ensure!(
+        swap_fee < Decimal::one(),
+        ContractError::InvalidSwapFee { swap_fee }
+    );
+    let fee_collector = match msg.fee_collector {
+        Some(fee_collector) => deps.api.addr_validate(&fee_collector)?,
+        None => info.sender,
+    };
+    FEE_CONFIG.save(
+        deps.storage,
+        &FeeConfig {
+            swap_fee,
+            fee_collector,
+        },
+    )?;
+
+    let denom_metadata = DenomMetadata {
+        name: msg.name,
+        symbol: msg.symbol,
+        description: msg.description,
+        uri: msg.uri,
+    };
+    DENOM_METADATA.save(deps.storage, &denom_metadata)?;
+    let msg_set_denom_metadata = set_denom_metadata_msg(
+        supply.supply_denom.clone(),
+        supply.decimals.supply,
+        &denom_metadata,
+        env.contract.address.to_string(),
+    );
 
     DISSOLVED_CURVE_STATE.save(deps.storage, &supply)?;
     CURVE_STATE.save(deps.storage, &supply)?;
+    msg.curve_type.validate()?;
     CURVE_TYPE.save(deps.storage, &msg.curve_type)?;
-    IS_ACTIVE.save(deps.storage, &true)?;
+    record_price(deps.storage, &env, &supply)?;
+    PAUSE_STATE.save(deps.storage, &PauseState::default())?;
     IS_TEST_MODE.save(deps.storage, &msg.test_mode.unwrap_or(false))?;
     IS_SIMULATION_MODE.save(deps.storage, &msg.simulation_mode.unwrap_or(false))?;
 
@@ -54,7 +184,7 @@ pub fn instantiate(
         // return without executing messages if in simulation mode
         return Ok(Response::new()
             .add_attribute("method", "instantiate")
-            .add_attribute("owner", info.sender));
+            .add_attribute("owner", owner));
     }
 
     let msg_create_denom: CosmosMsg = MsgCreateDenom {
@@ -67,8 +197,133 @@ pub fn instantiate(
     // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
     Ok(Response::new()
         .add_message(msg_create_denom)
+        .add_message(msg_set_denom_metadata)
         .add_attribute("method", "instantiate")
-        .add_attribute("owner", info.sender))
+        .add_attribute("owner", owner))
+}
+
+fn validate_graduation_config(
+    api: &dyn Api,
+    config: GraduationConfig,
+    reserve_asset: &ReserveAsset,
+    max_supply: Uint128,
+) -> Result<GraduationConfig, ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidGraduationConfig {
+        reason: reason.to_string(),
+    };
+    ensure!(
+        *reserve_asset == ReserveAsset::Native,
+        invalid("graduation pools need a native reserve")
+    );
+    ensure!(
+        !config.supply_threshold.is_zero() && config.supply_threshold <= max_supply,
+        invalid("supply threshold must be between 1 and the max supply")
+    );
+    ensure!(
+        !config.reserve_ratio.is_zero() && config.reserve_ratio <= Decimal::one(),
+        invalid("reserve ratio must be above 0 and at most 1")
+    );
+    match config.pool {
+        GraduationPool::Balancer { swap_fee } => ensure!(
+            swap_fee < Decimal::one(),
+            invalid("pool swap fee must be less than 1")
+        ),
+    }
+    for (i, fee) in config.pool_creation_fee.iter().enumerate() {
+        ensure!(
+            !fee.amount.is_zero(),
+            invalid("pool creation fee coins must be non-zero")
+        );
+        ensure!(
+            config.pool_creation_fee[..i]
+                .iter()
+                .all(|other| other.denom != fee.denom),
+            invalid("pool creation fee denoms must be unique")
+        );
+    }
+    Ok(GraduationConfig {
+        lp_recipient: api.addr_validate(config.lp_recipient.as_str())?,
+        ..config
+    })
+}
+
+fn validate_crowdfund_config(
+    storage: &dyn Storage,
+    env: &Env,
+    config: &CrowdfundConfig,
+    max_supply: Uint128,
+) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidCrowdfundConfig {
+        reason: reason.to_string(),
+    };
+    ensure!(
+        !config.target_supply.is_zero() && config.target_supply <= max_supply,
+        invalid("target supply must be between 1 and the max supply")
+    );
+    ensure!(
+        config.deadline > env.block.time,
+        invalid("deadline must be in the future")
+    );
+    // graduating moves part of the reserve, which has to stay refundable until funded
+    if let Some(graduation) = GRADUATION_CONFIG.may_load(storage)? {
+        ensure!(
+            config.target_supply <= graduation.supply_threshold,
+            invalid("target supply must not be above the graduation threshold")
+        );
+    }
+    Ok(())
+}
+
+fn validate_circuit_breaker(config: &CircuitBreakerConfig) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidCircuitBreaker {
+        reason: reason.to_string(),
+    };
+    let window = match config.window {
+        BreakerWindow::Blocks { blocks } => blocks,
+        BreakerWindow::Seconds { seconds } => seconds,
+    };
+    ensure!(window > 0, invalid("window must not be empty"));
+    ensure!(
+        config.max_price_change.is_some()
+            || config.max_reserve_inflow.is_some()
+            || config.max_reserve_outflow.is_some(),
+        invalid("at least one limit must be set")
+    );
+    ensure!(
+        config.max_price_change != Some(Decimal::zero())
+            && config.max_reserve_inflow != Some(Uint128::zero())
+            && config.max_reserve_outflow != Some(Uint128::zero()),
+        invalid("limits must be greater than zero")
+    );
+    Ok(())
+}
+
+fn validate_trade_history(config: &TradeHistoryConfig) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidTradeHistoryConfig {
+        reason: reason.to_string(),
+    };
+    ensure!(
+        config.max_trades != Some(0) && config.max_age != Some(0),
+        invalid("bounds must be greater than zero")
+    );
+    Ok(())
+}
+
+fn validate_sale_phases(phases: &[SalePhase]) -> Result<(), ContractError> {
+    let invalid = |reason: &str| ContractError::InvalidSalePhases {
+        reason: reason.to_string(),
+    };
+    ensure!(
+        phases.windows(2).all(|pair| pair[0].start < pair[1].start),
+        invalid("phases must be ordered by start, with distinct starts")
+    );
+    ensure!(
+        phases
+            .iter()
+            .all(|phase| phase.price_multiplier.unwrap_or(Decimal::one()) >= Decimal::one()),
+        invalid("price multipliers must be at least 1")
+    );
+    Ok(())
 }
 
 /// Handling contract migration
@@ -77,13 +332,128 @@ pub fn instantiate(
 /// - only contract admin can migrate, so admin has to be set at contract initiation time
 /// Handling contract execution
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
-    match msg {
-        // Find matched incoming message variant and execute them with your custom logic.
-        //
-        // With `Response` type, it is possible to dispatch message to invoke external logic.
-        // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored_version = ensure_from_older_version(deps.storage)?;
+
+    let (owner, swap_fee, fee_collector, max_supply) = match msg {
+        MigrateMsg::Upgrade {} => (None, None, None, None),
+        MigrateMsg::UpgradeWithConfig {
+            owner,
+            swap_fee,
+            fee_collector,
+            max_supply,
+        } => (owner, swap_fee, fee_collector, max_supply),
+    };
+
+    if stored_version < Version::new(0, 2, 0) {
+        migrate_from_v0_1(
+            deps.branch(),
+            &env,
+            owner,
+            swap_fee,
+            fee_collector,
+            max_supply,
+        )?;
+    }
+
+    if PAUSE_STATE.may_load(deps.storage)?.is_none() {
+        let pause_state = load_pause_state(deps.storage)?;
+        PAUSE_STATE.save(deps.storage, &pause_state)?;
+        IS_ACTIVE.remove(deps.storage);
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored_version.to_string())
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+/// Errors unless the stored cw2 info belongs to this contract and is not newer than this code.
+/// Returns the stored version.
+fn ensure_from_older_version(storage: &dyn Storage) -> Result<Version, ContractError> {
+    let stored = get_contract_version(storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContractName {
+            expected: CONTRACT_NAME.to_string(),
+            actual: stored.contract,
+        });
+    }
+    let stored_version: Version = stored.version.parse()?;
+    let version: Version = CONTRACT_VERSION.parse()?;
+    if stored_version > version {
+        return Err(ContractError::CannotDowngrade {
+            from: stored.version,
+            to: CONTRACT_VERSION.to_string(),
+        });
     }
+    Ok(stored_version)
+}
+
+/// 0.1.x pools stored no owner, no fee config and no supply cap
+fn migrate_from_v0_1(
+    deps: DepsMut,
+    env: &Env,
+    owner: Option<String>,
+    swap_fee: Option<Decimal>,
+    fee_collector: Option<String>,
+    max_supply: Option<Uint128>,
+) -> Result<(), ContractError> {
+    if let Some(max_supply) = max_supply {
+        let mut curve_state = CURVE_STATE.load(deps.storage)?;
+        if curve_state.max_supply.is_none() {
+            ensure!(
+                !max_supply.is_zero() && max_supply >= curve_state.supply,
+                ContractError::InvalidMaxSupply {}
+            );
+            curve_state.max_supply = Some(max_supply);
+            CURVE_STATE.save(deps.storage, &curve_state)?;
+            DISSOLVED_CURVE_STATE.update(deps.storage, |mut dissolved| -> StdResult<_> {
+                dissolved.max_supply = Some(max_supply);
+                Ok(dissolved)
+            })?;
+        }
+    }
+
+    let owner = owner
+        .map(|owner| deps.api.addr_validate(&owner))
+        .transpose()?;
+
+    if OWNERSHIP.may_load(deps.storage)?.is_none() {
+        OWNERSHIP.save(
+            deps.storage,
+            &Ownership {
+                owner: owner.clone(),
+                pending_owner: None,
+            },
+        )?;
+    }
+
+    if FEE_CONFIG.may_load(deps.storage)?.is_none() {
+        let swap_fee = swap_fee.unwrap_or_default();
+        ensure!(
+            swap_fee < Decimal::one(),
+            ContractError::InvalidSwapFee { swap_fee }
+        );
+        let fee_collector = match fee_collector {
+            Some(fee_collector) => deps.api.addr_validate(&fee_collector)?,
+            None => owner.unwrap_or_else(|| env.contract.address.clone()),
+        };
+        FEE_CONFIG.save(
+            deps.storage,
+            &FeeConfig {
+                swap_fee,
+                fee_collector,
+            },
+        )?;
+    }
+
+    if RESERVE_ASSET.may_load(deps.storage)?.is_none() {
+        RESERVE_ASSET.save(deps.storage, &ReserveAsset::Native)?;
+    }
+
+    Ok(())
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -109,211 +479,1659 @@ pub fn execute(
             );
             execute_simulate(deps, env, sim_msg)
         }
+        ExecuteMsg::Buy {
+            min_out,
+            recipient,
+            deadline,
+            allowlist_proof,
+        } => execute_buy(
+            deps,
+            env,
+            info,
+            min_out,
+            recipient,
+            deadline,
+            allowlist_proof,
+        ),
+        ExecuteMsg::Sell {
+            min_out,
+            recipient,
+            deadline,
+        } => execute_sell(deps, env, info, min_out, recipient, deadline),
+        ExecuteMsg::Receive(cw20_msg) => execute_receive(deps, env, info, cw20_msg),
+        ExecuteMsg::ProposeNewOwner { new_owner } => {
+            execute_propose_new_owner(deps, info, new_owner)
+        }
+        ExecuteMsg::AcceptOwnership {} => execute_accept_ownership(deps, info),
+        ExecuteMsg::RenounceOwnership {} => execute_renounce_ownership(deps, info),
+        ExecuteMsg::SetActive { is_active } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            execute_set_active(deps, env, is_active)
+        }
+        ExecuteMsg::UpdateFeeConfig {
+            swap_fee,
+            fee_collector,
+        } => execute_update_fee_config(deps, info, swap_fee, fee_collector),
+        ExecuteMsg::TransferDenomAdmin { new_admin } => {
+            execute_transfer_denom_admin(deps, env, info, new_admin)
+        }
+        ExecuteMsg::RetirePool { denom_admin } => execute_retire_pool(deps, env, info, denom_admin),
+        ExecuteMsg::ClaimRefund {} => execute_claim_refund(deps, env, info),
+        ExecuteMsg::UpdateSalePhases { phases } => execute_update_sale_phases(deps, info, phases),
+        ExecuteMsg::RegisterMerkleRoot { merkle_root } => {
+            execute_register_merkle_root(deps, info, merkle_root)
+        }
+        ExecuteMsg::UpdatePauseState {
+            buys,
+            sells,
+            dissolve,
+        } => {
+            ensure_owner(deps.as_ref(), &info)?;
+            let pause_state = load_pause_state(deps.storage)?;
+            let pause_state = PauseState {
+                buys: buys.unwrap_or(pause_state.buys),
+                sells: sells.unwrap_or(pause_state.sells),
+                dissolve: dissolve.unwrap_or(pause_state.dissolve),
+            };
+            execute_update_pause_state(deps, pause_state)
+        }
+        ExecuteMsg::UpdateCircuitBreaker { circuit_breaker } => {
+            execute_update_circuit_breaker(deps, info, circuit_breaker)
+        }
+        ExecuteMsg::UpdateMaxPerAddress { max_per_address } => {
+            execute_update_max_per_address(deps, info, max_per_address)
+        }
+        ExecuteMsg::UpdateWalletExemptions { add, remove } => {
+            execute_update_wallet_exemptions(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateTradeHistory { trade_history } => {
+            execute_update_trade_history(deps, info, trade_history)
+        }
+        ExecuteMsg::UpdateAllowlist { add, remove } => {
+            execute_update_allowlist(deps, info, add, remove)
+        }
+        ExecuteMsg::UpdateDenomMetadata {
+            name,
+            symbol,
+            description,
+            uri,
+        } => execute_update_denom_metadata(
+            deps,
+            env,
+            info,
+            DenomMetadata {
+                name,
+                symbol,
+                description,
+                uri,
+            },
+        ),
     }
 }
 
-pub fn execute_simulate(
-    deps: DepsMut,
-    _env: Env,
-    msg: SimulationMsg,
-) -> Result<Response, ContractError> {
-    match msg {
-        SimulationMsg::SetState { state } => {
-            CURVE_STATE.save(deps.storage, &state.curve_state)?;
-            DISSOLVED_CURVE_STATE.save(deps.storage, &state.dissolved_curve_state)?;
-            CURVE_TYPE.save(deps.storage, &state.curve_type)?;
-            IS_ACTIVE.save(deps.storage, &state.is_active)?;
-            Ok(Response::new().add_attribute("method", "simulate"))
-        }
-    }
+/// Errors unless the message was sent by the current owner
+pub fn ensure_owner(deps: Deps, info: &MessageInfo) -> Result<(), ContractError> {
+    let ownership = OWNERSHIP.load(deps.storage)?;
+    ensure!(
+        ownership.owner.as_ref() == Some(&info.sender),
+        ContractError::Unauthorized {}
+    );
+    Ok(())
 }
 
-pub fn execute_dissolve(
+pub fn execute_propose_new_owner(
     deps: DepsMut,
-    _env: Env,
     info: MessageInfo,
+    new_owner: String,
 ) -> Result<Response, ContractError> {
-    let curve_state = CURVE_STATE.load(deps.storage)?;
-    let curve = CURVE_TYPE.load(deps.storage)?;
-    let curve_fn = curve.to_curve_fn();
-    one_coin(&info)?;
-    let paid = must_pay(&info, &curve_state.supply_denom)?;
-    let dissolved_curve_state = DISSOLVED_CURVE_STATE.load(deps.storage)?;
+    ensure_owner(deps.as_ref(), &info)?;
+    let new_owner = deps.api.addr_validate(&new_owner)?;
+    OWNERSHIP.update(deps.storage, |mut ownership| -> StdResult<_> {
+        ownership.pending_owner = Some(new_owner.clone());
+        Ok(ownership)
+    })?;
 
-    let (dissolved_reserve_cost, next_dissolved_curve_state) =
-        calc_buy_exact_out(dissolved_curve_state, curve_fn, paid)?;
+    Ok(Response::new()
+        .add_attribute("method", "propose_new_owner")
+        .add_attribute("pending_owner", new_owner))
+}
 
-    DISSOLVED_CURVE_STATE.save(deps.storage, &next_dissolved_curve_state)?;
+pub fn execute_accept_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let mut ownership = OWNERSHIP.load(deps.storage)?;
+    let pending_owner = ownership
+        .pending_owner
+        .take()
+        .ok_or(ContractError::NoPendingOwner {})?;
+    ensure!(pending_owner == info.sender, ContractError::Unauthorized {});
+    ownership.owner = Some(pending_owner);
+    OWNERSHIP.save(deps.storage, &ownership)?;
 
-    let mut messages: Vec<cosmwasm_std::CosmosMsg> = vec![];
+    Ok(Response::new()
+        .add_attribute("method", "accept_ownership")
+        .add_attribute("owner", info.sender))
+}
 
-    if dissolved_reserve_cost > Uint128::zero() {
-        messages.push(
-            BankMsg::Send {
-                to_address: info.sender.to_string(),
-                amount: coins(
-                    dissolved_reserve_cost.u128(),
-                    curve_state.reserve_denom.clone(),
-                ),
-            }
-            .into(),
-        );
-    }
+pub fn execute_renounce_ownership(
+    deps: DepsMut,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    OWNERSHIP.save(
+        deps.storage,
+        &Ownership {
+            owner: None,
+            pending_owner: None,
+        },
+    )?;
 
     Ok(Response::new()
-        .add_attribute("method", "dissolve")
-        .add_attribute("sender", info.sender)
-        .add_attribute("reserve_denom", curve_state.reserve_denom)
-        .add_attribute("supply_denom", curve_state.supply_denom)
-        .add_attribute("dissolved", paid)
-        .add_attribute("distributed", dissolved_reserve_cost)
-        .add_messages(messages))
+        .add_attribute("method", "renounce_ownership")
+        .add_attribute("previous_owner", info.sender))
 }
 
-/// Handling contract execution
-#[cfg_attr(not(feature = "library"), entry_point)]
-pub fn sudo(deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
-    // if the message is not SetActive, ensure that the contract is active
-    if !matches!(msg, SudoMsg::SetActive { .. }) && !IS_ACTIVE.load(deps.storage)? {
-        return Err(ContractError::Std(StdError::generic_err(
-            "Contract is not active",
-        )));
+pub fn execute_update_fee_config(
+    deps: DepsMut,
+    info: MessageInfo,
+    swap_fee: Option<Decimal>,
+    fee_collector: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    let mut fee_config = FEE_CONFIG.load(deps.storage)?;
+    if let Some(swap_fee) = swap_fee {
+        ensure!(
+            swap_fee < Decimal::one(),
+            ContractError::InvalidSwapFee { swap_fee }
+        );
+        fee_config.swap_fee = swap_fee;
     }
-    match msg {
-        SudoMsg::SwapExactAmountIn {
-            sender,
-            token_in,
-            token_out_denom,
-            token_out_min_amount,
-            swap_fee,
-        } => execute_swap_exact_amount_in(
-            deps,
-            env,
-            sender,
-            token_in,
-            token_out_denom,
-            token_out_min_amount,
-            swap_fee,
-        ),
-        SudoMsg::SwapExactAmountOut {
-            sender,
-            token_in_denom,
-            token_in_max_amount,
-            token_out,
-            swap_fee,
-        } => execute_swap_exact_amount_out(
-            deps,
-            env,
-            sender,
-            token_in_denom,
-            token_in_max_amount,
-            token_out,
-            swap_fee,
-        ),
-        SudoMsg::SetActive { is_active } => execute_set_active(deps, env, is_active),
-        // Find matched incoming message variant and execute them with your custom logic.
-        //
-        // With `Response` type, it is possible to dispatch message to invoke external logic.
-        // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+    if let Some(fee_collector) = fee_collector {
+        fee_config.fee_collector = deps.api.addr_validate(&fee_collector)?;
     }
+    FEE_CONFIG.save(deps.storage, &fee_config)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "update_fee_config")
+        .add_attribute("swap_fee", fee_config.swap_fee.to_string())
+        .add_attribute("fee_collector", fee_config.fee_collector))
 }
 
-pub fn execute_set_active(
+pub fn execute_update_sale_phases(
     deps: DepsMut,
-    _env: Env,
-    is_active: bool,
+    info: MessageInfo,
+    phases: Vec<SalePhase>,
 ) -> Result<Response, ContractError> {
-    IS_ACTIVE.save(deps.storage, &is_active)?;
+    ensure_owner(deps.as_ref(), &info)?;
+    validate_sale_phases(&phases)?;
+    SALE_PHASES.save(deps.storage, &phases)?;
 
     Ok(Response::new()
-        .add_attribute("method", "set_active")
-        .add_attribute("is_active", is_active.to_string()))
+        .add_attribute("method", "update_sale_phases")
+        .add_attribute("phases", phases.len().to_string()))
 }
 
-/// SwapExactAmountIn swaps an exact amount of tokens in for as many tokens out as possible.
-/// The amount of tokens out is determined by the current exchange rate and the swap fee.
-/// The user specifies a minimum amount of tokens out, and the transaction will revert if that amount of tokens
-/// is not received.
-pub fn execute_swap_exact_amount_in(
+pub fn execute_register_merkle_root(
     deps: DepsMut,
-    env: Env,
-    sender: String,
-    token_in: Coin,
-    token_out_denom: String,
-    token_out_min_amount: Uint128,
-    swap_fee: Decimal,
+    info: MessageInfo,
+    merkle_root: String,
 ) -> Result<Response, ContractError> {
-    let (token_out_amount, curve) = calc_swap_exact_amount_in(
-        token_in,
-        token_out_denom.clone(),
-        swap_fee,
-        CURVE_STATE.load(deps.storage)?,
-        CURVE_TYPE.load(deps.storage)?,
-    )?;
-
-    if token_out_amount < token_out_min_amount {
-        return Err(ContractError::Std(StdError::generic_err(
-            "insufficient output amount",
-        )));
-    }
+    ensure_owner(deps.as_ref(), &info)?;
+    decode_hash(&merkle_root)?;
+    MERKLE_ROOT.save(deps.storage, &merkle_root)?;
 
-    CURVE_STATE.save(deps.storage, &curve)?;
+    Ok(Response::new()
+        .add_attribute("method", "register_merkle_root")
+        .add_attribute("merkle_root", merkle_root))
+}
 
-    let send_msg = mint_or_send(
-        curve.supply_denom,
-        token_out_denom,
-        token_out_amount,
-        sender,
-        env.contract.address.to_string(),
+/// Verifies the merkle allocation of `address` and keeps it for its later buys
+fn prove_allocation(
+    storage: &mut dyn Storage,
+    address: &str,
+    proof: AllowlistProof,
+) -> Result<(), ContractError> {
+    let merkle_root = MERKLE_ROOT
+        .may_load(storage)?
+        .ok_or(ContractError::NoMerkleRoot {})?;
+    ensure!(
+        verify_merkle_proof(&merkle_root, address, proof.allocation, &proof.proof)?,
+        ContractError::InvalidMerkleProof {}
     );
+    MERKLE_ALLOCATIONS.save(
+        storage,
+        &Addr::unchecked(address),
+        &MerkleAllocation {
+            merkle_root,
+            allocation: proof.allocation,
+        },
+    )?;
+    Ok(())
+}
 
-    let swap_result = SwapExactAmountInResponseData { token_out_amount };
+pub fn execute_update_allowlist(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    for address in &add {
+        ALLOWLIST.save(deps.storage, &deps.api.addr_validate(address)?, &Empty {})?;
+    }
+    for address in &remove {
+        ALLOWLIST.remove(deps.storage, &deps.api.addr_validate(address)?);
+    }
 
     Ok(Response::new()
-        .add_attribute("method", "swap_exact_amount_in")
-        .add_message(send_msg)
-        .set_data(to_binary(&swap_result)?))
+        .add_attribute("method", "update_allowlist")
+        .add_attribute("added", add.len().to_string())
+        .add_attribute("removed", remove.len().to_string()))
 }
 
-/// SwapExactAmountOut swaps as many tokens in as possible for an exact amount of tokens out.
-/// The amount of tokens in is determined by the current exchange rate and the swap fee.
-/// The user specifies a maximum amount of tokens in, and the transaction will revert if that amount of tokens
-/// is exceeded.
-pub fn execute_swap_exact_amount_out(
+pub fn execute_update_circuit_breaker(
     deps: DepsMut,
-    _env: Env,
-    sender: String,
-    token_in_denom: String,
-    token_in_max_amount: Uint128,
+    info: MessageInfo,
+    circuit_breaker: Option<CircuitBreakerConfig>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    match &circuit_breaker {
+        Some(circuit_breaker) => {
+            validate_circuit_breaker(circuit_breaker)?;
+            CIRCUIT_BREAKER_CONFIG.save(deps.storage, circuit_breaker)?;
+        }
+        None => CIRCUIT_BREAKER_CONFIG.remove(deps.storage),
+    }
+    // the next swap starts a window under the new limits
+    BREAKER_WINDOW.remove(deps.storage);
+
+    Ok(Response::new()
+        .add_attribute("method", "update_circuit_breaker")
+        .add_attribute("enabled", circuit_breaker.is_some().to_string()))
+}
+
+pub fn execute_update_max_per_address(
+    deps: DepsMut,
+    info: MessageInfo,
+    max_per_address: Option<Uint128>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    match max_per_address {
+        Some(max_per_address) => {
+            ensure!(
+                !max_per_address.is_zero(),
+                ContractError::InvalidMaxPerAddress {}
+            );
+            MAX_PER_ADDRESS.save(deps.storage, &max_per_address)?;
+        }
+        None => MAX_PER_ADDRESS.remove(deps.storage),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_max_per_address")
+        .add_attribute(
+            "max_per_address",
+            max_per_address.map_or("none".to_string(), |max| max.to_string()),
+        ))
+}
+
+pub fn execute_update_wallet_exemptions(
+    deps: DepsMut,
+    info: MessageInfo,
+    add: Vec<String>,
+    remove: Vec<String>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    let purchases = WalletPurchaseState::default().purchases;
+    for (addresses, exempt) in [(&add, true), (&remove, false)] {
+        for address in addresses {
+            let address = deps.api.addr_validate(address)?;
+            let mut purchase = load_wallet_purchase(deps.storage, &address)?;
+            purchase.exempt = exempt;
+            purchases.save(deps.storage, &address, &purchase)?;
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_wallet_exemptions")
+        .add_attribute("added", add.len().to_string())
+        .add_attribute("removed", remove.len().to_string()))
+}
+
+pub fn execute_update_trade_history(
+    deps: DepsMut,
+    info: MessageInfo,
+    trade_history: Option<TradeHistoryConfig>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    match &trade_history {
+        Some(trade_history) => {
+            validate_trade_history(trade_history)?;
+            TRADE_HISTORY_CONFIG.save(deps.storage, trade_history)?;
+        }
+        None => TRADE_HISTORY_CONFIG.remove(deps.storage),
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "update_trade_history")
+        .add_attribute("bounded", trade_history.is_some().to_string()))
+}
+
+/// Hands the supply denom admin over once the pool no longer needs to mint or burn
+pub fn execute_transfer_denom_admin(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    new_admin: String,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    ensure!(is_retired(deps.storage)?, ContractError::PoolNotRetired {});
+    let new_admin = deps.api.addr_validate(&new_admin)?;
+    let supply_denom = CURVE_STATE.load(deps.storage)?.supply_denom;
+    let (msg, event) = change_denom_admin(&env, supply_denom, &new_admin);
+
+    Ok(Response::new()
+        .add_message(msg)
+        .add_event(event)
+        .add_attribute("method", "transfer_denom_admin"))
+}
+
+/// Stops swaps for good and settles the reserve according to the retire policy
+pub fn execute_retire_pool(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    denom_admin: Option<String>,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    ensure!(!is_retired(deps.storage)?, ContractError::PoolRetired {});
+    let denom_admin = denom_admin
+        .map(|denom_admin| deps.api.addr_validate(&denom_admin))
+        .transpose()?;
+    IS_RETIRED.save(deps.storage, &true)?;
+    pause_swaps(deps.storage)?;
+
+    let mut curve_state = CURVE_STATE.load(deps.storage)?;
+    let mut event = Event::new("bonding_pool_retired")
+        .add_attribute("supply_denom", curve_state.supply_denom.clone())
+        .add_attribute("supply", curve_state.supply)
+        .add_attribute("reserve_denom", curve_state.reserve_denom.clone())
+        .add_attribute("reserve", curve_state.reserve);
+    let mut response = Response::new();
+    match RETIRE_POLICY.may_load(deps.storage)?.unwrap_or_default() {
+        RetirePolicy::KeepReserve => {
+            event = event.add_attribute("retire_policy", "keep_reserve");
+        }
+        RetirePolicy::SendReserve { recipient } => {
+            event = event
+                .add_attribute("retire_policy", "send_reserve")
+                .add_attribute("reserve_recipient", recipient.clone());
+            if !curve_state.reserve.is_zero() {
+                response = response.add_message(send_reserve(
+                    curve_state.reserve_denom.clone(),
+                    curve_state.reserve,
+                    recipient.to_string(),
+                    &load_reserve_asset(deps.storage)?,
+                )?);
+                curve_state.reserve = Uint128::zero();
+                CURVE_STATE.save(deps.storage, &curve_state)?;
+            }
+        }
+    }
+    response = response.add_event(event);
+
+    if let Some(denom_admin) = denom_admin {
+        let (msg, event) = change_denom_admin(&env, curve_state.supply_denom, &denom_admin);
+        response = response.add_message(msg).add_event(event);
+    }
+
+    Ok(response.add_attribute("method", "retire_pool"))
+}
+
+fn change_denom_admin(env: &Env, supply_denom: String, new_admin: &Addr) -> (CosmosMsg, Event) {
+    let event = Event::new("denom_admin_transferred")
+        .add_attribute("denom", supply_denom.clone())
+        .add_attribute("new_admin", new_admin);
+    let msg = MsgChangeAdmin {
+        sender: env.contract.address.to_string(),
+        denom: supply_denom,
+        new_admin: new_admin.to_string(),
+    }
+    .into();
+    (msg, event)
+}
+
+/// Overwrites the supply token metadata with the fields set in `update`
+pub fn execute_update_denom_metadata(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    update: DenomMetadata,
+) -> Result<Response, ContractError> {
+    ensure_owner(deps.as_ref(), &info)?;
+    let mut metadata = DENOM_METADATA.may_load(deps.storage)?.unwrap_or_default();
+    metadata.name = update.name.or(metadata.name);
+    metadata.symbol = update.symbol.or(metadata.symbol);
+    metadata.description = update.description.or(metadata.description);
+    metadata.uri = update.uri.or(metadata.uri);
+    DENOM_METADATA.save(deps.storage, &metadata)?;
+
+    let curve_state = CURVE_STATE.load(deps.storage)?;
+    Ok(Response::new()
+        .add_message(set_denom_metadata_msg(
+            curve_state.supply_denom,
+            curve_state.decimals.supply,
+            &metadata,
+            env.contract.address.to_string(),
+        ))
+        .add_attribute("method", "update_denom_metadata"))
+}
+
+pub fn execute_simulate(
+    deps: DepsMut,
+    _env: Env,
+    msg: SimulationMsg,
+) -> Result<Response, ContractError> {
+    match msg {
+        SimulationMsg::SetState { state } => {
+            CURVE_STATE.save(deps.storage, &state.curve_state)?;
+            DISSOLVED_CURVE_STATE.save(deps.storage, &state.dissolved_curve_state)?;
+            state.curve_type.validate()?;
+            CURVE_TYPE.save(deps.storage, &state.curve_type)?;
+            PAUSE_STATE.save(
+                deps.storage,
+                &PauseState {
+                    buys: !state.is_active,
+                    sells: !state.is_active,
+                    dissolve: false,
+                },
+            )?;
+            RESERVE_ASSET.save(deps.storage, &state.reserve_asset)?;
+            Ok(Response::new().add_attribute("method", "simulate"))
+        }
+    }
+}
+
+pub fn execute_dissolve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure!(
+        !load_pause_state(deps.storage)?.dissolve,
+        ContractError::Paused {
+            operation: "dissolve".to_string()
+        }
+    );
+    // contributors are refunded from the reserve until the crowdfund is funded
+    match crowdfund_status(deps.storage, &env)? {
+        None | Some(CrowdfundStatus::Funded) => {}
+        Some(CrowdfundStatus::Open) => return Err(ContractError::CrowdfundNotFunded {}),
+        Some(CrowdfundStatus::Failed) => return Err(ContractError::CrowdfundFailed {}),
+    }
+    let curve_state = CURVE_STATE.load(deps.storage)?;
+    let curve = CURVE_TYPE.load(deps.storage)?;
+    let curve_fn = curve.to_curve_fn();
+    one_coin(&info)?;
+    let paid = must_pay(&info, &curve_state.supply_denom)?;
+    let dissolved_curve_state = DISSOLVED_CURVE_STATE.load(deps.storage)?;
+    let price_before = spot_price(deps.storage, &dissolved_curve_state)?;
+
+    let (dissolved_reserve_cost, next_dissolved_curve_state) =
+        calc_buy_exact_out(dissolved_curve_state, curve_fn, paid)?;
+
+    DISSOLVED_CURVE_STATE.save(deps.storage, &next_dissolved_curve_state)?;
+    let price_after = spot_price(deps.storage, &next_dissolved_curve_state)?;
+    record_trade(
+        deps.storage,
+        &env,
+        info.sender.clone(),
+        TradeDirection::Dissolve,
+        (paid, dissolved_reserve_cost),
+        (price_before, price_after),
+    )?;
+
+    let mut messages: Vec<cosmwasm_std::CosmosMsg> = vec![];
+
+    if dissolved_reserve_cost > Uint128::zero() {
+        messages.push(send_reserve(
+            curve_state.reserve_denom.clone(),
+            dissolved_reserve_cost,
+            info.sender.to_string(),
+            &load_reserve_asset(deps.storage)?,
+        )?);
+    }
+
+    Ok(Response::new()
+        .add_attribute("method", "dissolve")
+        .add_attribute("sender", info.sender)
+        .add_attribute("reserve_denom", curve_state.reserve_denom)
+        .add_attribute("supply_denom", curve_state.supply_denom)
+        .add_attribute("dissolved", paid)
+        .add_attribute("distributed", dissolved_reserve_cost)
+        .add_messages(messages))
+}
+
+/// Handling contract execution
+#[cfg_attr(not(feature = "library"), entry_point)]
+pub fn sudo(mut deps: DepsMut, env: Env, msg: SudoMsg) -> Result<Response, ContractError> {
+    match msg {
+        SudoMsg::SwapExactAmountIn {
+            sender,
+            token_in,
+            token_out_denom,
+            token_out_min_amount,
+            swap_fee,
+        } => {
+            let swap = execute_swap_exact_amount_in(
+                deps.branch(),
+                env.clone(),
+                sender.clone(),
+                sender.clone(),
+                token_in.clone(),
+                token_out_denom,
+                token_out_min_amount,
+                swap_fee,
+            );
+            trip_circuit_breaker(
+                deps.storage,
+                &env,
+                swap,
+                Some((sender, token_in)),
+                to_binary(&SwapExactAmountInResponseData {
+                    token_out_amount: Uint128::zero(),
+                })?,
+            )
+        }
+        SudoMsg::SwapExactAmountOut {
+            sender,
+            token_in_denom,
+            token_in_max_amount,
+            token_out,
+            swap_fee,
+        } => {
+            let swap = execute_swap_exact_amount_out(
+                deps.branch(),
+                env.clone(),
+                sender,
+                token_in_denom,
+                token_in_max_amount,
+                token_out,
+                swap_fee,
+            );
+            // the module returns all of `token_in_max_amount` when none of it is used
+            trip_circuit_breaker(
+                deps.storage,
+                &env,
+                swap,
+                None,
+                to_binary(&SwapExactAmountOutResponseData {
+                    token_in_amount: Uint128::zero(),
+                })?,
+            )
+        }
+        SudoMsg::SetActive { is_active } => {
+            if let Some(trip) = BREAKER_TRIP.may_load(deps.storage)? {
+                ensure!(
+                    !is_active,
+                    ContractError::CircuitBreakerTripped {
+                        reason: trip.reason
+                    }
+                );
+            }
+            execute_set_active(deps, env, is_active)
+        } // Find matched incoming message variant and execute them with your custom logic.
+          //
+          // With `Response` type, it is possible to dispatch message to invoke external logic.
+          // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+    }
+}
+
+pub fn execute_set_active(
+    deps: DepsMut,
+    _env: Env,
+    is_active: bool,
+) -> Result<Response, ContractError> {
+    let pause_state = PauseState {
+        buys: !is_active,
+        sells: !is_active,
+        ..load_pause_state(deps.storage)?
+    };
+    Ok(execute_update_pause_state(deps, pause_state)?
+        .add_attribute("method", "set_active")
+        .add_attribute("is_active", is_active.to_string()))
+}
+
+pub fn execute_update_pause_state(
+    deps: DepsMut,
+    pause_state: PauseState,
+) -> Result<Response, ContractError> {
+    // a retired pool may no longer be able to mint or burn the supply token
+    ensure!(
+        (pause_state.buys && pause_state.sells) || !is_retired(deps.storage)?,
+        ContractError::PoolRetired {}
+    );
+    let prev_state = load_pause_state(deps.storage)?;
+    PAUSE_STATE.save(deps.storage, &pause_state)?;
+    if pause_state.is_active() {
+        BREAKER_TRIP.remove(deps.storage);
+        BREAKER_WINDOW.remove(deps.storage);
+    }
+
+    let switches = [
+        ("buys", prev_state.buys, pause_state.buys),
+        ("sells", prev_state.sells, pause_state.sells),
+        ("dissolve", prev_state.dissolve, pause_state.dissolve),
+    ];
+    let events = switches
+        .into_iter()
+        .filter(|(_, was_paused, paused)| was_paused != paused)
+        .map(|(switch, _, paused)| {
+            Event::new("pause_switch")
+                .add_attribute("switch", switch)
+                .add_attribute("paused", paused.to_string())
+        });
+    Ok(Response::new()
+        .add_attribute("method", "update_pause_state")
+        .add_events(events))
+}
+
+/// Pauses buys and sells, leaving dissolves as they are
+fn pause_swaps(storage: &mut dyn Storage) -> StdResult<()> {
+    let pause_state = load_pause_state(storage)?;
+    PAUSE_STATE.save(
+        storage,
+        &PauseState {
+            buys: true,
+            sells: true,
+            ..pause_state
+        },
+    )
+}
+
+/// Refuses swaps in a paused direction. Pools paused in both directions keep the error of the
+/// former single active flag.
+fn ensure_swap_not_paused(storage: &dyn Storage, is_buy: bool) -> Result<(), ContractError> {
+    let pause_state = load_pause_state(storage)?;
+    if !pause_state.is_active() {
+        return Err(ContractError::Std(StdError::generic_err(
+            "Contract is not active",
+        )));
+    }
+    let (paused, operation) = if is_buy {
+        (pause_state.buys, "buys")
+    } else {
+        (pause_state.sells, "sells")
+    };
+    ensure!(
+        !paused,
+        ContractError::Paused {
+            operation: operation.to_string()
+        }
+    );
+    Ok(())
+}
+
+/// The swap fee passed by the cosmwasmpool module may not undercut the fee configured for the pool.
+fn ensure_swap_fee(storage: &dyn Storage, swap_fee: Decimal) -> Result<(), ContractError> {
+    let pool_swap_fee = FEE_CONFIG.load(storage)?.swap_fee;
+    ensure!(
+        swap_fee >= pool_swap_fee,
+        ContractError::SwapFeeBelowPoolFee {
+            swap_fee,
+            pool_swap_fee,
+        }
+    );
+    Ok(())
+}
+
+/// Forwards a collected swap fee to the fee collector
+fn fee_msg(
+    storage: &dyn Storage,
+    fee: Uint128,
+    reserve_denom: String,
+) -> StdResult<Option<CosmosMsg>> {
+    if fee.is_zero() {
+        return Ok(None);
+    }
+    send_reserve(
+        reserve_denom,
+        fee,
+        FEE_CONFIG.load(storage)?.fee_collector.to_string(),
+        &load_reserve_asset(storage)?,
+    )
+    .map(Some)
+}
+
+/// Buys supply tokens with the reserve tokens in `info.funds`, priced like a `SwapExactAmountIn`
+/// sent by the cosmwasmpool module at the pool's swap fee.
+pub fn execute_buy(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    min_out: Uint128,
+    recipient: Option<String>,
+    deadline: Option<Timestamp>,
+    allowlist_proof: Option<AllowlistProof>,
+) -> Result<Response, ContractError> {
+    let recipient = ensure_standalone_swap(deps.as_ref(), &env, &info.sender, recipient, deadline)?;
+    if let Some(allowlist_proof) = allowlist_proof {
+        prove_allocation(deps.storage, info.sender.as_str(), allowlist_proof)?;
+    }
+    let curve_state = CURVE_STATE.load(deps.storage)?;
+    let token_in = coin(
+        must_pay(&info, &curve_state.reserve_denom)?.u128(),
+        curve_state.reserve_denom,
+    );
+    let swap_fee = FEE_CONFIG.load(deps.storage)?.swap_fee;
+    let swap = execute_swap_exact_amount_in(
+        deps.branch(),
+        env.clone(),
+        info.sender.to_string(),
+        recipient,
+        token_in.clone(),
+        curve_state.supply_denom,
+        min_out,
+        swap_fee,
+    );
+    trip_circuit_breaker(
+        deps.storage,
+        &env,
+        swap,
+        Some((info.sender.to_string(), token_in)),
+        to_binary(&SwapExactAmountInResponseData {
+            token_out_amount: Uint128::zero(),
+        })?,
+    )
+}
+
+/// Sells the supply tokens in `info.funds` for reserve tokens, priced like a `SwapExactAmountIn`
+/// sent by the cosmwasmpool module at the pool's swap fee.
+pub fn execute_sell(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    min_out: Uint128,
+    recipient: Option<String>,
+    deadline: Option<Timestamp>,
+) -> Result<Response, ContractError> {
+    let recipient = ensure_standalone_swap(deps.as_ref(), &env, &info.sender, recipient, deadline)?;
+    let curve_state = CURVE_STATE.load(deps.storage)?;
+    let token_in = coin(
+        must_pay(&info, &curve_state.supply_denom)?.u128(),
+        curve_state.supply_denom,
+    );
+    let swap_fee = FEE_CONFIG.load(deps.storage)?.swap_fee;
+    let swap = execute_swap_exact_amount_in(
+        deps.branch(),
+        env.clone(),
+        info.sender.to_string(),
+        recipient,
+        token_in.clone(),
+        curve_state.reserve_denom,
+        min_out,
+        swap_fee,
+    );
+    trip_circuit_breaker(
+        deps.storage,
+        &env,
+        swap,
+        Some((info.sender.to_string(), token_in)),
+        to_binary(&SwapExactAmountInResponseData {
+            token_out_amount: Uint128::zero(),
+        })?,
+    )
+}
+
+/// Buys with the cw20 reserve tokens sent to the pool, priced like `execute_buy`
+pub fn execute_receive(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    cw20_msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let curve_state = CURVE_STATE.load(deps.storage)?;
+    ensure!(
+        load_reserve_asset(deps.storage)? == ReserveAsset::Cw20
+            && info.sender == curve_state.reserve_denom,
+        ContractError::InvalidCw20Token {
+            token: info.sender.to_string()
+        }
+    );
+    match from_binary(&cw20_msg.msg)? {
+        ReceiveMsg::Buy {
+            min_out,
+            recipient,
+            deadline,
+            allowlist_proof,
+        } => {
+            let sender = deps.api.addr_validate(&cw20_msg.sender)?;
+            let recipient =
+                ensure_standalone_swap(deps.as_ref(), &env, &sender, recipient, deadline)?;
+            if let Some(allowlist_proof) = allowlist_proof {
+                prove_allocation(deps.storage, sender.as_str(), allowlist_proof)?;
+            }
+            let token_in = coin(cw20_msg.amount.u128(), curve_state.reserve_denom);
+            let swap_fee = FEE_CONFIG.load(deps.storage)?.swap_fee;
+            let swap = execute_swap_exact_amount_in(
+                deps.branch(),
+                env.clone(),
+                sender.to_string(),
+                recipient,
+                token_in.clone(),
+                curve_state.supply_denom,
+                min_out,
+                swap_fee,
+            );
+            trip_circuit_breaker(
+                deps.storage,
+                &env,
+                swap,
+                Some((sender.to_string(), token_in)),
+                to_binary(&SwapExactAmountInResponseData {
+                    token_out_amount: Uint128::zero(),
+                })?,
+            )
+        }
+    }
+}
+
+/// Checks a `Buy` or `Sell` can go ahead and returns who receives its output
+fn ensure_standalone_swap(
+    deps: Deps,
+    env: &Env,
+    sender: &Addr,
+    recipient: Option<String>,
+    deadline: Option<Timestamp>,
+) -> Result<String, ContractError> {
+    if let Some(deadline) = deadline {
+        ensure!(
+            env.block.time <= deadline,
+            ContractError::DeadlineExceeded { deadline }
+        );
+    }
+    match recipient {
+        Some(recipient) => Ok(deps.api.addr_validate(&recipient)?.to_string()),
+        None => Ok(sender.to_string()),
+    }
+}
+
+/// SwapExactAmountIn swaps an exact amount of tokens in for as many tokens out as possible.
+/// The amount of tokens out is determined by the current exchange rate and the swap fee.
+/// The user specifies a minimum amount of tokens out, and the transaction will revert if that amount of tokens
+/// is not received.
+/// `sender` pays and is charged against per-wallet limits, `recipient` receives the tokens out.
+#[allow(clippy::too_many_arguments)]
+pub fn execute_swap_exact_amount_in(
+    mut deps: DepsMut,
+    env: Env,
+    sender: String,
+    recipient: String,
+    token_in: Coin,
+    token_out_denom: String,
+    token_out_min_amount: Uint128,
+    swap_fee: Decimal,
+) -> Result<Response, ContractError> {
+    ensure_swap_fee(deps.storage, swap_fee)?;
+    let prev_curve = CURVE_STATE.load(deps.storage)?;
+    let is_buy = token_out_denom == prev_curve.supply_denom;
+    ensure_swap_not_paused(deps.storage, is_buy)?;
+    if is_buy {
+        ensure_sale_phase(deps.storage, &env, &sender)?;
+    }
+    let swap_fee = phase_swap_fee(deps.storage, &env, is_buy, swap_fee)?;
+    let SwapCalculation {
+        amount: token_out_amount,
+        fee,
+        refund,
+        state: curve,
+    } = calc_swap_exact_amount_in(
+        token_in.clone(),
+        token_out_denom.clone(),
+        swap_fee,
+        prev_curve.clone(),
+        CURVE_TYPE.load(deps.storage)?,
+    )?;
+
+    if token_out_amount < token_out_min_amount {
+        return Err(ContractError::Std(StdError::generic_err(
+            "insufficient output amount",
+        )));
+    }
+
+    if let Some(reason) = check_circuit_breaker(deps.storage, &env, &prev_curve, &curve)? {
+        return Err(ContractError::CircuitBreakerLimit { reason });
+    }
+    if is_buy {
+        record_sale_phase_buy(deps.storage, &env, &sender, token_out_amount)?;
+        record_wallet_trade(deps.storage, &sender, is_buy, token_out_amount)?;
+    } else {
+        record_wallet_trade(deps.storage, &sender, is_buy, token_in.amount)?;
+    }
+    let (forward_fee, crowdfund_event) =
+        apply_crowdfund(deps.storage, &env, &recipient, &prev_curve, &curve, fee)?;
+    CURVE_STATE.save(deps.storage, &curve)?;
+    let prices = (
+        spot_price(deps.storage, &prev_curve)?,
+        record_price(deps.storage, &env, &curve)?,
+    );
+    record_trade(
+        deps.storage,
+        &env,
+        Addr::unchecked(&sender),
+        if is_buy {
+            TradeDirection::Buy
+        } else {
+            TradeDirection::Sell
+        },
+        (token_in.amount - refund, token_out_amount),
+        prices,
+    )?;
+
+    // burn supply tokens received on sells, mint supply tokens sent on buys
+    let reserve_asset = load_reserve_asset(deps.storage)?;
+    let coin_io_msgs = create_coin_io_messages(
+        token_in,
+        coin(token_out_amount.u128(), token_out_denom),
+        curve.supply_denom.clone(),
+        recipient,
+        env.contract.address.to_string(),
+        &reserve_asset,
+    )?;
+
+    let swap_result = SwapExactAmountInResponseData { token_out_amount };
+
+    let mut response = Response::new()
+        .add_attribute("method", "swap_exact_amount_in")
+        .add_attribute("fee", fee)
+        .add_messages(coin_io_msgs)
+        .add_messages(fee_msg(
+            deps.storage,
+            forward_fee,
+            curve.reserve_denom.clone(),
+        )?)
+        .add_events(crowdfund_event);
+    response = graduate_at_threshold(deps.branch(), &env, response)?;
+
+    // buys that cross `max_supply` only spend part of `token_in`
+    if !refund.is_zero() {
+        response = response
+            .add_attribute("refund", refund)
+            .add_message(send_reserve(
+                curve.reserve_denom,
+                refund,
+                sender,
+                &reserve_asset,
+            )?);
+    }
+
+    Ok(response.set_data(to_binary(&swap_result)?))
+}
+
+/// SwapExactAmountOut swaps as many tokens in as possible for an exact amount of tokens out.
+/// The amount of tokens in is determined by the current exchange rate and the swap fee.
+/// The user specifies a maximum amount of tokens in, and the transaction will revert if that amount of tokens
+/// is exceeded.
+pub fn execute_swap_exact_amount_out(
+    deps: DepsMut,
+    env: Env,
+    sender: String,
+    token_in_denom: String,
+    token_in_max_amount: Uint128,
     token_out: Coin,
     swap_fee: Decimal,
 ) -> Result<Response, ContractError> {
-    let (token_in_amount, state) = calc_swap_exact_amount_out(
-        token_in_denom,
+    ensure_swap_fee(deps.storage, swap_fee)?;
+    let prev_state = CURVE_STATE.load(deps.storage)?;
+    let is_buy = token_out.denom == prev_state.supply_denom;
+    ensure_swap_not_paused(deps.storage, is_buy)?;
+    if is_buy {
+        ensure_sale_phase(deps.storage, &env, &sender)?;
+    }
+    let swap_fee = phase_swap_fee(deps.storage, &env, is_buy, swap_fee)?;
+    let SwapCalculation {
+        amount: token_in_amount,
+        fee,
+        state,
+        ..
+    } = calc_swap_exact_amount_out(
+        token_in_denom.clone(),
         token_out.clone(),
         swap_fee,
-        CURVE_STATE.load(deps.storage)?,
+        prev_state.clone(),
         CURVE_TYPE.load(deps.storage)?,
     )?;
 
-    CURVE_STATE.save(deps.storage, &state)?;
-
     if token_in_amount > token_in_max_amount {
         return Err(ContractError::Std(StdError::generic_err(
             "token in amount exceeds max amount",
         )));
     };
-    let send_token_out_to_sender_msg = BankMsg::Send {
-        to_address: sender,
-        amount: vec![token_out],
-    };
+    if let Some(reason) = check_circuit_breaker(deps.storage, &env, &prev_state, &state)? {
+        return Err(ContractError::CircuitBreakerLimit { reason });
+    }
+    if is_buy {
+        record_sale_phase_buy(deps.storage, &env, &sender, token_out.amount)?;
+        record_wallet_trade(deps.storage, &sender, is_buy, token_out.amount)?;
+    } else {
+        record_wallet_trade(deps.storage, &sender, is_buy, token_in_amount)?;
+    }
+    let (forward_fee, crowdfund_event) =
+        apply_crowdfund(deps.storage, &env, &sender, &prev_state, &state, fee)?;
+    CURVE_STATE.save(deps.storage, &state)?;
+    let prices = (
+        spot_price(deps.storage, &prev_state)?,
+        record_price(deps.storage, &env, &state)?,
+    );
+    record_trade(
+        deps.storage,
+        &env,
+        Addr::unchecked(&sender),
+        if is_buy {
+            TradeDirection::Buy
+        } else {
+            TradeDirection::Sell
+        },
+        (token_in_amount, token_out.amount),
+        prices,
+    )?;
+
+    // burn supply tokens received on sells, mint supply tokens sent on buys
+    let coin_io_msgs = create_coin_io_messages(
+        coin(token_in_amount.u128(), token_in_denom),
+        token_out,
+        state.supply_denom.clone(),
+        sender,
+        env.contract.address.to_string(),
+        &load_reserve_asset(deps.storage)?,
+    )?;
 
     let swap_result = SwapExactAmountOutResponseData { token_in_amount };
 
-    Ok(Response::new()
+    let response = Response::new()
         .add_attribute("method", "swap_exact_amount_out")
-        .add_message(send_token_out_to_sender_msg)
-        .set_data(to_binary(&swap_result)?))
+        .add_attribute("fee", fee)
+        .add_messages(coin_io_msgs)
+        .add_messages(fee_msg(deps.storage, forward_fee, state.reserve_denom)?)
+        .add_events(crowdfund_event);
+    Ok(graduate_at_threshold(deps, &env, response)?.set_data(to_binary(&swap_result)?))
+}
+
+/// Adds a swap from `prev_state` to `state` to the circuit breaker window, or returns why the
+/// swap trips the breaker, in which case the window is left as is.
+fn check_circuit_breaker(
+    storage: &mut dyn Storage,
+    env: &Env,
+    prev_state: &CurveState,
+    state: &CurveState,
+) -> Result<Option<String>, ContractError> {
+    let config = match CIRCUIT_BREAKER_CONFIG.may_load(storage)? {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let curve_type = CURVE_TYPE.load(storage)?;
+    let spot_price = |state: &CurveState| {
+        calc_spot_price(
+            state.reserve_denom.clone(),
+            state.supply_denom.clone(),
+            state.clone(),
+            curve_type.clone(),
+        )
+    };
+    let mut window = match BREAKER_WINDOW.may_load(storage)? {
+        Some(window) if !breaker_window_ended(&config.window, &window, env) => window,
+        _ => BreakerWindowState {
+            start_height: env.block.height,
+            start_time: env.block.time,
+            start_price: spot_price(prev_state)?,
+            reserve_inflow: Uint128::zero(),
+            reserve_outflow: Uint128::zero(),
+        },
+    };
+    if state.reserve > prev_state.reserve {
+        window.reserve_inflow += state.reserve - prev_state.reserve;
+    } else {
+        window.reserve_outflow += prev_state.reserve - state.reserve;
+    }
+
+    if let Some(max_price_change) = config.max_price_change {
+        let price = spot_price(state)?;
+        // the change can't be measured from a zero price, eg. at the start of a linear curve
+        if !window.start_price.is_zero() {
+            let change = if price > window.start_price {
+                price - window.start_price
+            } else {
+                window.start_price - price
+            };
+            let change = change
+                .checked_div(window.start_price)
+                .unwrap_or(Decimal::MAX);
+            if change > max_price_change {
+                return Ok(Some(format!(
+                    "spot price changed by {} within the window, above {}",
+                    change, max_price_change
+                )));
+            }
+        }
+    }
+    if let Some(max_reserve_inflow) = config.max_reserve_inflow {
+        if window.reserve_inflow > max_reserve_inflow {
+            return Ok(Some(format!(
+                "reserve inflow of {} within the window, above {}",
+                window.reserve_inflow, max_reserve_inflow
+            )));
+        }
+    }
+    if let Some(max_reserve_outflow) = config.max_reserve_outflow {
+        if window.reserve_outflow > max_reserve_outflow {
+            return Ok(Some(format!(
+                "reserve outflow of {} within the window, above {}",
+                window.reserve_outflow, max_reserve_outflow
+            )));
+        }
+    }
+    BREAKER_WINDOW.save(storage, &window)?;
+    Ok(None)
+}
+
+fn breaker_window_ended(window: &BreakerWindow, state: &BreakerWindowState, env: &Env) -> bool {
+    match window {
+        BreakerWindow::Blocks { blocks } => env.block.height >= state.start_height + blocks,
+        BreakerWindow::Seconds { seconds } => {
+            env.block.time >= state.start_time.plus_seconds(*seconds)
+        }
+    }
+}
+
+/// Pauses the pool instead of a swap that exceeds the circuit breaker. The swap is answered with
+/// `no_swap`, the response data of a swap without output, and `refund` returns the tokens the
+/// pool already received. The cosmwasmpool module returns the unused `token_in_max_amount` of exact
+/// out swaps itself. It still rejects an exact in swap without output if `token_out_min_amount` is
+/// above zero, which reverts the pause along with it.
+fn trip_circuit_breaker(
+    storage: &mut dyn Storage,
+    env: &Env,
+    swap: Result<Response, ContractError>,
+    refund: Option<(String, Coin)>,
+    no_swap: Binary,
+) -> Result<Response, ContractError> {
+    let reason = match swap {
+        Err(ContractError::CircuitBreakerLimit { reason }) => reason,
+        swap => return swap,
+    };
+    let mut response = Response::new().add_attribute("method", "trip_circuit_breaker");
+    if let Some((sender, token_in)) = refund {
+        response = response.add_message(
+            if token_in.denom == CURVE_STATE.load(storage)?.supply_denom {
+                BankMsg::Send {
+                    to_address: sender,
+                    amount: vec![token_in],
+                }
+                .into()
+            } else {
+                send_reserve(
+                    token_in.denom,
+                    token_in.amount,
+                    sender,
+                    &load_reserve_asset(storage)?,
+                )?
+            },
+        );
+    }
+    pause_swaps(storage)?;
+    BREAKER_TRIP.save(
+        storage,
+        &CircuitBreakerTrip {
+            reason: reason.clone(),
+            height: env.block.height,
+            time: env.block.time,
+        },
+    )?;
+
+    Ok(response
+        .add_event(Event::new("circuit_breaker_tripped").add_attribute("reason", reason))
+        .set_data(no_swap))
+}
+
+fn load_wallet_purchase(storage: &dyn Storage, wallet: &Addr) -> StdResult<WalletPurchase> {
+    Ok(WalletPurchaseState::default()
+        .purchases
+        .may_load(storage, wallet)?
+        .unwrap_or(WalletPurchase {
+            address: wallet.clone(),
+            net_purchased: Uint128::zero(),
+            exempt: false,
+        }))
+}
+
+/// Adds a buy to the net purchases of `wallet`, up to `max_per_address` unless it is exempt,
+/// or takes a sell off them
+fn record_wallet_trade(
+    storage: &mut dyn Storage,
+    wallet: &str,
+    is_buy: bool,
+    supply_amount: Uint128,
+) -> Result<(), ContractError> {
+    let wallet = Addr::unchecked(wallet);
+    let mut purchase = load_wallet_purchase(storage, &wallet)?;
+    if is_buy {
+        purchase.net_purchased += supply_amount;
+        if let Some(max_per_address) = MAX_PER_ADDRESS.may_load(storage)? {
+            ensure!(
+                purchase.exempt || purchase.net_purchased <= max_per_address,
+                ContractError::WalletLimitExceeded { max_per_address }
+            );
+        }
+    } else if purchase.net_purchased.is_zero() {
+        return Ok(());
+    } else {
+        purchase.net_purchased = purchase.net_purchased.saturating_sub(supply_amount);
+    }
+    WalletPurchaseState::default()
+        .purchases
+        .save(storage, &wallet, &purchase)?;
+    Ok(())
+}
+
+/// Stores a trade under the next id, then prunes the oldest trades past the trade history bounds
+fn record_trade(
+    storage: &mut dyn Storage,
+    env: &Env,
+    trader: Addr,
+    direction: TradeDirection,
+    (amount_in, amount_out): (Uint128, Uint128),
+    (price_before, price_after): (Decimal, Decimal),
+) -> StdResult<()> {
+    let trades = TradeState::default().trades;
+    let mut ids = TRADE_IDS.may_load(storage)?.unwrap_or_default();
+    let trade = Trade {
+        id: ids.next,
+        trader,
+        direction,
+        amount_in,
+        amount_out,
+        price_before,
+        price_after,
+        height: env.block.height,
+        time: env.block.time,
+    };
+    trades.save(storage, trade.id, &trade)?;
+    ids.next += 1;
+
+    if let Some(config) = TRADE_HISTORY_CONFIG.may_load(storage)? {
+        let expiry = config
+            .max_age
+            .map(|max_age| env.block.time.seconds().saturating_sub(max_age));
+        for _ in 0..MAX_PRUNED_PER_TRADE {
+            let too_many = matches!(config.max_trades, Some(max) if ids.next - ids.oldest > max);
+            let expired = match expiry {
+                Some(expiry) => trades.load(storage, ids.oldest)?.time.seconds() < expiry,
+                None => false,
+            };
+            if !too_many && !expired {
+                break;
+            }
+            trades.remove(storage, ids.oldest)?;
+            ids.oldest += 1;
+        }
+    }
+    TRADE_IDS.save(storage, &ids)
+}
+
+/// The phase at `time` and the start of the next one. No phase before the first one starts.
+fn current_sale_phase(
+    phases: &[SalePhase],
+    time: Timestamp,
+) -> (Option<&SalePhase>, Option<Timestamp>) {
+    let next = phases.iter().position(|phase| phase.start > time);
+    let current = match next {
+        Some(0) => None,
+        Some(next) => phases.get(next - 1),
+        None => phases.last(),
+    };
+    (current, next.map(|next| phases[next].start))
+}
+
+/// Checks the current sale phase lets `buyer` buy. Buys are always open without a sale schedule.
+fn ensure_sale_phase(storage: &dyn Storage, env: &Env, buyer: &str) -> Result<(), ContractError> {
+    let phases = SALE_PHASES.may_load(storage)?.unwrap_or_default();
+    if phases.is_empty() {
+        return Ok(());
+    }
+    let phase = match current_sale_phase(&phases, env.block.time).0 {
+        Some(phase) => phase,
+        None => {
+            return Err(ContractError::SaleNotStarted {
+                start: phases[0].start,
+            })
+        }
+    };
+    match phase.access {
+        SaleAccess::Public => {}
+        SaleAccess::Allowlist => ensure!(
+            ALLOWLIST.has(storage, &Addr::unchecked(buyer))
+                || load_merkle_allocation(storage, &Addr::unchecked(buyer))?.is_some(),
+            ContractError::NotAllowlisted {
+                phase: phase.name.clone()
+            }
+        ),
+        SaleAccess::Closed => {
+            return Err(ContractError::SaleClosed {
+                phase: phase.name.clone(),
+            })
+        }
+    }
+    Ok(())
+}
+
+/// Price multiplier of the current sale phase, charged on buys on top of the curve price
+fn phase_price_multiplier(storage: &dyn Storage, env: &Env) -> StdResult<Option<Decimal>> {
+    let phases = SALE_PHASES.may_load(storage)?.unwrap_or_default();
+    Ok(current_sale_phase(&phases, env.block.time)
+        .0
+        .and_then(|phase| phase.price_multiplier))
+}
+
+/// The swap fee raised to also take the price premium of the current sale phase on buys. Swaps
+/// and their quotes are both priced with it.
+fn phase_swap_fee(
+    storage: &dyn Storage,
+    env: &Env,
+    is_buy: bool,
+    swap_fee: Decimal,
+) -> Result<Decimal, ContractError> {
+    match phase_price_multiplier(storage, env)? {
+        // buys pay `cost / (1 - fee)`, so `1 - fee` divided by the multiplier charges
+        // `cost * multiplier / (1 - swap_fee)`
+        Some(multiplier) if is_buy => {
+            Ok(Decimal::one() - (Decimal::one() - swap_fee).checked_div(multiplier)?)
+        }
+        _ => Ok(swap_fee),
+    }
+}
+
+/// Counts a buy toward the buyer's `max_per_wallet` in the current sale phase, and toward its
+/// merkle allocation during allowlist phases
+fn record_sale_phase_buy(
+    storage: &mut dyn Storage,
+    env: &Env,
+    buyer: &str,
+    bought: Uint128,
+) -> Result<(), ContractError> {
+    let phases = SALE_PHASES.may_load(storage)?.unwrap_or_default();
+    let phase = match current_sale_phase(&phases, env.block.time).0 {
+        Some(phase) => phase,
+        None => return Ok(()),
+    };
+    let buyer = Addr::unchecked(buyer);
+    if let Some(max_per_wallet) = phase.max_per_wallet {
+        let key = (phase.start.seconds(), &buyer);
+        let purchased = PHASE_PURCHASES.may_load(storage, key)?.unwrap_or_default() + bought;
+        ensure!(
+            purchased <= max_per_wallet,
+            ContractError::PhaseWalletCapExceeded {
+                phase: phase.name.clone(),
+                max_per_wallet,
+            }
+        );
+        PHASE_PURCHASES.save(storage, key, &purchased)?;
+    }
+    if phase.access == SaleAccess::Allowlist {
+        if let Some(allocation) = load_merkle_allocation(storage, &buyer)? {
+            let purchased = ALLOWLIST_PURCHASES
+                .may_load(storage, &buyer)?
+                .unwrap_or_default()
+                + bought;
+            ensure!(
+                purchased <= allocation,
+                ContractError::AllocationExceeded { allocation }
+            );
+            ALLOWLIST_PURCHASES.save(storage, &buyer, &purchased)?;
+        }
+    }
+    Ok(())
+}
+
+/// `None` for pools without a crowdfund
+fn crowdfund_status(storage: &dyn Storage, env: &Env) -> StdResult<Option<CrowdfundStatus>> {
+    let config = match CROWDFUND_CONFIG.may_load(storage)? {
+        Some(config) => config,
+        None => return Ok(None),
+    };
+    let status = if CROWDFUND_STATE
+        .may_load(storage)?
+        .unwrap_or_default()
+        .funded
+    {
+        CrowdfundStatus::Funded
+    } else if env.block.time > config.deadline {
+        CrowdfundStatus::Failed
+    } else {
+        CrowdfundStatus::Open
+    };
+    Ok(Some(status))
+}
+
+/// Crowdfund rules for a trade taking the curve from `prev` to `next`. While the crowdfund is open
+/// only buys are allowed. They are recorded for `holder`, who receives the supply and can send it
+/// back for a refund, and their swap fee is held in the pool so that refunds can return the full
+/// payment. Returns the fee to forward to the fee collector
+/// now, and the `crowdfund_funded` event of the buy reaching the target.
+fn apply_crowdfund(
+    storage: &mut dyn Storage,
+    env: &Env,
+    holder: &str,
+    prev: &CurveState,
+    next: &CurveState,
+    fee: Uint128,
+) -> Result<(Uint128, Option<Event>), ContractError> {
+    match crowdfund_status(storage, env)? {
+        None | Some(CrowdfundStatus::Funded) => return Ok((fee, None)),
+        Some(CrowdfundStatus::Failed) => return Err(ContractError::CrowdfundFailed {}),
+        Some(CrowdfundStatus::Open) => {}
+    }
+    ensure!(
+        next.supply > prev.supply,
+        ContractError::CrowdfundNotFunded {}
+    );
+
+    let holder = Addr::unchecked(holder);
+    let mut contribution = CONTRIBUTIONS
+        .may_load(storage, &holder)?
+        .unwrap_or_default();
+    contribution.supply += next.supply - prev.supply;
+    contribution.reserve += next.reserve - prev.reserve;
+    contribution.fee += fee;
+    CONTRIBUTIONS.save(storage, &holder, &contribution)?;
+
+    let mut state = CROWDFUND_STATE.may_load(storage)?.unwrap_or_default();
+    state.held_fees += fee;
+    let target_supply = CROWDFUND_CONFIG.load(storage)?.target_supply;
+    if next.supply < target_supply {
+        CROWDFUND_STATE.save(storage, &state)?;
+        return Ok((Uint128::zero(), None));
+    }
+    CROWDFUND_STATE.save(
+        storage,
+        &CrowdfundState {
+            funded: true,
+            held_fees: Uint128::zero(),
+        },
+    )?;
+    let event = Event::new("crowdfund_funded")
+        .add_attribute("supply", next.supply)
+        .add_attribute("target_supply", target_supply);
+    Ok((state.held_fees, Some(event)))
+}
+
+/// Burns supply bought during a failed crowdfund and refunds what was paid for it
+pub fn execute_claim_refund(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    ensure!(
+        crowdfund_status(deps.storage, &env)? == Some(CrowdfundStatus::Failed),
+        ContractError::CrowdfundNotFailed {}
+    );
+    let mut curve_state = CURVE_STATE.load(deps.storage)?;
+    let burned = must_pay(&info, &curve_state.supply_denom)?;
+    let mut contribution = CONTRIBUTIONS
+        .may_load(deps.storage, &info.sender)?
+        .unwrap_or_default();
+    ensure!(
+        burned <= contribution.supply,
+        ContractError::RefundExceedsContribution {
+            contributed: contribution.supply
+        }
+    );
+
+    // partial refunds round down, the last one takes what is left
+    let reserve = contribution
+        .reserve
+        .multiply_ratio(burned, contribution.supply);
+    let fee = contribution.fee.multiply_ratio(burned, contribution.supply);
+    contribution.supply -= burned;
+    contribution.reserve -= reserve;
+    contribution.fee -= fee;
+    if contribution.supply.is_zero() {
+        CONTRIBUTIONS.remove(deps.storage, &info.sender);
+    } else {
+        CONTRIBUTIONS.save(deps.storage, &info.sender, &contribution)?;
+    }
+
+    curve_state.supply = curve_state.supply.checked_sub(burned)?;
+    curve_state.reserve = curve_state.reserve.checked_sub(reserve)?;
+    CURVE_STATE.save(deps.storage, &curve_state)?;
+    record_price(deps.storage, &env, &curve_state)?;
+    let mut state = CROWDFUND_STATE.may_load(deps.storage)?.unwrap_or_default();
+    state.held_fees = state.held_fees.checked_sub(fee)?;
+    CROWDFUND_STATE.save(deps.storage, &state)?;
+
+    let refund = reserve + fee;
+    let mut response = Response::new()
+        .add_attribute("method", "claim_refund")
+        .add_attribute("burned", burned)
+        .add_attribute("refund", refund)
+        .add_messages(burn_or_receive(
+            curve_state.supply_denom.clone(),
+            curve_state.supply_denom,
+            burned,
+            env.contract.address.to_string(),
+        ));
+    if !refund.is_zero() {
+        response = response.add_message(send_reserve(
+            curve_state.reserve_denom,
+            refund,
+            info.sender.to_string(),
+            &load_reserve_asset(deps.storage)?,
+        )?);
+    }
+    Ok(response)
+}
+
+/// Graduates the pool once a buy takes the supply to the graduation threshold. Swaps stop for
+/// good, and part of the reserve seeds a standard Osmosis pool at the final spot price, paired
+/// with newly minted supply. The LP shares are sent on when the pool creation replies.
+fn graduate_at_threshold(
+    deps: DepsMut,
+    env: &Env,
+    response: Response,
+) -> Result<Response, ContractError> {
+    let storage = deps.storage;
+    let config = match GRADUATION_CONFIG.may_load(storage)? {
+        Some(config) => config,
+        None => return Ok(response),
+    };
+    let mut curve_state = CURVE_STATE.load(storage)?;
+    if curve_state.supply < config.supply_threshold || GRADUATION.may_load(storage)?.is_some() {
+        return Ok(response);
+    }
+
+    // Until the pool can graduate it keeps trading and tries again on a later swap
+    let pending = |response: Response, key: &str, value: String| -> Result<_, ContractError> {
+        Ok(response
+            .add_event(Event::new("bonding_pool_graduation_pending").add_attribute(key, value)))
+    };
+
+    // the fee in the reserve denom is held back from the reserve, the contract has to hold the rest
+    let mut reserve_fee = Uint128::zero();
+    for fee in &config.pool_creation_fee {
+        let covered = if fee.denom == curve_state.reserve_denom {
+            reserve_fee = fee.amount;
+            // some reserve has to be left to seed the pool with
+            curve_state.reserve * config.reserve_ratio > fee.amount
+        } else {
+            let balance = deps
+                .querier
+                .query_balance(&env.contract.address, &fee.denom)?;
+            balance.amount >= fee.amount
+        };
+        if !covered {
+            return pending(response, "pool_creation_fee", fee.to_string());
+        }
+    }
+
+    let (reserve, supply) = match calc_graduation_liquidity(
+        &curve_state,
+        CURVE_TYPE.load(storage)?.to_curve_fn(),
+        config.reserve_ratio,
+        reserve_fee,
+    ) {
+        Ok(liquidity) => liquidity,
+        Err(err) => return pending(response, "error", err.to_string()),
+    };
+    // the supply minted into the pool counts against the max supply as well
+    if let Some(max_supply) = curve_state.max_supply {
+        if supply > max_supply.saturating_sub(curve_state.supply) {
+            return pending(response, "pool_supply", supply.to_string());
+        }
+    }
+    curve_state.reserve = curve_state
+        .reserve
+        .checked_sub(reserve)?
+        .checked_sub(reserve_fee)?;
+    CURVE_STATE.save(storage, &curve_state)?;
+    GRADUATION.save(
+        storage,
+        &Graduation {
+            supply,
+            reserve,
+            pool_id: None,
+        },
+    )?;
+    // the minted supply isn't backed by the curve, so the pool can't resume swaps
+    pause_swaps(storage)?;
+    IS_RETIRED.save(storage, &true)?;
+
+    let contract = env.contract.address.to_string();
+    let mint = MsgMint {
+        amount: Some(coin(supply.u128(), curve_state.supply_denom.clone()).into()),
+        mint_to_address: contract.clone(),
+        sender: contract.clone(),
+    };
+    let create_pool = create_graduation_pool_msg(
+        &config.pool,
+        vec![
+            coin(reserve.u128(), curve_state.reserve_denom.clone()),
+            coin(supply.u128(), curve_state.supply_denom.clone()),
+        ],
+        contract,
+    );
+    let event = Event::new("bonding_pool_graduated")
+        .add_attribute("supply_denom", curve_state.supply_denom)
+        .add_attribute("supply", curve_state.supply)
+        .add_attribute("reserve_denom", curve_state.reserve_denom)
+        .add_attribute("reserve", curve_state.reserve)
+        .add_attribute("pool_supply", supply)
+        .add_attribute("pool_reserve", reserve)
+        .add_attribute("lp_recipient", config.lp_recipient);
+
+    Ok(response
+        .add_message(mint)
+        .add_submessage(SubMsg::reply_on_success(create_pool, GRADUATION_REPLY_ID))
+        .add_event(event))
 }
 
 /// Handling contract query
@@ -321,7 +2139,7 @@ pub fn execute_swap_exact_amount_out(
 pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> Result<Binary, ContractError> {
     let curve_state = || CURVE_STATE.load(deps.storage);
     let curve_type = || CURVE_TYPE.load(deps.storage);
-    let is_active = || IS_ACTIVE.load(deps.storage);
+    let is_active = || load_pause_state(deps.storage).map(|pause_state| pause_state.is_active());
 
     query_pool_msg_with_state(deps, env, msg, &curve_state, &curve_type, &is_active)
 }
@@ -382,25 +2200,321 @@ pub fn query_pool_msg_with_state(
             dissolved_curve_state: DISSOLVED_CURVE_STATE.load(deps.storage)?,
             curve_type: curve_type()?,
             is_active: is_active()?,
+            reserve_asset: load_reserve_asset(deps.storage)?,
+        }),
+        QueryMsg::RemainingSupply {} => to_binary(&query_remaining_supply(curve_state()?)?),
+        QueryMsg::FeeConfig {} => to_binary(&FEE_CONFIG.load(deps.storage)?),
+        QueryMsg::Ownership {} => to_binary(&OWNERSHIP.load(deps.storage)?),
+        QueryMsg::Retirement {} => to_binary(&RetirementResponse {
+            retire_policy: RETIRE_POLICY.may_load(deps.storage)?.unwrap_or_default(),
+            is_retired: is_retired(deps.storage)?,
+        }),
+        QueryMsg::Graduation {} => to_binary(&GraduationResponse {
+            config: GRADUATION_CONFIG.may_load(deps.storage)?,
+            graduation: GRADUATION.may_load(deps.storage)?,
+        }),
+        QueryMsg::SalePhases {} => {
+            to_binary(&SALE_PHASES.may_load(deps.storage)?.unwrap_or_default())
+        }
+        QueryMsg::CurrentPhase {} => {
+            let phases = SALE_PHASES.may_load(deps.storage)?.unwrap_or_default();
+            let (phase, next_phase_start) = current_sale_phase(&phases, env.block.time);
+            to_binary(&CurrentPhaseResponse {
+                phase: phase.cloned(),
+                next_phase_start,
+            })
+        }
+        QueryMsg::MerkleRoot {} => to_binary(&MerkleRootResponse {
+            merkle_root: MERKLE_ROOT.may_load(deps.storage)?,
+        }),
+        QueryMsg::RemainingAllocation { address, proof } => {
+            to_binary(&query_remaining_allocation(deps, address, proof)?)
+        }
+        QueryMsg::Twap {
+            start_time,
+            end_time,
+        } => to_binary(&TwapResponse {
+            twap: arithmetic_twap(deps.storage, &env, start_time, end_time)?,
         }),
-        // Find matched incoming message variant and query them your custom logic
-        // and then construct your query response with the type usually defined
-        // `msg.rs` alongside with the query message itself.
-        //
-        // use `cosmwasm_std::to_binary` to serialize query response to json binary.
+        QueryMsg::ArithmeticTwapToNow { start_time } => to_binary(&TwapResponse {
+            twap: arithmetic_twap(deps.storage, &env, start_time, env.block.time)?,
+        }),
+        QueryMsg::PauseState {} => to_binary(&load_pause_state(deps.storage)?),
+        QueryMsg::CircuitBreaker {} => to_binary(&CircuitBreakerResponse {
+            config: CIRCUIT_BREAKER_CONFIG.may_load(deps.storage)?,
+            window: BREAKER_WINDOW.may_load(deps.storage)?,
+            trip: BREAKER_TRIP.may_load(deps.storage)?,
+        }),
+        QueryMsg::WalletPurchases { address } => to_binary(&query_wallet_purchases(deps, address)?),
+        QueryMsg::ExemptWallets { start_after, limit } => {
+            to_binary(&query_exempt_wallets(deps, start_after, limit)?)
+        }
+        QueryMsg::Trades { start_after, limit } => {
+            to_binary(&query_trades(deps, start_after, limit)?)
+        }
+        QueryMsg::TradesByTrader {
+            trader,
+            start_after,
+            limit,
+        } => to_binary(&query_trades_by_trader(deps, trader, start_after, limit)?),
+        QueryMsg::TradesInRange {
+            start_time,
+            end_time,
+            start_after,
+            limit,
+        } => to_binary(&query_trades_in_range(
+            deps,
+            start_time,
+            end_time,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::TradeHistory {} => to_binary(&TRADE_HISTORY_CONFIG.may_load(deps.storage)?),
+        QueryMsg::Crowdfund {} => to_binary(&CrowdfundResponse {
+            config: CROWDFUND_CONFIG.may_load(deps.storage)?,
+            status: crowdfund_status(deps.storage, &env)?,
+            held_fees: CROWDFUND_STATE
+                .may_load(deps.storage)?
+                .unwrap_or_default()
+                .held_fees,
+        }),
+        QueryMsg::Contribution { address } => to_binary(
+            &CONTRIBUTIONS
+                .may_load(deps.storage, &deps.api.addr_validate(&address)?)?
+                .unwrap_or_default(),
+        ),
+        QueryMsg::DenomMetadata {} => {
+            to_binary(&DENOM_METADATA.may_load(deps.storage)?.unwrap_or_default())
+        }
+        QueryMsg::SupplyReconciliation {} => {
+            to_binary(&query_supply_reconciliation(deps, curve_state()?)?)
+        }
+        QueryMsg::CheckCurveInvariants { samples } => to_binary(&query_check_curve_invariants(
+            samples,
+            curve_state()?,
+            curve_type()?,
+        )?), // Find matched incoming message variant and query them your custom logic
+             // and then construct your query response with the type usually defined
+             // `msg.rs` alongside with the query message itself.
+             //
+             // use `cosmwasm_std::to_binary` to serialize query response to json binary.
     }
     .map_err(ContractError::Std)
 }
 
-pub fn query_get_swap_fee(_deps: Deps, _env: Env) -> StdResult<GetSwapFeeResponse> {
+pub fn query_get_swap_fee(deps: Deps, _env: Env) -> StdResult<GetSwapFeeResponse> {
     Ok(GetSwapFeeResponse {
-        swap_fee: Decimal::zero(),
+        swap_fee: FEE_CONFIG.load(deps.storage)?.swap_fee,
+    })
+}
+
+pub fn query_remaining_supply(curve_state: CurveState) -> StdResult<RemainingSupplyResponse> {
+    Ok(RemainingSupplyResponse {
+        supply: curve_state.supply,
+        max_supply: curve_state.max_supply,
+        remaining_supply: curve_state.remaining_supply(),
+    })
+}
+
+/// The merkle allocation of `address`, proven by `proof` or else by an earlier buy, and how much
+/// of it the address bought during allowlist phases
+pub fn query_remaining_allocation(
+    deps: Deps,
+    address: String,
+    proof: Option<AllowlistProof>,
+) -> StdResult<RemainingAllocationResponse> {
+    let address = deps.api.addr_validate(&address)?;
+    let allocation = match (proof, MERKLE_ROOT.may_load(deps.storage)?) {
+        (Some(proof), Some(merkle_root)) => verify_merkle_proof(
+            &merkle_root,
+            address.as_str(),
+            proof.allocation,
+            &proof.proof,
+        )?
+        .then_some(proof.allocation),
+        _ => load_merkle_allocation(deps.storage, &address)?,
+    };
+    let purchased = ALLOWLIST_PURCHASES
+        .may_load(deps.storage, &address)?
+        .unwrap_or_default();
+    Ok(RemainingAllocationResponse {
+        allocation,
+        purchased,
+        remaining: allocation.map(|allocation| allocation.saturating_sub(purchased)),
+    })
+}
+
+pub fn query_wallet_purchases(deps: Deps, address: String) -> StdResult<WalletPurchasesResponse> {
+    let purchase = load_wallet_purchase(deps.storage, &deps.api.addr_validate(&address)?)?;
+    let max_per_address = MAX_PER_ADDRESS.may_load(deps.storage)?;
+    let remaining = match max_per_address {
+        Some(max_per_address) if !purchase.exempt => {
+            Some(max_per_address.saturating_sub(purchase.net_purchased))
+        }
+        _ => None,
+    };
+    Ok(WalletPurchasesResponse {
+        net_purchased: purchase.net_purchased,
+        exempt: purchase.exempt,
+        max_per_address,
+        remaining,
+    })
+}
+
+pub fn query_exempt_wallets(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ExemptWalletsResponse> {
+    let limit = limit.unwrap_or(30) as usize;
+    let start_after = start_after
+        .map(|address| deps.api.addr_validate(&address))
+        .transpose()?;
+    let wallets = WalletPurchaseState::default()
+        .purchases
+        .idx
+        .exempt
+        .prefix(1)
+        .keys(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|wallet| wallet.map(String::from))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(ExemptWalletsResponse { wallets })
+}
+
+pub fn query_trades(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TradesResponse> {
+    let limit = limit.unwrap_or(30) as usize;
+    let trades = TradeState::default()
+        .trades
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|trade| trade.map(|(_, trade)| trade))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(TradesResponse { trades })
+}
+
+pub fn query_trades_by_trader(
+    deps: Deps,
+    trader: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TradesResponse> {
+    let limit = limit.unwrap_or(30) as usize;
+    let trader = deps.api.addr_validate(&trader)?;
+    let trades = TradeState::default()
+        .trades
+        .idx
+        .trader
+        .prefix(trader)
+        .range(
+            deps.storage,
+            start_after.map(Bound::exclusive),
+            None,
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|trade| trade.map(|(_, trade)| trade))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(TradesResponse { trades })
+}
+
+pub fn query_trades_in_range(
+    deps: Deps,
+    start_time: Timestamp,
+    end_time: Timestamp,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<TradesResponse> {
+    let limit = limit.unwrap_or(30) as usize;
+    let state = TradeState::default();
+    let start = (start_time.seconds(), 0);
+    // trades are keyed by time and id, a pruned `start_after` is older than every stored trade
+    let start_after = match start_after {
+        Some(id) => state
+            .trades
+            .may_load(deps.storage, id)?
+            .map(|trade| (trade.time.seconds(), id)),
+        None => None,
+    };
+    let min = match start_after {
+        Some(key) if key >= start => Bound::exclusive(key),
+        _ => Bound::inclusive(start),
+    };
+    let trades = state
+        .trades
+        .idx
+        .time
+        .range(
+            deps.storage,
+            Some(min),
+            Some(Bound::inclusive((end_time.seconds(), u64::MAX))),
+            Order::Ascending,
+        )
+        .take(limit)
+        .map(|trade| trade.map(|(_, trade)| trade))
+        .collect::<StdResult<Vec<_>>>()?;
+    Ok(TradesResponse { trades })
+}
+
+/// Compares the supply tracked by the curve with the bank supply of the supply denom.
+/// Tokens received through `Dissolve` stay in the contract, so they count towards both.
+pub fn query_supply_reconciliation(
+    deps: Deps,
+    curve_state: CurveState,
+) -> StdResult<SupplyReconciliationResponse> {
+    let bank_supply = deps.querier.query_supply(curve_state.supply_denom)?.amount;
+    let graduation_supply = GRADUATION
+        .may_load(deps.storage)?
+        .map(|graduation| graduation.supply)
+        .unwrap_or_default();
+    Ok(SupplyReconciliationResponse {
+        curve_supply: curve_state.supply,
+        bank_supply,
+        is_reconciled: curve_state.supply + graduation_supply == bank_supply,
+    })
+}
+
+/// Samples the curve up to the max supply, or the current supply for uncapped pools
+pub fn query_check_curve_invariants(
+    samples: u32,
+    curve_state: CurveState,
+    curve_type: CurveType,
+) -> Result<CheckCurveInvariantsResponse, ContractError> {
+    ensure!(
+        (1..=MAX_INVARIANT_SAMPLES).contains(&samples),
+        ContractError::InvalidSamples {
+            max: MAX_INVARIANT_SAMPLES
+        }
+    );
+    let curve = curve_type.to_curve_fn()(curve_state.decimals.clone())?;
+    let max_supply = curve_state.max_supply.unwrap_or(curve_state.supply);
+    let invariants = check_invariants(curve.as_ref(), max_supply, samples)?;
+    let curve_reserve = curve.reserve(curve_state.supply, Rounding::Up)?;
+    Ok(CheckCurveInvariantsResponse {
+        invariants,
+        reserve: curve_state.reserve,
+        curve_reserve,
+        is_reserve_backed: curve_state.reserve >= curve_reserve,
     })
 }
 
 pub fn query_is_active(deps: Deps, _env: Env, _is_active: bool) -> StdResult<IsActiveResponse> {
     Ok(IsActiveResponse {
-        is_active: IS_ACTIVE.load(deps.storage)?,
+        is_active: load_pause_state(deps.storage)?.is_active(),
     })
 }
 
@@ -417,34 +2531,46 @@ pub fn query_get_total_pool_liquidity(
     })
 }
 
+/// The price of the supply token includes the premium of the current sale phase
 pub fn query_spot_price(
-    _deps: Deps,
-    _env: Env,
+    deps: Deps,
+    env: Env,
     quote_asset_denom: String,
     base_asset_denom: String,
     curve_state: CurveState,
     curve_type: CurveType,
 ) -> Result<SpotPriceResponse, ContractError> {
-    let spot_price = calc_spot_price(quote_asset_denom, base_asset_denom, curve_state, curve_type)?;
+    let is_supply_price = base_asset_denom == curve_state.supply_denom;
+    let mut spot_price =
+        calc_spot_price(quote_asset_denom, base_asset_denom, curve_state, curve_type)?;
+    if is_supply_price {
+        if let Some(multiplier) = phase_price_multiplier(deps.storage, &env)? {
+            spot_price = spot_price.checked_mul(multiplier)?;
+        }
+    }
     Ok(SpotPriceResponse { spot_price })
 }
 
 pub fn query_calc_out_amt_given_in(
     deps: Deps,
-    _env: Env,
+    env: Env,
     token_in: Coin,
     token_out_denom: String,
     swap_fee: Decimal,
     curve_state: CurveState,
     curve_type: CurveType,
 ) -> Result<CalcOutAmtGivenInResponse, ContractError> {
-    let (token_out_amount, _state) = calc_swap_exact_amount_in(
+    ensure_swap_fee(deps.storage, swap_fee)?;
+    let is_buy = token_out_denom == curve_state.supply_denom;
+    let swap_fee = phase_swap_fee(deps.storage, &env, is_buy, swap_fee)?;
+    let token_out_amount = calc_swap_exact_amount_in(
         token_in,
         token_out_denom.clone(),
         swap_fee,
         curve_state,
         curve_type,
-    )?;
+    )?
+    .amount;
     Ok(CalcOutAmtGivenInResponse {
         token_out: coin(token_out_amount.u128(), token_out_denom),
     })
@@ -452,20 +2578,24 @@ pub fn query_calc_out_amt_given_in(
 
 pub fn query_calc_in_amt_given_out(
     deps: Deps,
-    _env: Env,
+    env: Env,
     token_out: Coin,
     token_in_denom: String,
     swap_fee: Decimal,
     curve_state: CurveState,
     curve_type: CurveType,
 ) -> Result<CalcInAmtGivenOutResponse, ContractError> {
-    let (token_in_amount, _state) = calc_swap_exact_amount_out(
+    ensure_swap_fee(deps.storage, swap_fee)?;
+    let is_buy = token_out.denom == curve_state.supply_denom;
+    let swap_fee = phase_swap_fee(deps.storage, &env, is_buy, swap_fee)?;
+    let token_in_amount = calc_swap_exact_amount_out(
         token_in_denom.clone(),
         token_out,
         swap_fee,
         curve_state,
         curve_type,
-    )?;
+    )?
+    .amount;
     Ok(CalcInAmtGivenOutResponse {
         token_in: coin(token_in_amount.u128(), token_in_denom),
     })
@@ -474,9 +2604,2775 @@ pub fn query_calc_in_amt_given_out(
 /// Handling submessage reply.
 /// For more info on submessage and reply, see https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#submessages
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn reply(_deps: DepsMut, _env: Env, _msg: Reply) -> Result<Response, ContractError> {
+pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
     // With `Response` type, it is still possible to dispatch message to invoke external logic.
     // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+    match msg.id {
+        GRADUATION_REPLY_ID => reply_graduation_pool_created(deps, env, msg.result),
+        id => Err(ContractError::UnknownReplyId { id }),
+    }
+}
+
+/// Records the graduation pool and sends its LP shares to the LP recipient
+fn reply_graduation_pool_created(
+    deps: DepsMut,
+    env: Env,
+    result: SubMsgResult,
+) -> Result<Response, ContractError> {
+    let MsgCreateBalancerPoolResponse { pool_id } = result.try_into()?;
+    let mut graduation = GRADUATION.load(deps.storage)?;
+    graduation.pool_id = Some(pool_id);
+    GRADUATION.save(deps.storage, &graduation)?;
+
+    let lp_recipient = GRADUATION_CONFIG.load(deps.storage)?.lp_recipient;
+    let shares = deps
+        .querier
+        .query_balance(env.contract.address, format!("gamm/pool/{}", pool_id))?;
+    let mut response = Response::new().add_event(
+        Event::new("graduation_pool_created")
+            .add_attribute("pool_id", pool_id.to_string())
+            .add_attribute("lp_shares", shares.amount)
+            .add_attribute("lp_recipient", lp_recipient.clone()),
+    );
+    if !shares.amount.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: lp_recipient.to_string(),
+            amount: vec![shares],
+        });
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{coins, from_binary, Addr, BankMsg, SubMsg, SubMsgResponse, WasmMsg};
+
+    use crate::state::{Contribution, SaleAccess, SalePhase};
+    use cw20::Cw20ExecuteMsg;
+    use osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgBurn, MsgMint};
+
+    use super::*;
+
+    const RESERVE_DENOM: &str = "uosmo";
+
+    fn supply_denom() -> String {
+        format!("factory/{}/ushirt", mock_env().contract.address)
+    }
+
+    fn default_instantiate_msg() -> InstantiateMsg {
+        InstantiateMsg {
+            supply_subdenom: "ushirt".to_string(),
+            supply_decimals: 0,
+            name: None,
+            symbol: None,
+            description: None,
+            uri: None,
+            max_supply: Uint128::new(500),
+            reserve_denom: RESERVE_DENOM.to_string(),
+            reserve_asset: None,
+            reserve_decimals: 6,
+            // spot price is always 1.5 osmo per shirt
+            curve_type: CurveType::Constant {
+                value: 15u128.into(),
+                scale: 1,
+            },
+            swap_fee: None,
+            fee_collector: None,
+            owner: None,
+            retire_policy: None,
+            graduation: None,
+            crowdfund: None,
+            sale_phases: None,
+            max_per_address: None,
+            circuit_breaker: None,
+            trade_history: None,
+            test_mode: Some(true),
+            simulation_mode: None,
+        }
+    }
+
+    #[test]
+    fn instantiate_rejects_zero_max_supply() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            max_supply: Uint128::zero(),
+            ..default_instantiate_msg()
+        };
+        let err =
+            instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxSupply {}));
+    }
+
+    #[test]
+    fn swap_exact_amount_in_refunds_past_max_supply() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+
+        // buy 490 of the 500 shirts
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(735_000000),
+                token_out: coin(490, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+
+        // pay for 20 shirts when only 10 are left
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountIn {
+                sender: "buyer".to_string(),
+                token_in: coin(30_000000, RESERVE_DENOM),
+                token_out_denom: supply_denom(),
+                token_out_min_amount: Uint128::new(10),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        let data: SwapExactAmountInResponseData = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.token_out_amount, Uint128::new(10));
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "buyer".to_string(),
+                amount: coins(15_000000, RESERVE_DENOM),
+            })
+        );
+
+        let remaining: RemainingSupplyResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::RemainingSupply {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            remaining,
+            RemainingSupplyResponse {
+                supply: Uint128::new(500),
+                max_supply: Some(Uint128::new(500)),
+                remaining_supply: Some(Uint128::zero()),
+            }
+        );
+
+        // sold out
+        let err = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountIn {
+                sender: "buyer".to_string(),
+                token_in: coin(1_500000, RESERVE_DENOM),
+                token_out_denom: supply_denom(),
+                token_out_min_amount: Uint128::zero(),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SoldOut { .. }));
+    }
+
+    #[test]
+    fn swaps_send_fee_to_fee_collector() {
+        let mut deps = mock_dependencies();
+        let msg = InstantiateMsg {
+            swap_fee: Some(Decimal::percent(1)),
+            fee_collector: Some("collector".to_string()),
+            ..default_instantiate_msg()
+        };
+        instantiate(deps.as_mut(), mock_env(), mock_info("creator", &[]), msg).unwrap();
+
+        let res: GetSwapFeeResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::GetSwapFee {}).unwrap())
+                .unwrap();
+        assert_eq!(res.swap_fee, Decimal::percent(1));
+
+        // the module may not pass a lower fee than the pool's
+        let err = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(3_030304),
+                token_out: coin(2, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::SwapFeeBelowPoolFee { .. }));
+
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(3_030304),
+                token_out: coin(2, supply_denom()),
+                swap_fee: Decimal::percent(1),
+            },
+        )
+        .unwrap();
+        let data: SwapExactAmountOutResponseData = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.token_in_amount, Uint128::new(3_030304));
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "collector".to_string(),
+                amount: coins(30304, RESERVE_DENOM),
+            })
+        );
+
+        // quotes match execution
+        let res: CalcOutAmtGivenInResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::CalcOutAmtGivenIn {
+                    token_in: coin(2, supply_denom()),
+                    token_out_denom: RESERVE_DENOM.to_string(),
+                    swap_fee: Decimal::percent(1),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        let swap = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountIn {
+                sender: "seller".to_string(),
+                token_in: coin(2, supply_denom()),
+                token_out_denom: RESERVE_DENOM.to_string(),
+                token_out_min_amount: res.token_out.amount,
+                swap_fee: Decimal::percent(1),
+            },
+        )
+        .unwrap();
+        let data: SwapExactAmountInResponseData = from_binary(&swap.data.unwrap()).unwrap();
+        assert_eq!(data.token_out_amount, Uint128::new(2_970000));
+        assert_eq!(data.token_out_amount, res.token_out.amount);
+    }
+
+    #[test]
+    fn sells_burn_supply_and_keep_bank_supply_reconciled() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(15_000000),
+                token_out: coin(10, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(MsgMint {
+                sender: mock_env().contract.address.to_string(),
+                amount: Some(coin(10, supply_denom()).into()),
+                mint_to_address: "buyer".to_string(),
+            })]
+        );
+        deps.querier
+            .update_balance("buyer", vec![coin(10, supply_denom())]);
+
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountIn {
+                sender: "buyer".to_string(),
+                token_in: coin(4, supply_denom()),
+                token_out_denom: RESERVE_DENOM.to_string(),
+                token_out_min_amount: Uint128::new(6_000000),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(MsgBurn {
+                    sender: mock_env().contract.address.to_string(),
+                    amount: Some(coin(4, supply_denom()).into()),
+                    burn_from_address: mock_env().contract.address.to_string(),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "buyer".to_string(),
+                    amount: coins(6_000000, RESERVE_DENOM),
+                })
+            ]
+        );
+        deps.querier
+            .update_balance("buyer", vec![coin(6, supply_denom())]);
+
+        let res: SupplyReconciliationResponse = from_binary(
+            &query(deps.as_ref(), mock_env(), QueryMsg::SupplyReconciliation {}).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res,
+            SupplyReconciliationResponse {
+                curve_supply: Uint128::new(6),
+                bank_supply: Uint128::new(6),
+                is_reconciled: true,
+            }
+        );
+    }
+
+    #[test]
+    fn standalone_buy_and_sell() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+        let buy = |min_out: u128, recipient: Option<&str>, deadline| ExecuteMsg::Buy {
+            min_out: Uint128::new(min_out),
+            recipient: recipient.map(str::to_string),
+            deadline,
+            allowlist_proof: None,
+        };
+
+        // 30 osmo buys 20 shirts for the sender
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &coins(30_000000, RESERVE_DENOM)),
+            buy(20, None, Some(mock_env().block.time)),
+        )
+        .unwrap();
+        let data: SwapExactAmountInResponseData = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.token_out_amount, Uint128::new(20));
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(MsgMint {
+                sender: mock_env().contract.address.to_string(),
+                amount: Some(coin(20, supply_denom()).into()),
+                mint_to_address: "buyer".to_string(),
+            })]
+        );
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &coins(30_000000, RESERVE_DENOM)),
+            buy(21, None, None),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: insufficient output amount");
+
+        let deadline = mock_env().block.time.minus_seconds(1);
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &coins(30_000000, RESERVE_DENOM)),
+            buy(20, None, Some(deadline)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::DeadlineExceeded { deadline: d } if d == deadline));
+
+        // buys have to pay in the reserve token
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &[coin(20, supply_denom())]),
+            buy(0, None, None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Payment(_)));
+
+        // sell 4 shirts, paying out to another address
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &[coin(4, supply_denom())]),
+            ExecuteMsg::Sell {
+                min_out: Uint128::new(6_000000),
+                recipient: Some("friend".to_string()),
+                deadline: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(MsgBurn {
+                    sender: mock_env().contract.address.to_string(),
+                    amount: Some(coin(4, supply_denom()).into()),
+                    burn_from_address: mock_env().contract.address.to_string(),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "friend".to_string(),
+                    amount: coins(6_000000, RESERVE_DENOM),
+                })
+            ]
+        );
+        let state = CURVE_STATE.load(deps.as_ref().storage).unwrap();
+        assert_eq!(state.supply, Uint128::new(16));
+        assert_eq!(state.reserve, Uint128::new(24_000000));
+        // the sale is the seller's trade, the recipient is only paid out
+        let trades = |trader: &str| {
+            let msg = QueryMsg::TradesByTrader {
+                trader: trader.to_string(),
+                start_after: None,
+                limit: None,
+            };
+            let res: TradesResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+            res.trades.len()
+        };
+        assert_eq!((trades("buyer"), trades("friend")), (2, 0));
+
+        // standalone swaps follow the pool's active flag
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetActive { is_active: false },
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &coins(30_000000, RESERVE_DENOM)),
+            buy(0, None, None),
+        )
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: Contract is not active");
+    }
+
+    #[test]
+    fn cw20_reserve() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                reserve_denom: "reservetoken".to_string(),
+                reserve_asset: Some(ReserveAsset::Cw20),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let receive = |min_out: u128| {
+            ExecuteMsg::Receive(Cw20ReceiveMsg {
+                sender: "buyer".to_string(),
+                amount: Uint128::new(30_000000),
+                msg: to_binary(&ReceiveMsg::Buy {
+                    min_out: Uint128::new(min_out),
+                    recipient: None,
+                    deadline: None,
+                    allowlist_proof: None,
+                })
+                .unwrap(),
+            })
+        };
+
+        // only the reserve token contract can buy through the hook
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("othertoken", &[]),
+            receive(20),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCw20Token { token } if token == "othertoken"));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("reservetoken", &[]),
+            receive(20),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(MsgMint {
+                sender: mock_env().contract.address.to_string(),
+                amount: Some(coin(20, supply_denom()).into()),
+                mint_to_address: "buyer".to_string(),
+            })]
+        );
+
+        // sells pay out with a cw20 transfer
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("buyer", &[coin(4, supply_denom())]),
+            ExecuteMsg::Sell {
+                min_out: Uint128::new(6_000000),
+                recipient: None,
+                deadline: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(WasmMsg::Execute {
+                contract_addr: "reservetoken".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "buyer".to_string(),
+                    amount: Uint128::new(6_000000),
+                })
+                .unwrap(),
+                funds: vec![],
+            })
+        );
+
+        let state: BondingPoolState =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::BondingPoolState {}).unwrap())
+                .unwrap();
+        assert_eq!(state.reserve_asset, ReserveAsset::Cw20);
+        assert_eq!(state.curve_state.reserve, Uint128::new(24_000000));
+
+        // native pools don't take cw20 tokens
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(RESERVE_DENOM, &[]),
+            receive(20),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCw20Token { .. }));
+    }
+
+    #[test]
+    fn check_curve_invariants() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(150_000000),
+                token_out: coin(100, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+
+        let check = |deps: Deps, samples| {
+            query(deps, mock_env(), QueryMsg::CheckCurveInvariants { samples })
+                .and_then(|res| Ok(from_binary::<CheckCurveInvariantsResponse>(&res)?))
+        };
+        let res = check(deps.as_ref(), 50).unwrap();
+        assert_eq!(50, res.invariants.samples);
+        assert_eq!(Uint128::zero(), res.invariants.max_round_trip_error);
+        assert_eq!(0, res.invariants.monotonicity_violations);
+        assert_eq!(Uint128::new(150_000000), res.reserve);
+        assert_eq!(Uint128::new(150_000000), res.curve_reserve);
+        assert!(res.is_reserve_backed);
+
+        for samples in [0, MAX_INVARIANT_SAMPLES + 1] {
+            let err = check(deps.as_ref(), samples).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidSamples { max: 1000 }));
+        }
+
+        // a reserve short of what the supply was bought for is flagged
+        CURVE_STATE
+            .update(deps.as_mut().storage, |mut state| -> StdResult<_> {
+                state.reserve -= Uint128::one();
+                Ok(state)
+            })
+            .unwrap();
+        let res = check(deps.as_ref(), 50).unwrap();
+        assert_eq!(Uint128::new(149_999999), res.reserve);
+        assert!(!res.is_reserve_backed);
+    }
+
+    #[test]
+    fn denom_metadata() {
+        let mut deps = mock_dependencies();
+        let res = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                supply_decimals: 6,
+                name: Some("Suit Shirt".to_string()),
+                symbol: Some("SHIRT".to_string()),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let metadata = DenomMetadata {
+            name: Some("Suit Shirt".to_string()),
+            symbol: Some("SHIRT".to_string()),
+            description: None,
+            uri: None,
+        };
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(set_denom_metadata_msg(
+                supply_denom(),
+                6,
+                &metadata,
+                mock_env().contract.address.to_string(),
+            ))
+        );
+
+        let update = ExecuteMsg::UpdateDenomMetadata {
+            name: None,
+            symbol: None,
+            description: Some("Limited drop".to_string()),
+            uri: Some("ipfs://shirt".to_string()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            update.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // unset fields keep their value
+        let res = execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), update).unwrap();
+        let metadata = DenomMetadata {
+            description: Some("Limited drop".to_string()),
+            uri: Some("ipfs://shirt".to_string()),
+            ..metadata
+        };
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(set_denom_metadata_msg(
+                supply_denom(),
+                6,
+                &metadata,
+                mock_env().contract.address.to_string(),
+            ))]
+        );
+        let queried: DenomMetadata =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::DenomMetadata {}).unwrap())
+                .unwrap();
+        assert_eq!(queried, metadata);
+    }
+
+    #[test]
+    fn retire_pool_and_transfer_denom_admin() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                retire_policy: Some(RetirePolicy::SendReserve {
+                    recipient: Addr::unchecked("treasury"),
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(15_000000),
+                token_out: coin(10, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        let change_admin = |new_admin: &str| {
+            SubMsg::new(MsgChangeAdmin {
+                sender: mock_env().contract.address.to_string(),
+                denom: supply_denom(),
+                new_admin: new_admin.to_string(),
+            })
+        };
+
+        // the pool still mints and burns until it is retired
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::TransferDenomAdmin {
+                new_admin: "dao".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::PoolNotRetired {}));
+
+        let retire = ExecuteMsg::RetirePool {
+            denom_admin: Some("dao".to_string()),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            retire.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            retire.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(BankMsg::Send {
+                    to_address: "treasury".to_string(),
+                    amount: coins(15_000000, RESERVE_DENOM),
+                }),
+                change_admin("dao"),
+            ]
+        );
+        assert_eq!(
+            res.events,
+            vec![
+                Event::new("bonding_pool_retired")
+                    .add_attribute("supply_denom", supply_denom())
+                    .add_attribute("supply", "10")
+                    .add_attribute("reserve_denom", RESERVE_DENOM)
+                    .add_attribute("reserve", "15000000")
+                    .add_attribute("retire_policy", "send_reserve")
+                    .add_attribute("reserve_recipient", "treasury"),
+                Event::new("denom_admin_transferred")
+                    .add_attribute("denom", supply_denom())
+                    .add_attribute("new_admin", "dao"),
+            ]
+        );
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        assert!(CURVE_STATE.load(&deps.storage).unwrap().reserve.is_zero());
+        let res: RetirementResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Retirement {}).unwrap())
+                .unwrap();
+        assert!(res.is_retired);
+
+        // retiring is final
+        let err =
+            execute(deps.as_mut(), mock_env(), mock_info("creator", &[]), retire).unwrap_err();
+        assert!(matches!(err, ContractError::PoolRetired {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetActive { is_active: true },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::PoolRetired {}));
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::TransferDenomAdmin {
+                new_admin: "newdao".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages, vec![change_admin("newdao")]);
+    }
+
+    #[test]
+    fn graduate_to_balancer_pool() {
+        let mut deps = mock_dependencies();
+        let graduation = GraduationConfig {
+            supply_threshold: Uint128::new(100),
+            reserve_ratio: Decimal::percent(80),
+            pool: GraduationPool::Balancer {
+                swap_fee: Decimal::permille(3),
+            },
+            lp_recipient: Addr::unchecked("dao"),
+            pool_creation_fee: vec![],
+        };
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                graduation: Some(GraduationConfig {
+                    supply_threshold: Uint128::new(501),
+                    ..graduation.clone()
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidGraduationConfig { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                graduation: Some(graduation.clone()),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: "buyer".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 1_500000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+
+        // below the threshold the curve keeps trading
+        let res = sudo(deps.as_mut(), mock_env(), buy(99)).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+
+        // 80% of the 150 osmo reserve is paired with 80 shirts at 1.5 osmo each
+        let res = sudo(deps.as_mut(), mock_env(), buy(1)).unwrap();
+        let contract = mock_env().contract.address.to_string();
+        assert_eq!(
+            res.messages[1..],
+            vec![
+                SubMsg::new(MsgMint {
+                    amount: Some(coin(80, supply_denom()).into()),
+                    mint_to_address: contract.clone(),
+                    sender: contract.clone(),
+                }),
+                SubMsg::reply_on_success(
+                    create_graduation_pool_msg(
+                        &graduation.pool,
+                        vec![coin(120_000000, RESERVE_DENOM), coin(80, supply_denom())],
+                        contract.clone(),
+                    ),
+                    GRADUATION_REPLY_ID,
+                ),
+            ]
+        );
+        assert_eq!(res.events[0].ty, "bonding_pool_graduated");
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        let curve_state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(curve_state.supply, Uint128::new(100));
+        assert_eq!(curve_state.reserve, Uint128::new(30_000000));
+
+        let err = sudo(deps.as_mut(), mock_env(), buy(1)).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: Contract is not active");
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetActive { is_active: true },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::PoolRetired {}));
+
+        // the gamm module replies with the new pool, whose shares go to the LP recipient
+        deps.querier
+            .update_balance(contract, vec![coin(100_000000, "gamm/pool/7")]);
+        let pool_created = |id| Reply {
+            id,
+            result: SubMsgResult::Ok(SubMsgResponse {
+                events: vec![],
+                data: Some(MsgCreateBalancerPoolResponse { pool_id: 7 }.into()),
+            }),
+        };
+        let err = reply(deps.as_mut(), mock_env(), pool_created(2)).unwrap_err();
+        assert!(matches!(err, ContractError::UnknownReplyId { id: 2 }));
+        let res = reply(deps.as_mut(), mock_env(), pool_created(GRADUATION_REPLY_ID)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "dao".to_string(),
+                amount: coins(100_000000, "gamm/pool/7"),
+            })]
+        );
+        let res: GraduationResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Graduation {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            res,
+            GraduationResponse {
+                config: Some(graduation),
+                graduation: Some(Graduation {
+                    supply: Uint128::new(80),
+                    reserve: Uint128::new(120_000000),
+                    pool_id: Some(7),
+                }),
+            }
+        );
+    }
+
+    #[test]
+    fn graduation_stays_within_max_supply() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                graduation: Some(GraduationConfig {
+                    supply_threshold: Uint128::new(300),
+                    reserve_ratio: Decimal::percent(80),
+                    pool: GraduationPool::Balancer {
+                        swap_fee: Decimal::permille(3),
+                    },
+                    lp_recipient: Addr::unchecked("dao"),
+                    pool_creation_fee: vec![],
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+
+        // 240 shirts for the pool on top of the 300 sold would exceed the max supply of 500
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(450_000000),
+                token_out: coin(300, supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_pool_graduation_pending").add_attribute("pool_supply", "240")]
+        );
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+        assert_eq!(GRADUATION.may_load(&deps.storage).unwrap(), None);
+    }
+
+    #[test]
+    fn graduation_liquidity_overflow_keeps_trading() {
+        let mut deps = mock_dependencies();
+        let max_supply = Uint128::new(200_000_000_000_000_000_000_000_000_000_000_000_000);
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                max_supply,
+                reserve_decimals: 0,
+                // 1.9e-18 rounds down to a spot price of 1e-18, so the pool supply the reserve
+                // buys at it doesn't fit a Uint128
+                curve_type: CurveType::Constant {
+                    value: 19u128.into(),
+                    scale: 19,
+                },
+                graduation: Some(GraduationConfig {
+                    supply_threshold: max_supply,
+                    reserve_ratio: Decimal::one(),
+                    pool: GraduationPool::Balancer {
+                        swap_fee: Decimal::permille(3),
+                    },
+                    lp_recipient: Addr::unchecked("dao"),
+                    pool_creation_fee: vec![],
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+
+        // the buy reaching the threshold still goes through
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "buyer".to_string(),
+                token_in_denom: RESERVE_DENOM.to_string(),
+                token_in_max_amount: Uint128::new(380_000_000_000_000_000_000),
+                token_out: coin(max_supply.u128(), supply_denom()),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_pool_graduation_pending").add_attribute(
+                "error",
+                "Error converting Uint256 to Uint128 for 380000000000000000000000000000000000000"
+            )]
+        );
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+        assert_eq!(GRADUATION.may_load(&deps.storage).unwrap(), None);
+        let curve_state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert_eq!(curve_state.supply, max_supply);
+        assert_eq!(
+            curve_state.reserve,
+            Uint128::new(380_000_000_000_000_000_000)
+        );
+    }
+
+    #[test]
+    fn graduation_pool_creation_fee() {
+        let mut deps = mock_dependencies();
+        let graduation = GraduationConfig {
+            supply_threshold: Uint128::new(100),
+            reserve_ratio: Decimal::percent(80),
+            pool: GraduationPool::Balancer {
+                swap_fee: Decimal::permille(3),
+            },
+            lp_recipient: Addr::unchecked("dao"),
+            pool_creation_fee: vec![coin(20_000000, RESERVE_DENOM), coin(100, "uion")],
+        };
+        for pool_creation_fee in [
+            vec![coin(0, "uion")],
+            vec![coin(100, "uion"), coin(200, "uion")],
+        ] {
+            let err = instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                InstantiateMsg {
+                    graduation: Some(GraduationConfig {
+                        pool_creation_fee,
+                        ..graduation.clone()
+                    }),
+                    ..default_instantiate_msg()
+                },
+            )
+            .unwrap_err();
+            assert!(matches!(err, ContractError::InvalidGraduationConfig { .. }));
+        }
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                graduation: Some(graduation.clone()),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: "buyer".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 1_500000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+
+        // without the uion part of the fee the pool can't be created, so it keeps trading
+        let res = sudo(deps.as_mut(), mock_env(), buy(100)).unwrap();
+        assert_eq!(res.messages.len(), 1);
+        assert_eq!(
+            res.events,
+            vec![Event::new("bonding_pool_graduation_pending")
+                .add_attribute("pool_creation_fee", "100uion")]
+        );
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+        assert_eq!(GRADUATION.may_load(&deps.storage).unwrap(), None);
+
+        // once the contract holds it, the next swap graduates
+        let contract = mock_env().contract.address.to_string();
+        deps.querier
+            .update_balance(contract.clone(), vec![coin(100, "uion")]);
+        let res = sudo(deps.as_mut(), mock_env(), buy(2)).unwrap();
+        // 80% of the 153 osmo reserve less the 20 osmo fee buys 68 shirts at 1.5 osmo each
+        assert_eq!(
+            res.messages[1..],
+            vec![
+                SubMsg::new(MsgMint {
+                    amount: Some(coin(68, supply_denom()).into()),
+                    mint_to_address: contract.clone(),
+                    sender: contract.clone(),
+                }),
+                SubMsg::reply_on_success(
+                    create_graduation_pool_msg(
+                        &graduation.pool,
+                        vec![coin(102_400000, RESERVE_DENOM), coin(68, supply_denom())],
+                        contract,
+                    ),
+                    GRADUATION_REPLY_ID,
+                ),
+            ]
+        );
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+
+        // the fee comes out of the reserve on top of the pool liquidity, the rest is left for
+        // `Dissolve` redemptions
+        let curve_state = CURVE_STATE.load(&deps.storage).unwrap();
+        let graduated = GRADUATION.load(&deps.storage).unwrap();
+        assert_eq!(graduated.reserve, Uint128::new(102_400000));
+        assert_eq!(curve_state.reserve, Uint128::new(30_600000));
+        assert_eq!(
+            graduated.reserve + Uint128::new(20_000000) + curve_state.reserve,
+            Uint128::new(153_000000)
+        );
+    }
+
+    #[test]
+    fn crowdfund_refunds_or_funds() {
+        let deadline = mock_env().block.time.plus_seconds(1000);
+        let after_deadline = || {
+            let mut env = mock_env();
+            env.block.time = deadline.plus_seconds(1);
+            env
+        };
+        let instantiate_crowdfund = || {
+            let mut deps = mock_dependencies();
+            instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("creator", &[]),
+                InstantiateMsg {
+                    swap_fee: Some(Decimal::percent(1)),
+                    crowdfund: Some(CrowdfundConfig {
+                        target_supply: Uint128::new(100),
+                        deadline,
+                    }),
+                    ..default_instantiate_msg()
+                },
+            )
+            .unwrap();
+            deps
+        };
+        let buy = |buyer: &str, amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: buyer.to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 2_000000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::percent(1),
+        };
+        let sell = |amount: u128| SudoMsg::SwapExactAmountIn {
+            sender: "alice".to_string(),
+            token_in: coin(amount, supply_denom()),
+            token_out_denom: RESERVE_DENOM.to_string(),
+            token_out_min_amount: Uint128::zero(),
+            swap_fee: Decimal::percent(1),
+        };
+        let claim_refund = |amount: u128| {
+            (
+                mock_info("alice", &coins(amount, supply_denom())),
+                ExecuteMsg::ClaimRefund {},
+            )
+        };
+        let contribution = |deps: Deps| -> Contribution {
+            let msg = QueryMsg::Contribution {
+                address: "alice".to_string(),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // the crowdfund misses its target
+        let mut deps = instantiate_crowdfund();
+        let res = sudo(deps.as_mut(), mock_env(), buy("alice", 30)).unwrap();
+        // the swap fee is held for refunds
+        assert_eq!(res.messages.len(), 1);
+        let fee = contribution(deps.as_ref()).fee;
+        assert!(!fee.is_zero());
+        assert_eq!(
+            contribution(deps.as_ref()),
+            Contribution {
+                supply: Uint128::new(30),
+                reserve: Uint128::new(45_000000),
+                fee,
+            }
+        );
+        let err = sudo(deps.as_mut(), mock_env(), sell(10)).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundNotFunded {}));
+        let dissolve = || {
+            (
+                mock_info("alice", &coins(10, supply_denom())),
+                ExecuteMsg::Dissolve {},
+            )
+        };
+        let (info, msg) = dissolve();
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundNotFunded {}));
+        let (info, msg) = claim_refund(10);
+        let err = execute(deps.as_mut(), mock_env(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundNotFailed {}));
+
+        let err = sudo(deps.as_mut(), after_deadline(), buy("bob", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundFailed {}));
+        let (info, msg) = dissolve();
+        let err = execute(deps.as_mut(), after_deadline(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundFailed {}));
+        let (info, msg) = claim_refund(20);
+        let res = execute(deps.as_mut(), after_deadline(), info, msg).unwrap();
+        let refund = (Uint128::new(45_000000) + fee).multiply_ratio(20u128, 30u128);
+        assert_eq!(
+            res.messages,
+            vec![
+                SubMsg::new(MsgBurn {
+                    amount: Some(coin(20, supply_denom()).into()),
+                    burn_from_address: mock_env().contract.address.to_string(),
+                    sender: mock_env().contract.address.to_string(),
+                }),
+                SubMsg::new(BankMsg::Send {
+                    to_address: "alice".to_string(),
+                    amount: coins(refund.u128(), RESERVE_DENOM),
+                }),
+            ]
+        );
+        let (info, msg) = claim_refund(11);
+        let err = execute(deps.as_mut(), after_deadline(), info, msg).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::RefundExceedsContribution { contributed } if contributed.u128() == 10
+        ));
+        let (info, msg) = claim_refund(10);
+        execute(deps.as_mut(), after_deadline(), info, msg).unwrap();
+        assert_eq!(contribution(deps.as_ref()), Contribution::default());
+        let curve_state = CURVE_STATE.load(&deps.storage).unwrap();
+        assert!(curve_state.supply.is_zero() && curve_state.reserve.is_zero());
+        let res: CrowdfundResponse =
+            from_binary(&query(deps.as_ref(), after_deadline(), QueryMsg::Crowdfund {}).unwrap())
+                .unwrap();
+        assert_eq!(res.status, Some(CrowdfundStatus::Failed));
+        assert!(res.held_fees.is_zero());
+
+        // a buy for another recipient is recorded for the recipient, who holds the supply
+        let mut deps = instantiate_crowdfund();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(30_000000, RESERVE_DENOM)),
+            ExecuteMsg::Buy {
+                min_out: Uint128::zero(),
+                recipient: Some("alice".to_string()),
+                deadline: None,
+                allowlist_proof: None,
+            },
+        )
+        .unwrap();
+        let msg = QueryMsg::Contribution {
+            address: "bob".to_string(),
+        };
+        let bob: Contribution =
+            from_binary(&query(deps.as_ref(), mock_env(), msg).unwrap()).unwrap();
+        assert_eq!(bob, Contribution::default());
+        let alice = contribution(deps.as_ref());
+        assert_eq!(alice.supply, Uint128::new(19));
+        let (info, msg) = claim_refund(alice.supply.u128());
+        let res = execute(deps.as_mut(), after_deadline(), info, msg).unwrap();
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins((alice.reserve + alice.fee).u128(), RESERVE_DENOM),
+            })
+        );
+        assert_eq!(contribution(deps.as_ref()), Contribution::default());
 
-    Ok(Response::new())
+        // the crowdfund reaches its target and forwards the held fees
+        let mut deps = instantiate_crowdfund();
+        sudo(deps.as_mut(), mock_env(), buy("alice", 30)).unwrap();
+        let res = sudo(deps.as_mut(), mock_env(), buy("bob", 70)).unwrap();
+        assert_eq!(res.events[0].ty, "crowdfund_funded");
+        // bob pays 105 osmo grossed up by the 1% fee, 106.060607 osmo
+        let held_fees = fee + Uint128::new(1_060607);
+        assert!(res.messages.contains(&SubMsg::new(BankMsg::Send {
+            to_address: "creator".to_string(),
+            amount: coins(held_fees.u128(), RESERVE_DENOM),
+        })));
+        // the pool trades normally from here on, also after the deadline
+        sudo(deps.as_mut(), after_deadline(), sell(10)).unwrap();
+        sudo(deps.as_mut(), after_deadline(), buy("carol", 1)).unwrap();
+        let (info, msg) = dissolve();
+        execute(deps.as_mut(), after_deadline(), info, msg).unwrap();
+        let (info, msg) = claim_refund(10);
+        let err = execute(deps.as_mut(), after_deadline(), info, msg).unwrap_err();
+        assert!(matches!(err, ContractError::CrowdfundNotFailed {}));
+    }
+
+    #[test]
+    fn sale_phases() {
+        let start = mock_env().block.time;
+        let phase = |name: &str, seconds, access| SalePhase {
+            name: name.to_string(),
+            start: start.plus_seconds(seconds),
+            access,
+            max_per_wallet: None,
+            price_multiplier: None,
+        };
+        let phases = vec![
+            SalePhase {
+                max_per_wallet: Some(Uint128::new(5)),
+                price_multiplier: Some(Decimal::percent(200)),
+                ..phase("presale", 100, SaleAccess::Allowlist)
+            },
+            phase("public", 200, SaleAccess::Public),
+            phase("closed", 300, SaleAccess::Closed),
+        ];
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                sale_phases: Some(phases.clone()),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let at = |seconds| {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(seconds);
+            env
+        };
+        let buy = |buyer: &str, amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: buyer.to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 3_000000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let current_phase = |deps: Deps, seconds| -> CurrentPhaseResponse {
+            from_binary(&query(deps, at(seconds), QueryMsg::CurrentPhase {}).unwrap()).unwrap()
+        };
+
+        let err = sudo(deps.as_mut(), at(99), buy("alice", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::SaleNotStarted { start: s } if s == phases[0].start));
+        assert_eq!(
+            current_phase(deps.as_ref(), 99),
+            CurrentPhaseResponse {
+                phase: None,
+                next_phase_start: Some(phases[0].start),
+            }
+        );
+
+        // the presale is limited to the allowlist, at twice the curve price
+        let err = sudo(deps.as_mut(), at(100), buy("alice", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted { .. }));
+        let allow_alice = ExecuteMsg::UpdateAllowlist {
+            add: vec!["alice".to_string()],
+            remove: vec![],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            allow_alice.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            allow_alice,
+        )
+        .unwrap();
+        // access and caps follow the buyer, naming alice as recipient doesn't get mallory in
+        let buy_for = |recipient: &str| ExecuteMsg::Buy {
+            min_out: Uint128::zero(),
+            recipient: Some(recipient.to_string()),
+            deadline: None,
+            allowlist_proof: None,
+        };
+        let err = execute(
+            deps.as_mut(),
+            at(100),
+            mock_info("mallory", &coins(3_000000, RESERVE_DENOM)),
+            buy_for("alice"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted { .. }));
+        // quotes include the premium, so exact out swaps can rely on them
+        let quote: CalcInAmtGivenOutResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                at(100),
+                QueryMsg::CalcInAmtGivenOut {
+                    token_out: coin(5, supply_denom()),
+                    token_in_denom: RESERVE_DENOM.to_string(),
+                    swap_fee: Decimal::zero(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(quote.token_in, coin(15_000000, RESERVE_DENOM));
+        let quote: CalcOutAmtGivenInResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                at(100),
+                QueryMsg::CalcOutAmtGivenIn {
+                    token_in: coin(15_000000, RESERVE_DENOM),
+                    token_out_denom: supply_denom(),
+                    swap_fee: Decimal::zero(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(quote.token_out, coin(5, supply_denom()));
+        let spot_price = |seconds| -> Decimal {
+            let msg = QueryMsg::SpotPrice {
+                quote_asset_denom: RESERVE_DENOM.to_string(),
+                base_asset_denom: supply_denom(),
+            };
+            let res: SpotPriceResponse =
+                from_binary(&query(deps.as_ref(), at(seconds), msg).unwrap()).unwrap();
+            res.spot_price
+        };
+        assert_eq!(spot_price(100), Decimal::percent(300));
+        assert_eq!(spot_price(200), Decimal::percent(150));
+        let res = sudo(deps.as_mut(), at(100), buy("alice", 5)).unwrap();
+        let data: SwapExactAmountOutResponseData = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.token_in_amount, Uint128::new(15_000000));
+        assert_eq!(
+            res.messages[1],
+            SubMsg::new(BankMsg::Send {
+                to_address: "creator".to_string(),
+                amount: coins(7_500000, RESERVE_DENOM),
+            })
+        );
+        let err = sudo(deps.as_mut(), at(150), buy("alice", 1)).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::PhaseWalletCapExceeded { max_per_wallet, .. } if max_per_wallet.u128() == 5
+        ));
+        let err = execute(
+            deps.as_mut(),
+            at(150),
+            mock_info("alice", &coins(3_000000, RESERVE_DENOM)),
+            buy_for("friend"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::PhaseWalletCapExceeded { .. }));
+
+        // the public sale is open to anyone at the curve price
+        assert_eq!(
+            current_phase(deps.as_ref(), 250),
+            CurrentPhaseResponse {
+                phase: Some(phases[1].clone()),
+                next_phase_start: Some(phases[2].start),
+            }
+        );
+        let res = sudo(deps.as_mut(), at(250), buy("bob", 10)).unwrap();
+        let data: SwapExactAmountOutResponseData = from_binary(&res.data.unwrap()).unwrap();
+        assert_eq!(data.token_in_amount, Uint128::new(15_000000));
+
+        // once closed, holders can still sell
+        let err = sudo(deps.as_mut(), at(300), buy("bob", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::SaleClosed { .. }));
+        sudo(
+            deps.as_mut(),
+            at(300),
+            SudoMsg::SwapExactAmountIn {
+                sender: "bob".to_string(),
+                token_in: coin(10, supply_denom()),
+                token_out_denom: RESERVE_DENOM.to_string(),
+                token_out_min_amount: Uint128::zero(),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateSalePhases {
+                phases: vec![phases[1].clone(), phases[0].clone()],
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidSalePhases { .. }));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateSalePhases { phases: vec![] },
+        )
+        .unwrap();
+        sudo(deps.as_mut(), at(300), buy("bob", 1)).unwrap();
+    }
+
+    #[test]
+    fn merkle_allowlist() {
+        use sha2::{Digest, Sha256};
+
+        let leaf = |address: &str, allocation: u128| -> [u8; 32] {
+            Sha256::digest(format!("{}{}", address, allocation)).into()
+        };
+        let (alice_leaf, bob_leaf) = (leaf("alice", 3), leaf("bob", 2));
+        let (first, second) = if alice_leaf < bob_leaf {
+            (alice_leaf, bob_leaf)
+        } else {
+            (bob_leaf, alice_leaf)
+        };
+        let merkle_root = hex::encode(
+            Sha256::new()
+                .chain_update(first)
+                .chain_update(second)
+                .finalize(),
+        );
+        let alice_proof = |allocation| AllowlistProof {
+            allocation: Uint128::new(allocation),
+            proof: vec![hex::encode(bob_leaf)],
+        };
+
+        let start = mock_env().block.time;
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                sale_phases: Some(vec![
+                    SalePhase {
+                        name: "presale".to_string(),
+                        start: start.plus_seconds(100),
+                        access: SaleAccess::Allowlist,
+                        max_per_wallet: None,
+                        price_multiplier: None,
+                    },
+                    SalePhase {
+                        name: "public".to_string(),
+                        start: start.plus_seconds(200),
+                        access: SaleAccess::Public,
+                        max_per_wallet: None,
+                        price_multiplier: None,
+                    },
+                ]),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let at = |seconds| {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(seconds);
+            env
+        };
+        let buy = |buyer: &str, amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: buyer.to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 1_500000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let remaining = |deps: Deps, proof| -> RemainingAllocationResponse {
+            let msg = QueryMsg::RemainingAllocation {
+                address: "alice".to_string(),
+                proof,
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+
+        // only the owner registers the root, which must be a sha256 hash
+        let register = |merkle_root: &str| ExecuteMsg::RegisterMerkleRoot {
+            merkle_root: merkle_root.to_string(),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            register(&merkle_root),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            register("abcd"),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Std(_)));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            register(&merkle_root),
+        )
+        .unwrap();
+        let res: MerkleRootResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::MerkleRoot {}).unwrap())
+                .unwrap();
+        assert_eq!(res.merkle_root, Some(merkle_root.clone()));
+
+        // alice is not allowlisted until she proves her allocation
+        let err = sudo(deps.as_mut(), at(100), buy("alice", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::NotAllowlisted { .. }));
+        assert_eq!(
+            remaining(deps.as_ref(), Some(alice_proof(3))),
+            RemainingAllocationResponse {
+                allocation: Some(Uint128::new(3)),
+                purchased: Uint128::zero(),
+                remaining: Some(Uint128::new(3)),
+            }
+        );
+        assert_eq!(remaining(deps.as_ref(), None).allocation, None);
+        let buy_with_proof = |allocation| ExecuteMsg::Buy {
+            min_out: Uint128::zero(),
+            recipient: None,
+            deadline: None,
+            allowlist_proof: Some(alice_proof(allocation)),
+        };
+        let err = execute(
+            deps.as_mut(),
+            at(100),
+            mock_info("alice", &coins(3_000000, RESERVE_DENOM)),
+            buy_with_proof(4),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerkleProof {}));
+        // proofs are checked against the buyer, who can't spend alice's allocation for her
+        let err = execute(
+            deps.as_mut(),
+            at(100),
+            mock_info("mallory", &coins(3_000000, RESERVE_DENOM)),
+            ExecuteMsg::Buy {
+                min_out: Uint128::zero(),
+                recipient: Some("alice".to_string()),
+                deadline: None,
+                allowlist_proof: Some(alice_proof(3)),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMerkleProof {}));
+        execute(
+            deps.as_mut(),
+            at(100),
+            mock_info("alice", &coins(3_000000, RESERVE_DENOM)),
+            buy_with_proof(3),
+        )
+        .unwrap();
+
+        // later buys use the proven allocation, up to its amount
+        let err = sudo(deps.as_mut(), at(150), buy("alice", 2)).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::AllocationExceeded { allocation } if allocation.u128() == 3
+        ));
+        sudo(deps.as_mut(), at(150), buy("alice", 1)).unwrap();
+        assert_eq!(
+            remaining(deps.as_ref(), None),
+            RemainingAllocationResponse {
+                allocation: Some(Uint128::new(3)),
+                purchased: Uint128::new(3),
+                remaining: Some(Uint128::zero()),
+            }
+        );
+
+        // allocations proven under a replaced root no longer count
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            register(&hex::encode(bob_leaf)),
+        )
+        .unwrap();
+        assert_eq!(remaining(deps.as_ref(), None).allocation, None);
+
+        // the public sale is not capped by allocations
+        sudo(deps.as_mut(), at(200), buy("alice", 10)).unwrap();
+    }
+
+    #[test]
+    fn max_per_address() {
+        let mut deps = mock_dependencies();
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                max_per_address: Some(Uint128::zero()),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxPerAddress {}));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                max_per_address: Some(Uint128::new(5)),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy_out = |buyer: &str, amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: buyer.to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 1_500000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let buy_in = |buyer: &str, amount: u128| SudoMsg::SwapExactAmountIn {
+            sender: buyer.to_string(),
+            token_in: coin(amount * 1_500000, RESERVE_DENOM),
+            token_out_denom: supply_denom(),
+            token_out_min_amount: Uint128::zero(),
+            swap_fee: Decimal::zero(),
+        };
+        let wallet = |deps: Deps, address: &str| -> WalletPurchasesResponse {
+            let msg = QueryMsg::WalletPurchases {
+                address: address.to_string(),
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+
+        sudo(deps.as_mut(), mock_env(), buy_out("alice", 5)).unwrap();
+        let err = sudo(deps.as_mut(), mock_env(), buy_out("alice", 1)).unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::WalletLimitExceeded { max_per_address } if max_per_address.u128() == 5
+        ));
+
+        // sells free up room for more buys on both swap paths
+        sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountIn {
+                sender: "alice".to_string(),
+                token_in: coin(2, supply_denom()),
+                token_out_denom: RESERVE_DENOM.to_string(),
+                token_out_min_amount: Uint128::zero(),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            wallet(deps.as_ref(), "alice"),
+            WalletPurchasesResponse {
+                net_purchased: Uint128::new(3),
+                exempt: false,
+                max_per_address: Some(Uint128::new(5)),
+                remaining: Some(Uint128::new(2)),
+            }
+        );
+        let err = sudo(deps.as_mut(), mock_env(), buy_in("alice", 3)).unwrap_err();
+        assert!(matches!(err, ContractError::WalletLimitExceeded { .. }));
+        sudo(deps.as_mut(), mock_env(), buy_in("alice", 2)).unwrap();
+
+        // standalone swaps count for the sender whoever receives the output
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1, supply_denom())),
+            ExecuteMsg::Sell {
+                min_out: Uint128::zero(),
+                recipient: Some("fresh".to_string()),
+                deadline: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            wallet(deps.as_ref(), "alice").net_purchased,
+            Uint128::new(4)
+        );
+        let buy_for_fresh = ExecuteMsg::Buy {
+            min_out: Uint128::zero(),
+            recipient: Some("fresh".to_string()),
+            deadline: None,
+            allowlist_proof: None,
+        };
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1_500000, RESERVE_DENOM)),
+            buy_for_fresh.clone(),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1_500000, RESERVE_DENOM)),
+            buy_for_fresh,
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::WalletLimitExceeded { .. }));
+        assert_eq!(
+            wallet(deps.as_ref(), "fresh").net_purchased,
+            Uint128::zero()
+        );
+
+        // the owner exempts wallets from the cap
+        let exempt_treasury = ExecuteMsg::UpdateWalletExemptions {
+            add: vec!["treasury".to_string()],
+            remove: vec![],
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            exempt_treasury.clone(),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            exempt_treasury,
+        )
+        .unwrap();
+        sudo(deps.as_mut(), mock_env(), buy_out("treasury", 10)).unwrap();
+        assert_eq!(wallet(deps.as_ref(), "treasury").remaining, None);
+        let exempt_wallets = |deps: Deps| -> ExemptWalletsResponse {
+            let msg = QueryMsg::ExemptWallets {
+                start_after: None,
+                limit: None,
+            };
+            from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap()
+        };
+        assert_eq!(exempt_wallets(deps.as_ref()).wallets, vec!["treasury"]);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateWalletExemptions {
+                add: vec![],
+                remove: vec!["treasury".to_string()],
+            },
+        )
+        .unwrap();
+        assert!(exempt_wallets(deps.as_ref()).wallets.is_empty());
+        let err = sudo(deps.as_mut(), mock_env(), buy_out("treasury", 1)).unwrap_err();
+        assert!(matches!(err, ContractError::WalletLimitExceeded { .. }));
+
+        // lifting the cap opens buys to everyone
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::UpdateMaxPerAddress {
+                max_per_address: None,
+            },
+        )
+        .unwrap();
+        sudo(deps.as_mut(), mock_env(), buy_out("alice", 1)).unwrap();
+        assert_eq!(
+            wallet(deps.as_ref(), "alice").net_purchased,
+            Uint128::new(6)
+        );
+    }
+
+    #[test]
+    fn circuit_breaker() {
+        let breaker = CircuitBreakerConfig {
+            window: BreakerWindow::Blocks { blocks: 10 },
+            max_price_change: None,
+            max_reserve_inflow: Some(Uint128::new(6_000000)),
+            max_reserve_outflow: Some(Uint128::new(3_000000)),
+        };
+        let mut deps = mock_dependencies();
+        let err = instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                circuit_breaker: Some(CircuitBreakerConfig {
+                    window: BreakerWindow::Blocks { blocks: 0 },
+                    ..breaker.clone()
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidCircuitBreaker { .. }));
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                circuit_breaker: Some(breaker),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: "alice".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(amount * 1_500000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let sell = |amount: u128| SudoMsg::SwapExactAmountIn {
+            sender: "alice".to_string(),
+            token_in: coin(amount, supply_denom()),
+            token_out_denom: RESERVE_DENOM.to_string(),
+            token_out_min_amount: Uint128::zero(),
+            swap_fee: Decimal::zero(),
+        };
+        let breaker_status = |deps: Deps| -> CircuitBreakerResponse {
+            from_binary(&query(deps, mock_env(), QueryMsg::CircuitBreaker {}).unwrap()).unwrap()
+        };
+        let resume = |deps: DepsMut| {
+            execute(
+                deps,
+                mock_env(),
+                mock_info("creator", &[]),
+                ExecuteMsg::SetActive { is_active: true },
+            )
+            .unwrap();
+        };
+
+        // the buy that takes the inflow above its limit is refunded and pauses the pool
+        sudo(deps.as_mut(), mock_env(), buy(4)).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1_500000, RESERVE_DENOM)),
+            ExecuteMsg::Buy {
+                min_out: Uint128::zero(),
+                recipient: None,
+                deadline: None,
+                allowlist_proof: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(1_500000, RESERVE_DENOM),
+            })]
+        );
+        assert_eq!(res.events[0].ty, "circuit_breaker_tripped");
+        assert_eq!(
+            CURVE_STATE.load(&deps.storage).unwrap().supply,
+            Uint128::new(4)
+        );
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        let trip = breaker_status(deps.as_ref()).trip.unwrap();
+        assert_eq!(
+            trip.reason,
+            "reserve inflow of 7500000 within the window, above 6000000"
+        );
+        sudo(deps.as_mut(), mock_env(), buy(1)).unwrap_err();
+
+        // only the owner can resume the pool, which starts a new window
+        let err = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SetActive { is_active: true },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::CircuitBreakerTripped { .. }));
+        resume(deps.as_mut());
+        let status = breaker_status(deps.as_ref());
+        assert_eq!((status.window, status.trip), (None, None));
+        sudo(deps.as_mut(), mock_env(), buy(1)).unwrap();
+
+        // sells count toward the outflow limit. Swaps through the cosmwasmpool module over a limit
+        // answer without output, refund `token_in` and pause the pool.
+        sudo(deps.as_mut(), mock_env(), sell(2)).unwrap();
+        let window = breaker_status(deps.as_ref()).window;
+        let res = sudo(deps.as_mut(), mock_env(), sell(1)).unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(1, supply_denom()),
+            })]
+        );
+        assert_eq!(
+            res.data,
+            Some(
+                to_binary(&SwapExactAmountInResponseData {
+                    token_out_amount: Uint128::zero()
+                })
+                .unwrap()
+            )
+        );
+        let reason = "reserve outflow of 4500000 within the window, above 3000000";
+        assert_eq!(
+            res.events,
+            vec![Event::new("circuit_breaker_tripped").add_attribute("reason", reason)]
+        );
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        let status = breaker_status(deps.as_ref());
+        assert_eq!(status.window, window);
+        assert_eq!(status.trip.unwrap().reason, reason);
+        assert_eq!(
+            CURVE_STATE.load(&deps.storage).unwrap().supply,
+            Uint128::new(3)
+        );
+        resume(deps.as_mut());
+
+        // the module returns the unused `token_in_max_amount` of exact out swaps itself
+        sudo(deps.as_mut(), mock_env(), sell(2)).unwrap();
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SwapExactAmountOut {
+                sender: "alice".to_string(),
+                token_in_denom: supply_denom(),
+                token_in_max_amount: Uint128::new(1),
+                token_out: coin(1_500000, RESERVE_DENOM),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            res.data,
+            Some(
+                to_binary(&SwapExactAmountOutResponseData {
+                    token_in_amount: Uint128::zero()
+                })
+                .unwrap()
+            )
+        );
+        assert_eq!(res.events[0].ty, "circuit_breaker_tripped");
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        assert_eq!(
+            CURVE_STATE.load(&deps.storage).unwrap().supply,
+            Uint128::new(1)
+        );
+        resume(deps.as_mut());
+
+        // standalone sells pause the pool and refund the supply tokens
+        sudo(deps.as_mut(), mock_env(), buy(2)).unwrap();
+        sudo(deps.as_mut(), mock_env(), sell(2)).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1, supply_denom())),
+            ExecuteMsg::Sell {
+                min_out: Uint128::zero(),
+                recipient: Some("friend".to_string()),
+                deadline: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            res.messages,
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(1, supply_denom()),
+            })]
+        );
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        resume(deps.as_mut());
+
+        // limits apply per window
+        sudo(deps.as_mut(), mock_env(), buy(2)).unwrap();
+        sudo(deps.as_mut(), mock_env(), sell(2)).unwrap();
+        let mut env = mock_env();
+        env.block.height += 10;
+        sudo(deps.as_mut(), env, sell(1)).unwrap();
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+    }
+
+    #[test]
+    fn circuit_breaker_price_change() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                // spot price is the supply in osmo
+                curve_type: CurveType::Linear {
+                    slope: Uint128::one(),
+                    scale: 0,
+                },
+                circuit_breaker: Some(CircuitBreakerConfig {
+                    window: BreakerWindow::Seconds { seconds: 60 },
+                    max_price_change: Some(Decimal::percent(50)),
+                    max_reserve_inflow: None,
+                    max_reserve_outflow: None,
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: "alice".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(1_000_000000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let later = || {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(60);
+            env
+        };
+
+        // no change can be measured from the zero price at the start of the curve
+        sudo(deps.as_mut(), mock_env(), buy(10)).unwrap();
+
+        sudo(deps.as_mut(), later(), buy(5)).unwrap();
+        assert!(load_pause_state(&deps.storage).unwrap().is_active());
+        let res = sudo(deps.as_mut(), later(), buy(1)).unwrap();
+        assert_eq!(res.events[0].ty, "circuit_breaker_tripped");
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+        assert_eq!(
+            CURVE_STATE.load(&deps.storage).unwrap().supply,
+            Uint128::new(15)
+        );
+    }
+
+    #[test]
+    fn pause_switches() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+        let buy = SudoMsg::SwapExactAmountOut {
+            sender: "alice".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(1_500000),
+            token_out: coin(1, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let sell = SudoMsg::SwapExactAmountIn {
+            sender: "alice".to_string(),
+            token_in: coin(1, supply_denom()),
+            token_out_denom: RESERVE_DENOM.to_string(),
+            token_out_min_amount: Uint128::zero(),
+            swap_fee: Decimal::zero(),
+        };
+        let pause = |buys, sells, dissolve| ExecuteMsg::UpdatePauseState {
+            buys,
+            sells,
+            dissolve,
+        };
+        let is_active = |deps: Deps| -> bool {
+            let res: IsActiveResponse =
+                from_binary(&query(deps, mock_env(), QueryMsg::IsActive {}).unwrap()).unwrap();
+            res.is_active
+        };
+        sudo(deps.as_mut(), mock_env(), buy.clone()).unwrap();
+        sudo(deps.as_mut(), mock_env(), buy.clone()).unwrap();
+
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            pause(Some(true), None, None),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // holders can still exit while buys are paused
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            pause(Some(true), None, None),
+        )
+        .unwrap();
+        assert_eq!(
+            res.events,
+            vec![Event::new("pause_switch")
+                .add_attribute("switch", "buys")
+                .add_attribute("paused", "true")]
+        );
+        let err = sudo(deps.as_mut(), mock_env(), buy.clone()).unwrap_err();
+        assert!(matches!(err, ContractError::Paused { operation } if operation == "buys"));
+        sudo(deps.as_mut(), mock_env(), sell.clone()).unwrap();
+        assert!(is_active(deps.as_ref()));
+
+        // dissolves can be frozen on their own
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            pause(None, None, Some(true)),
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1, supply_denom())),
+            ExecuteMsg::Dissolve {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Paused { operation } if operation == "dissolve"));
+
+        // the pool is inactive for the cosmwasmpool module once both swap directions are paused
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            pause(None, Some(true), None),
+        )
+        .unwrap();
+        assert!(!is_active(deps.as_ref()));
+        let err = sudo(deps.as_mut(), mock_env(), sell.clone()).unwrap_err();
+        assert_eq!(err.to_string(), "Generic error: Contract is not active");
+
+        // SetActive switches buys and sells together
+        let res = sudo(
+            deps.as_mut(),
+            mock_env(),
+            SudoMsg::SetActive { is_active: true },
+        )
+        .unwrap();
+        assert_eq!(res.events.len(), 2);
+        let pause_state: PauseState =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PauseState {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            pause_state,
+            PauseState {
+                buys: false,
+                sells: false,
+                dissolve: true,
+            }
+        );
+        sudo(deps.as_mut(), mock_env(), sell).unwrap();
+    }
+
+    #[test]
+    fn twap() {
+        let start = mock_env().block.time;
+        let at = |seconds| {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(seconds);
+            env
+        };
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                // spot price is the supply in osmo
+                curve_type: CurveType::Linear {
+                    slope: Uint128::one(),
+                    scale: 0,
+                },
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: "alice".to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(1_000_000000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let twap = |deps: Deps, now, start_time, end_time| {
+            let msg = QueryMsg::Twap {
+                start_time: start.plus_seconds(start_time),
+                end_time: start.plus_seconds(end_time),
+            };
+            query(deps, at(now), msg).map(|res| from_binary::<TwapResponse>(&res).unwrap().twap)
+        };
+
+        // zero for 1000 seconds, then 10 osmo for 2000 seconds, then 20 osmo
+        sudo(deps.as_mut(), at(1000), buy(10)).unwrap();
+        sudo(deps.as_mut(), at(3000), buy(10)).unwrap();
+        let ten = Decimal::from_ratio(10u128, 1u128);
+        assert_eq!(twap(deps.as_ref(), 4000, 1000, 3000).unwrap(), ten);
+        assert_eq!(
+            twap(deps.as_ref(), 4000, 0, 3000).unwrap(),
+            Decimal::from_ratio(20u128, 3u128)
+        );
+        assert_eq!(
+            twap(deps.as_ref(), 4000, 2000, 4000).unwrap(),
+            Decimal::from_ratio(15u128, 1u128)
+        );
+        let res: TwapResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                at(4000),
+                QueryMsg::ArithmeticTwapToNow {
+                    start_time: start.plus_seconds(3000),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(res.twap, Decimal::from_ratio(20u128, 1u128));
+
+        for (start_time, end_time) in [(3000, 3000), (0, 5000)] {
+            let err = twap(deps.as_ref(), 4000, start_time, end_time).unwrap_err();
+            assert!(matches!(err, ContractError::InvalidTwapRange { .. }));
+        }
+        let err = query(
+            deps.as_ref(),
+            at(4000),
+            QueryMsg::ArithmeticTwapToNow {
+                start_time: start.minus_seconds(1),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTwapRange { .. }));
+    }
+
+    #[test]
+    fn trade_history() {
+        let at = |seconds| {
+            let mut env = mock_env();
+            env.block.time = env.block.time.plus_seconds(seconds);
+            env
+        };
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            InstantiateMsg {
+                // spot price is the supply in osmo
+                curve_type: CurveType::Linear {
+                    slope: Uint128::one(),
+                    scale: 0,
+                },
+                trade_history: Some(TradeHistoryConfig {
+                    max_trades: Some(3),
+                    max_age: None,
+                }),
+                ..default_instantiate_msg()
+            },
+        )
+        .unwrap();
+        let buy = |sender: &str, amount: u128| SudoMsg::SwapExactAmountOut {
+            sender: sender.to_string(),
+            token_in_denom: RESERVE_DENOM.to_string(),
+            token_in_max_amount: Uint128::new(1_000_000000),
+            token_out: coin(amount, supply_denom()),
+            swap_fee: Decimal::zero(),
+        };
+        let ids = |deps: Deps, msg| {
+            let res: TradesResponse = from_binary(&query(deps, mock_env(), msg).unwrap()).unwrap();
+            res.trades
+                .into_iter()
+                .map(|trade| trade.id)
+                .collect::<Vec<_>>()
+        };
+
+        let res = sudo(deps.as_mut(), at(100), buy("alice", 10)).unwrap();
+        let SwapExactAmountOutResponseData { token_in_amount } =
+            from_binary(&res.data.unwrap()).unwrap();
+        sudo(deps.as_mut(), at(200), buy("bob", 10)).unwrap();
+        sudo(
+            deps.as_mut(),
+            at(300),
+            SudoMsg::SwapExactAmountIn {
+                sender: "alice".to_string(),
+                token_in: coin(5, supply_denom()),
+                token_out_denom: RESERVE_DENOM.to_string(),
+                token_out_min_amount: Uint128::zero(),
+                swap_fee: Decimal::zero(),
+            },
+        )
+        .unwrap();
+        let res: TradesResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::Trades {
+                    start_after: None,
+                    limit: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(
+            res.trades[0],
+            Trade {
+                id: 0,
+                trader: Addr::unchecked("alice"),
+                direction: TradeDirection::Buy,
+                amount_in: token_in_amount,
+                amount_out: Uint128::new(10),
+                price_before: Decimal::zero(),
+                price_after: Decimal::from_ratio(10u128, 1u128),
+                height: at(100).block.height,
+                time: at(100).block.time,
+            }
+        );
+        let sell = &res.trades[2];
+        assert_eq!(sell.direction, TradeDirection::Sell);
+        assert_eq!(sell.amount_in, Uint128::new(5));
+        assert_eq!(
+            (sell.price_before, sell.price_after),
+            (
+                Decimal::from_ratio(20u128, 1u128),
+                Decimal::from_ratio(15u128, 1u128)
+            )
+        );
+
+        // dissolves are priced on the dissolved curve, the oldest trade is pruned past 3 trades
+        execute(
+            deps.as_mut(),
+            at(400),
+            mock_info("alice", &coins(2, supply_denom())),
+            ExecuteMsg::Dissolve {},
+        )
+        .unwrap();
+        let trades = |start_after, limit| QueryMsg::Trades { start_after, limit };
+        assert_eq!(ids(deps.as_ref(), trades(None, None)), vec![1, 2, 3]);
+        assert_eq!(ids(deps.as_ref(), trades(Some(1), Some(1))), vec![2]);
+        let res: TradesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), trades(Some(2), None)).unwrap()).unwrap();
+        let dissolve = &res.trades[0];
+        assert_eq!(dissolve.direction, TradeDirection::Dissolve);
+        assert_eq!(
+            (dissolve.amount_in, dissolve.amount_out),
+            (Uint128::new(2), Uint128::new(2_000000))
+        );
+        assert_eq!(
+            (dissolve.price_before, dissolve.price_after),
+            (Decimal::zero(), Decimal::from_ratio(2u128, 1u128))
+        );
+
+        let by_trader = |trader: &str, start_after| QueryMsg::TradesByTrader {
+            trader: trader.to_string(),
+            start_after,
+            limit: None,
+        };
+        assert_eq!(ids(deps.as_ref(), by_trader("alice", None)), vec![2, 3]);
+        assert_eq!(ids(deps.as_ref(), by_trader("alice", Some(2))), vec![3]);
+        assert_eq!(ids(deps.as_ref(), by_trader("bob", None)), vec![1]);
+
+        let in_range = |start_after, limit| QueryMsg::TradesInRange {
+            start_time: at(200).block.time,
+            end_time: at(300).block.time,
+            start_after,
+            limit,
+        };
+        assert_eq!(ids(deps.as_ref(), in_range(None, None)), vec![1, 2]);
+        assert_eq!(ids(deps.as_ref(), in_range(None, Some(1))), vec![1]);
+        assert_eq!(ids(deps.as_ref(), in_range(Some(1), None)), vec![2]);
+        // pruned trades are older than every stored trade
+        assert_eq!(ids(deps.as_ref(), in_range(Some(0), None)), vec![1, 2]);
+
+        // only the owner bounds the history, with bounds above zero
+        let update = |max_trades, max_age| ExecuteMsg::UpdateTradeHistory {
+            trade_history: Some(TradeHistoryConfig {
+                max_trades,
+                max_age,
+            }),
+        };
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &[]),
+            update(None, Some(150)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            update(None, Some(0)),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err,
+            ContractError::InvalidTradeHistoryConfig { .. }
+        ));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            update(None, Some(150)),
+        )
+        .unwrap();
+        let res: Option<TradeHistoryConfig> =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::TradeHistory {}).unwrap())
+                .unwrap();
+        assert_eq!(res.unwrap().max_age, Some(150));
+
+        // trades older than 150 seconds are pruned on the next trade
+        sudo(deps.as_mut(), at(500), buy("bob", 1)).unwrap();
+        assert_eq!(ids(deps.as_ref(), trades(None, None)), vec![3, 4]);
+        assert_eq!(ids(deps.as_ref(), by_trader("bob", None)), vec![4]);
+    }
+
+    #[test]
+    fn two_step_ownership_transfer() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+
+        // only the owner can propose
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::ProposeNewOwner {
+                new_owner: "dao".to_string(),
+            },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        // nothing to accept yet
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::NoPendingOwner {}));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::ProposeNewOwner {
+                new_owner: "dao".to_string(),
+            },
+        )
+        .unwrap();
+        let ownership: Ownership =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::Ownership {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            ownership,
+            Ownership {
+                owner: Some(Addr::unchecked("creator")),
+                pending_owner: Some(Addr::unchecked("dao")),
+            }
+        );
+
+        // only the pending owner can accept
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("anyone", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::AcceptOwnership {},
+        )
+        .unwrap();
+
+        // the previous owner lost access
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            ExecuteMsg::SetActive { is_active: false },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::SetActive { is_active: false },
+        )
+        .unwrap();
+        assert!(!load_pause_state(&deps.storage).unwrap().is_active());
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::UpdateFeeConfig {
+                swap_fee: Some(Decimal::permille(3)),
+                fee_collector: Some("treasury".to_string()),
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            FEE_CONFIG.load(&deps.storage).unwrap(),
+            FeeConfig {
+                swap_fee: Decimal::permille(3),
+                fee_collector: Addr::unchecked("treasury"),
+            }
+        );
+
+        // after renouncing, nobody can run admin actions
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::RenounceOwnership {},
+        )
+        .unwrap();
+        let err = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("dao", &[]),
+            ExecuteMsg::SetActive { is_active: true },
+        )
+        .unwrap_err();
+        assert!(matches!(err, ContractError::Unauthorized {}));
+    }
+
+    #[test]
+    fn migrate_checks_contract_name_and_version() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidContractName { .. }));
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDowngrade { .. }));
+
+        // re-running the current version is a no-op
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, CONTRACT_VERSION).unwrap();
+        let fee_config = FEE_CONFIG.load(&deps.storage).unwrap();
+        migrate(
+            deps.as_mut(),
+            mock_env(),
+            MigrateMsg::UpgradeWithConfig {
+                owner: None,
+                swap_fee: Some(Decimal::percent(50)),
+                fee_collector: None,
+                max_supply: None,
+            },
+        )
+        .unwrap();
+        assert_eq!(FEE_CONFIG.load(&deps.storage).unwrap(), fee_config);
+    }
+
+    #[test]
+    fn migrate_from_v0_1_fills_missing_state() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            default_instantiate_msg(),
+        )
+        .unwrap();
+
+        // rewind storage to what a 0.1.x pool with 100 tokens sold looked like
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.1.0").unwrap();
+        OWNERSHIP.remove(&mut deps.storage);
+        FEE_CONFIG.remove(&mut deps.storage);
+        PAUSE_STATE.remove(&mut deps.storage);
+        RESERVE_ASSET.remove(&mut deps.storage);
+        IS_ACTIVE.save(&mut deps.storage, &false).unwrap();
+        let mut curve_state = CURVE_STATE.load(&deps.storage).unwrap();
+        curve_state.max_supply = None;
+        curve_state.supply = Uint128::new(100);
+        CURVE_STATE.save(&mut deps.storage, &curve_state).unwrap();
+        let mut dissolved = DISSOLVED_CURVE_STATE.load(&deps.storage).unwrap();
+        dissolved.max_supply = None;
+        DISSOLVED_CURVE_STATE
+            .save(&mut deps.storage, &dissolved)
+            .unwrap();
+
+        let upgrade = |max_supply: u128| MigrateMsg::UpgradeWithConfig {
+            owner: Some("dao".to_string()),
+            swap_fee: Some(Decimal::permille(3)),
+            fee_collector: None,
+            max_supply: Some(Uint128::new(max_supply)),
+        };
+
+        // the cap cannot be below what was already sold
+        let err = migrate(deps.as_mut(), mock_env(), upgrade(99)).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidMaxSupply {}));
+
+        let res = migrate(deps.as_mut(), mock_env(), upgrade(1_000)).unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|attr| attr.key == "from_version" && attr.value == "0.1.0"));
+
+        assert_eq!(
+            OWNERSHIP.load(&deps.storage).unwrap(),
+            Ownership {
+                owner: Some(Addr::unchecked("dao")),
+                pending_owner: None,
+            }
+        );
+        assert_eq!(
+            FEE_CONFIG.load(&deps.storage).unwrap(),
+            FeeConfig {
+                swap_fee: Decimal::permille(3),
+                fee_collector: Addr::unchecked("dao"),
+            }
+        );
+        assert_eq!(
+            CURVE_STATE.load(&deps.storage).unwrap().max_supply,
+            Some(Uint128::new(1_000))
+        );
+        assert_eq!(
+            DISSOLVED_CURVE_STATE
+                .load(&deps.storage)
+                .unwrap()
+                .max_supply,
+            Some(Uint128::new(1_000))
+        );
+        // the single active flag became a pause of both swap directions
+        assert_eq!(
+            PAUSE_STATE.load(&deps.storage).unwrap(),
+            PauseState {
+                buys: true,
+                sells: true,
+                dissolve: false,
+            }
+        );
+        assert_eq!(IS_ACTIVE.may_load(&deps.storage).unwrap(), None);
+        assert_eq!(
+            RESERVE_ASSET.load(&deps.storage).unwrap(),
+            ReserveAsset::Native
+        );
+        // states serialized before cw20 reserves were supported still deserialize
+        let state = query(deps.as_ref(), mock_env(), QueryMsg::BondingPoolState {}).unwrap();
+        let state = String::from_utf8(state.to_vec()).unwrap();
+        assert!(state.contains(r#","reserve_asset":"native""#));
+        let state = state.replace(r#","reserve_asset":"native""#, "");
+        let state: BondingPoolState = from_binary(&Binary::from(state.as_bytes())).unwrap();
+        assert_eq!(state.reserve_asset, ReserveAsset::Native);
+        assert_eq!(
+            get_contract_version(&deps.storage).unwrap().version,
+            CONTRACT_VERSION
+        );
+    }
 }
@@ -1,11 +1,9 @@
 use cosmwasm_schema::cw_serde;
 use cosmwasm_std::{Decimal as StdDecimal, Uint128};
-use integer_cbrt::IntegerCubeRoot;
-use integer_sqrt::IntegerSquareRoot;
-use num_integer::Roots;
-use rust_decimal::prelude::ToPrimitive;
-use rust_decimal::Decimal as RustDecimal;
-use std::str::FromStr;
+use num_integer::Integer;
+
+use crate::error::ContractError;
+use crate::fixed::{Fixed, Rounding};
 
 /// This defines the curves we are using.
 ///
@@ -19,61 +17,77 @@ use std::str::FromStr;
 /// when you want to do more complex math than add and multiply `Uint128`, I decided to go the second
 /// route. That made the signatures quite complex and my final idea was to pass in `supply_decimal`
 /// and `reserve_decimal` in the curve constructors.
+///
+/// The math itself runs on `Fixed`, a 36 decimal place fixed point number, so reserve and supply
+/// tokens with up to 18 decimals are exact to the base unit.
+///
+/// All methods return `CurveOverflow` when a value outgrows `Fixed` (~10^41) or Uint128,
+/// `PrecisionLoss` when a value can't be represented at the decimal places it needs, and
+/// `DivisionByZero` for degenerate curve parameters.
 pub trait Curve {
     /// Returns the spot price given the supply.
     /// `f(x)` from the README
-    fn spot_price(&self, supply: Uint128) -> StdDecimal;
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError>;
 
-    /// Returns the total price paid up to purchase supply tokens (integral)
+    /// Returns the total price paid up to purchase supply tokens (integral), both in whole tokens.
     /// `F(x)` from the README
-    fn reserve(&self, supply: Uint128) -> Uint128;
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError>;
 
-    /// Inverse of reserve. Returns how many tokens would be issued
-    /// with a total paid amount of reserve.
+    /// Inverse of reserve_value. Returns how many tokens would be issued
+    /// with a total paid amount of reserve, both in whole tokens.
     /// `F^-1(x)` from the README
-    fn supply(&self, reserve: Uint128) -> Uint128;
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError>;
+
+    /// Decimal places of the supply and reserve tokens
+    fn decimals(&self) -> &DecimalPlaces;
+
+    /// `reserve_value` in base units, rounded to whole reserve base units in the given direction
+    fn reserve(&self, supply: Uint128, rounding: Rounding) -> Result<Uint128, ContractError> {
+        let decimals = self.decimals();
+        let reserve = self.reserve_value(decimals.from_supply(supply)?)?;
+        decimals.to_reserve(reserve, rounding)
+    }
+
+    /// `supply_value` in base units, rounded to whole supply base units in the given direction
+    fn supply(&self, reserve: Uint128, rounding: Rounding) -> Result<Uint128, ContractError> {
+        let decimals = self.decimals();
+        let supply = self.supply_value(decimals.from_reserve(reserve)?)?;
+        decimals.to_supply(supply, rounding)
+    }
 }
 
 /// decimal returns an object = num * 10 ^ -scale
 /// We use this function in contract.rs rather than call the crate constructor
 /// itself, in case we want to swap out the implementation, we can do it only in this file.
-pub fn decimal<T: Into<u128>>(num: T, scale: u32) -> RustDecimal {
-    RustDecimal::from_i128_with_scale(num.into() as i128, scale)
-}
-
-/// StdDecimal stores as a u128 with 18 decimal points of precision
-fn decimal_to_std(x: RustDecimal) -> StdDecimal {
-    // this seems straight-forward (if inefficient), converting via string representation
-    // TODO: execute errors better? Result?
-    // cut off at 18 decimal places
-    let stringified = x.to_string();
-    let parts: Vec<&str> = stringified.split('.').collect();
-    let whole = parts[0];
-    let frac = parts.get(1).unwrap_or(&"0");
-    let frac = frac.chars().take(18).collect::<String>();
-    let stringified = format!("{}.{}", whole, frac);
-    StdDecimal::from_str(&stringified).unwrap()
-
-    // // maybe a better approach doing math, not sure about rounding
-    //
-    // // try to preserve decimal points, max 9
-    // let digits = min(x.scale(), 9);
-    // let multiplier = 10u128.pow(digits);
-    //
-    // // we multiply up before we round off to u128,
-    // // let StdDecimal do its best to keep these decimal places
-    // let nominator = (x * decimal(multiplier, 0)).to_u128().unwrap();
-    // StdDecimal::from_ratio(nominator, multiplier)
+///
+/// Panics if `scale` is above 36, use `checked_decimal` for anything that isn't a constant.
+pub fn decimal<T: Into<u128>>(num: T, scale: u32) -> Fixed {
+    checked_decimal(num, scale).unwrap()
+}
+
+/// Same as `decimal`, but errors instead of panicking
+pub fn checked_decimal<T: Into<u128>>(num: T, scale: u32) -> Result<Fixed, ContractError> {
+    Fixed::from_atomics(num, scale)
+}
+
+/// Inverse of `decimal_to_std`, exact as StdDecimal has 18 decimal places
+pub fn std_to_decimal(x: StdDecimal) -> Fixed {
+    Fixed::from_std(x)
+}
+
+/// StdDecimal stores as a u128 with 18 decimal points of precision, digits beyond that are cut off
+fn decimal_to_std(x: Fixed) -> Result<StdDecimal, ContractError> {
+    x.to_std()
 }
 
 /// spot price is always a constant value
 pub struct Constant {
-    pub value: RustDecimal,
+    pub value: Fixed,
     pub normalize: DecimalPlaces,
 }
 
 impl Constant {
-    pub fn new(value: RustDecimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(value: Fixed, normalize: DecimalPlaces) -> Self {
         Self { value, normalize }
     }
 }
@@ -81,255 +95,444 @@ impl Constant {
 impl Curve for Constant {
     // we need to normalize value with the reserve decimal places
     // (eg 0.1 value would return 100_000 if reserve was uatom)
-    fn spot_price(&self, _supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, _supply: Uint128) -> Result<StdDecimal, ContractError> {
         // f(x) = self.value
         decimal_to_std(self.value)
     }
 
     /// Returns total number of reserve tokens needed to purchase a given number of supply tokens.
-    /// Note that both need to be normalized.
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
         // f(x) = supply * self.value
-        let reserve = self.normalize.from_supply(supply) * self.value;
-        self.normalize.clone().to_reserve(reserve)
+        let reserve = supply.checked_mul(self.value)?;
+        Ok(reserve)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
         // f(x) = reserve / self.value
-        let supply = self.normalize.from_reserve(reserve) / self.value;
-        self.normalize.clone().to_supply(supply)
+        let supply = reserve.checked_div(self.value)?;
+        Ok(supply)
+    }
+
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
     }
 }
 
 /// spot_price is slope * supply
 pub struct Linear {
-    pub slope: RustDecimal,
+    pub slope: Fixed,
     pub normalize: DecimalPlaces,
 }
 
 impl Linear {
-    pub fn new(slope: RustDecimal, normalize: DecimalPlaces) -> Self {
+    pub fn new(slope: Fixed, normalize: DecimalPlaces) -> Self {
         Self { slope, normalize }
     }
 }
 
 impl Curve for Linear {
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError> {
         // f(x) = supply * self.value
-        let out = self.normalize.from_supply(supply) * self.slope;
+        let out = self
+            .normalize
+            .from_supply(supply)?
+            .checked_mul(self.slope)?;
         decimal_to_std(out)
     }
 
-    fn reserve(&self, supply: Uint128) -> Uint128 {
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
         // f(x) = self.slope * supply * supply / 2
-        let normalized = self.normalize.from_supply(supply);
-        let square = normalized * normalized;
-        // Note: multiplying by 0.5 is much faster than dividing by 2
-        let reserve = square * self.slope * RustDecimal::new(5, 1);
-        self.normalize.clone().to_reserve(reserve)
+        let square = supply.checked_mul(supply)?;
+        let reserve = square
+            .checked_mul(self.slope)?
+            .checked_div(Fixed::from(2u32))?;
+        Ok(reserve)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
         // f(x) = (2 * reserve / self.slope) ^ 0.5
         // note: use addition here to optimize 2* operation
-        let square = self.normalize.from_reserve(reserve + reserve) / self.slope;
-        let supply = square_root(square);
-        self.normalize.clone().to_supply(supply)
+        let square = reserve.checked_add(reserve)?.checked_div(self.slope)?;
+        let supply = square.checked_nth_root(2)?;
+        Ok(supply)
+    }
+
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
     }
 }
 
-/// spot_price is slope * (supply)^0.5
-pub struct SquareRoot {
-    pub slope: RustDecimal,
+/// Power Curve Math:
+///
+/// spot_price = `f(x) = k * x^(p/q)`
+/// reserve = `F(x) = (k * q / (p + q)) * x^((p + q) / q)`
+/// supply = `F^-1(x) = ((p + q) * x / (k * q))^(q / (p + q))`
+///
+/// where:
+///
+/// `k` is the slope
+/// `p / q` is the exponent, with `q > 0`
+/// `x` is the supply
+///
+/// Roots are exact to the 36 decimal places of `Fixed`, so `reserve` and `supply` are only off by
+/// their rounding to whole base units.
+pub struct Power {
+    pub slope: Fixed,
+    pub numerator: u32,
+    pub denominator: u32,
     pub normalize: DecimalPlaces,
 }
 
-impl SquareRoot {
-    pub fn new(slope: RustDecimal, normalize: DecimalPlaces) -> Self {
-        Self { slope, normalize }
+impl Power {
+    pub fn new(slope: Fixed, numerator: u32, denominator: u32, normalize: DecimalPlaces) -> Self {
+        Self {
+            slope,
+            numerator,
+            denominator,
+            normalize,
+        }
+    }
+
+    /// `p + q`, the exponent of the integral
+    fn integral_exponent(&self) -> Result<u32, ContractError> {
+        self.numerator
+            .checked_add(self.denominator)
+            .ok_or(ContractError::CurveOverflow {})
     }
 }
 
-impl Curve for SquareRoot {
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
-        // f(x) = self.slope * supply^0.5
-        let square = self.normalize.from_supply(supply);
-        let root = square_root(square);
-        decimal_to_std(root * self.slope)
+impl Curve for Power {
+    /// spot_price = `f(x) = k * x^(p/q)`
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError> {
+        let normalized = self.normalize.from_supply(supply)?;
+        let raised = pow_ratio(normalized, self.numerator, self.denominator)?;
+        decimal_to_std(self.slope.checked_mul(raised)?)
     }
 
-    fn reserve(&self, supply: Uint128) -> Uint128 {
-        // f(x) = self.slope * supply * supply^0.5 / 1.5
-        let normalized = self.normalize.from_supply(supply);
-        let root = square_root(normalized);
-        let reserve = self.slope * normalized * root / RustDecimal::new(15, 1);
-        self.normalize.clone().to_reserve(reserve)
+    /// reserve = `F(x) = (k * q / (p + q)) * x^((p + q) / q)`
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
+        let exponent = self.integral_exponent()?;
+        let raised = pow_ratio(supply, exponent, self.denominator)?;
+        let factor = self
+            .slope
+            .checked_mul(Fixed::from(self.denominator))?
+            .checked_div(Fixed::from(exponent))?;
+        factor.checked_mul(raised)
     }
 
-    fn supply(&self, reserve: Uint128) -> Uint128 {
-        // f(x) = (1.5 * reserve / self.slope) ^ (2/3)
-        let base = self.normalize.from_reserve(reserve) * RustDecimal::new(15, 1) / self.slope;
-        let squared = base * base;
-        let supply = cube_root(squared);
-        self.normalize.clone().to_supply(supply)
+    /// supply = `F^-1(x) = ((p + q) * x / (k * q))^(q / (p + q))`
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
+        let exponent = self.integral_exponent()?;
+        let base = Fixed::from(exponent)
+            .checked_mul(reserve)?
+            .checked_div(self.slope.checked_mul(Fixed::from(self.denominator))?)?;
+        let supply = pow_ratio(base, self.denominator, exponent)?;
+        Ok(supply)
+    }
+
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
     }
 }
 
-/// Cube Root Squared Curve Math:
+/// Exponential Curve Math:
 ///
-/// spot_price = `f(x) = k * ((x)^(1/3))^2`
-/// reserve = `F(x) = ((3 * k) / 5) * x^(5/3) + C`
-/// supply = `F^-1(x) = (((5/(3*k)) * x) ^ (1/5)) ^ 3`
+/// spot_price = `f(x) = b^x`
+/// reserve = `F(x) = (b^x - 1) / ln(b)`
+/// supply = `F^-1(x) = ln(1 + x * ln(b)) / ln(b)`
 ///
 /// where:
 ///
-/// `k` is the slope
-/// `C` is the constant
+/// `b` is the base, which must be greater than 1
 /// `x` is the supply
-
-pub struct CubeRootSquared {
-    pub slope: RustDecimal,
+///
+/// `exp` and `ln` are evaluated to ~34 significant digits, well below the reserve and supply
+/// precision, so `reserve` and `supply` are only off by their rounding to whole base units.
+pub struct Exponential {
+    pub base: Fixed,
+    /// `ln(base)`, computed once
+    pub ln_base: Fixed,
     pub normalize: DecimalPlaces,
 }
 
-impl CubeRootSquared {
-    pub fn new(slope: RustDecimal, normalize: DecimalPlaces) -> Self {
-        Self { slope, normalize }
+impl Exponential {
+    pub fn new(base: Fixed, normalize: DecimalPlaces) -> Result<Self, ContractError> {
+        Ok(Self {
+            base,
+            ln_base: base.checked_ln()?,
+            normalize,
+        })
+    }
+
+    /// `b^x`
+    fn pow(&self, normalized: Fixed) -> Result<Fixed, ContractError> {
+        normalized.checked_mul(self.ln_base)?.checked_exp()
     }
 }
 
-impl Curve for CubeRootSquared {
-    /// spot_price = `f(x) = k * ((x)^(1/3))^2`
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
-        let normalized = self.normalize.from_supply(supply);
-        let cube_root = cube_root(normalized);
-        let squared = cube_root * cube_root;
-        decimal_to_std(squared * self.slope)
+impl Curve for Exponential {
+    /// spot_price = `f(x) = b^x`
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError> {
+        let normalized = self.normalize.from_supply(supply)?;
+        decimal_to_std(self.pow(normalized)?)
+    }
+
+    /// reserve = `F(x) = (b^x - 1) / ln(b)`
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
+        let reserve = self
+            .pow(supply)?
+            .checked_sub(Fixed::ONE)?
+            .checked_div(self.ln_base)?;
+        Ok(reserve)
     }
 
-    /// reserve = `F(x) = ((3 * k) / 5) * x^(5/3) + C`
-    fn reserve(&self, supply: Uint128) -> Uint128 {
-        let normalized = self.normalize.from_supply(supply);
-        let cube_root = cube_root(normalized);
-        let reserve = ((RustDecimal::from(3) * self.slope) / RustDecimal::from(5))
-            * (cube_root * cube_root * cube_root * cube_root * cube_root);
-        self.normalize.clone().to_reserve(reserve)
+    /// supply = `F^-1(x) = ln(1 + x * ln(b)) / ln(b)`
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
+        let scaled = Fixed::ONE.checked_add(reserve.checked_mul(self.ln_base)?)?;
+        let supply = scaled.checked_ln()?.checked_div(self.ln_base)?;
+        Ok(supply)
     }
 
-    /// supply = `F^-1(x) = (((5/(3*k)) * x) ^ (1/5)) ^ 3`
-    fn supply(&self, reserve: Uint128) -> Uint128 {
-        let normalized = self.normalize.from_reserve(reserve);
-        let base = (RustDecimal::from(5) / (RustDecimal::from(3) * self.slope)) * normalized;
-        let root = fifth_root(base);
-        let supply = root * root * root;
-        self.normalize.clone().to_supply(supply)
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
     }
 }
 
-/// SquareRootCubed Curve Math:
+/// Sigmoid (logistic) Curve Math:
 ///
-/// spot_price = `f(x) = k * ((x)^(1/2))^3`
-/// reserve = `F(x) = (2k/5) * (x^(1/2))^5 + C`
-/// supply = `F^-1(x) = ((5 * (x - C) / (2 * k))^(1/5)) ^ 2`
+/// spot_price = `f(x) = M / (1 + e^(-k * (x - m)))`
+/// reserve = `F(x) = (M / k) * (s(k * (x - m)) - s(-k * m))`
+/// supply = `F^-1(x) = m + s^-1(k * x / M + s(-k * m)) / k`
 ///
 /// where:
 ///
-/// `k` is the slope
-/// `C` is the constant
+/// `M` is the max price, approached as supply grows
+/// `m` is the midpoint, where the price is `M / 2`
+/// `k` is the steepness
+/// `s(t) = ln(1 + e^t)` is the softplus function, `s^-1(u) = ln(e^u - 1)` its inverse
 /// `x` is the supply
-
-pub struct SquareRootCubed {
-    pub slope: RustDecimal,
-    pub constant: RustDecimal, // The constant C in the functions
+///
+/// Both softplus and its inverse are rearranged to only ever exponentiate non-positive numbers, so
+/// they don't overflow far from the midpoint. Like `Exponential`, `reserve` and `supply` are only
+/// off by their rounding to whole base units.
+pub struct Sigmoid {
+    pub max_price: Fixed,
+    pub midpoint: Fixed,
+    pub steepness: Fixed,
+    /// `s(-k * m)`, the softplus at zero supply, computed once
+    pub offset: Fixed,
     pub normalize: DecimalPlaces,
 }
 
-impl SquareRootCubed {
-    pub fn new(slope: RustDecimal, normalize: DecimalPlaces) -> Self {
-        Self {
-            slope,
-            constant: RustDecimal::ZERO,
+impl Sigmoid {
+    /// `midpoint` is given in supply base units
+    pub fn new(
+        max_price: Fixed,
+        midpoint: Uint128,
+        steepness: Fixed,
+        normalize: DecimalPlaces,
+    ) -> Result<Self, ContractError> {
+        let midpoint = normalize.from_supply(midpoint)?;
+        Ok(Self {
+            max_price,
+            midpoint,
+            steepness,
+            offset: softplus((-steepness).checked_mul(midpoint)?)?,
             normalize,
-        }
+        })
+    }
+
+    /// `k * (x - m)`
+    fn exponent(&self, supply: Fixed) -> Result<Fixed, ContractError> {
+        self.steepness
+            .checked_mul(supply.checked_sub(self.midpoint)?)
     }
 }
 
-impl Curve for SquareRootCubed {
-    /// spot_price = `f(x) = k * ((x)^(1/2))^3`
-    fn spot_price(&self, supply: Uint128) -> StdDecimal {
-        let normalized = self.normalize.from_supply(supply);
-        let square_root = square_root(normalized);
-        let cubed = square_root * square_root * square_root;
-        decimal_to_std(cubed * self.slope)
+impl Curve for Sigmoid {
+    /// spot_price = `f(x) = M / (1 + e^(-k * (x - m)))`
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError> {
+        let t = self.exponent(self.normalize.from_supply(supply)?)?;
+        // same as the formula above, but only exponentiating -|t|
+        let e = (-t.abs()).checked_exp()?;
+        let numerator = if t.is_negative() {
+            self.max_price.checked_mul(e)?
+        } else {
+            self.max_price
+        };
+        decimal_to_std(numerator.checked_div(Fixed::ONE.checked_add(e)?)?)
+    }
+
+    /// reserve = `F(x) = (M / k) * (s(k * (x - m)) - s(-k * m))`
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
+        let softplus = softplus(self.exponent(supply)?)?;
+        let reserve = self
+            .max_price
+            .checked_div(self.steepness)?
+            .checked_mul(softplus.checked_sub(self.offset)?)?;
+        Ok(reserve.max(Fixed::ZERO))
     }
 
-    /// reserve = `F(x) = (2k/5) * (x^(1/2))^5 + C`
-    fn reserve(&self, supply: Uint128) -> Uint128 {
-        let normalized = self.normalize.from_supply(supply);
-        let square_root = square_root(normalized);
-        let raised = square_root * square_root * square_root * square_root * square_root;
-        let reserve =
-            (RustDecimal::from(2) * self.slope) / RustDecimal::from(5) * raised + self.constant;
-        self.normalize.clone().to_reserve(reserve)
+    /// supply = `F^-1(x) = m + s^-1(k * x / M + s(-k * m)) / k`
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
+        let u = self
+            .steepness
+            .checked_mul(reserve)?
+            .checked_div(self.max_price)?
+            .checked_add(self.offset)?;
+        let supply = self
+            .midpoint
+            .checked_add(inverse_softplus(u)?.checked_div(self.steepness)?)?;
+        Ok(supply.max(Fixed::ZERO))
     }
 
-    /// supply = `F^-1(x) = ((5 * (x - C) / (2 * k))^(1/5)) ^ 2`
-    fn supply(&self, reserve: Uint128) -> Uint128 {
-        let normalized = self.normalize.from_reserve(reserve);
-        let base = RustDecimal::from(5) * (normalized - self.constant)
-            / (RustDecimal::from(2) * self.slope);
-        let root = fifth_root(base);
-        let supply = root * root;
-        self.normalize.clone().to_supply(supply)
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
     }
 }
 
-// we multiply by 10^18, turn to int, take square root, then divide by 10^9 as we convert back to decimal
-fn square_root(square: RustDecimal) -> RustDecimal {
-    // must be even
-    // TODO: this can overflow easily at 18... what is a good value?
-    const EXTRA_DIGITS: u32 = 18;
-    let multiplier = 10u128.saturating_pow(EXTRA_DIGITS);
+/// Piecewise chains sub-curves over supply ranges.
+///
+/// Each segment covers supply from its `start` up to the next segment's `start` (the last one is
+/// unbounded). Sub-curves are evaluated at the absolute supply, so a segment only adds what its own
+/// curve accumulates inside its range:
+///
+/// reserve = `F(x) = sum(F_j(s_j+1) - F_j(s_j)) + F_i(x) - F_i(s_i)` for `s_i <= x < s_i+1`
+///
+/// This keeps `reserve` and `supply` continuous at the breakpoints. `spot_price` at a breakpoint is
+/// the price of the segment starting there; it is continuous when the sub-curves agree at their
+/// shared breakpoints.
+pub struct Piecewise {
+    /// `(start, curve)`, with the first start at zero and starts strictly increasing
+    pub segments: Vec<(Uint128, Box<dyn Curve>)>,
+    pub normalize: DecimalPlaces,
+}
+
+impl Piecewise {
+    pub fn new(segments: Vec<(Uint128, Box<dyn Curve>)>, normalize: DecimalPlaces) -> Self {
+        Self {
+            segments,
+            normalize,
+        }
+    }
 
-    // multiply by 10^18 and turn to u128
-    let extended = square * decimal(multiplier, 0);
-    let extended = extended.floor().to_u128().unwrap();
+    /// index of the segment covering `supply`
+    fn segment_index(&self, supply: Uint128) -> usize {
+        self.segments
+            .iter()
+            .rposition(|(start, _)| *start <= supply)
+            .unwrap_or(0)
+    }
+
+    /// start of segment `index`, in whole tokens
+    fn start_value(&self, index: usize) -> Result<Fixed, ContractError> {
+        self.normalize.from_supply(self.segments[index].0)
+    }
 
-    // take square root, and build a decimal again
-    let root = extended.integer_sqrt();
-    decimal(root, EXTRA_DIGITS / 2)
+    /// end of segment `index`, in whole tokens. The last segment is unbounded.
+    fn end_value(&self, index: usize) -> Result<Option<Fixed>, ContractError> {
+        self.segments
+            .get(index + 1)
+            .map(|(end, _)| self.normalize.from_supply(*end))
+            .transpose()
+    }
+
+    /// reserve accumulated by segment `index` between its start and `supply`
+    fn segment_reserve(&self, index: usize, supply: Fixed) -> Result<Fixed, ContractError> {
+        let curve = &self.segments[index].1;
+        let start = curve.reserve_value(self.start_value(index)?)?;
+        Ok(curve
+            .reserve_value(supply)?
+            .checked_sub(start)?
+            .max(Fixed::ZERO))
+    }
 }
 
-// we multiply by 10^9, turn to int, take cube root, then divide by 10^3 as we convert back to decimal
-fn cube_root(cube: RustDecimal) -> RustDecimal {
-    // must be multiple of 3
-    // TODO: what is a good value?
-    const EXTRA_DIGITS: u32 = 9;
-    let multiplier = 10u128.saturating_pow(EXTRA_DIGITS);
+impl Curve for Piecewise {
+    fn spot_price(&self, supply: Uint128) -> Result<StdDecimal, ContractError> {
+        let (_, curve) = &self.segments[self.segment_index(supply)];
+        curve.spot_price(supply)
+    }
 
-    // multiply out and turn to u128
-    let extended = cube * decimal(multiplier, 0);
-    let extended = extended.floor().to_u128().unwrap();
+    fn reserve_value(&self, supply: Fixed) -> Result<Fixed, ContractError> {
+        let mut reserve = Fixed::ZERO;
+        for index in 0..self.segments.len() {
+            let end = self.end_value(index)?;
+            match end {
+                Some(end) if end <= supply => {
+                    reserve = reserve.checked_add(self.segment_reserve(index, end)?)?;
+                }
+                _ => return reserve.checked_add(self.segment_reserve(index, supply)?),
+            }
+        }
+        Ok(reserve)
+    }
 
-    // take cube root, and build a decimal again
-    let root = extended.integer_cbrt();
-    decimal(root, EXTRA_DIGITS / 3)
+    fn supply_value(&self, reserve: Fixed) -> Result<Fixed, ContractError> {
+        let mut remaining = reserve;
+        for (index, (_, curve)) in self.segments.iter().enumerate() {
+            let end = self.end_value(index)?;
+            if let Some(end) = end {
+                let filled = self.segment_reserve(index, end)?;
+                if remaining >= filled {
+                    remaining = remaining.checked_sub(filled)?;
+                    continue;
+                }
+            }
+            // rounding in the sub-curve must not move us out of this segment
+            let start = self.start_value(index)?;
+            let start_reserve = curve.reserve_value(start)?.checked_add(remaining)?;
+            let supply = curve.supply_value(start_reserve)?.max(start);
+            return Ok(match end {
+                Some(end) => supply.min(end),
+                None => supply,
+            });
+        }
+        Ok(Fixed::ZERO)
+    }
+
+    fn decimals(&self) -> &DecimalPlaces {
+        &self.normalize
+    }
 }
 
-// we multiply by 10^10, turn to int, take 5th root, then divide by 10^5 as we convert back to decimal
-fn fifth_root(expo: RustDecimal) -> RustDecimal {
-    // must be multiple of 5
-    // TODO: what is a good value?
-    const EXTRA_DIGITS: u32 = 15;
-    let multiplier = 10u128.saturating_pow(EXTRA_DIGITS);
+// x^(a/b) for x >= 0, taking the root first so large supplies don't overflow
+fn pow_ratio(x: Fixed, a: u32, b: u32) -> Result<Fixed, ContractError> {
+    let gcd = a.gcd(&b);
+    if gcd == 0 {
+        return Err(ContractError::DivisionByZero {});
+    }
+    x.checked_nth_root(b / gcd)?.checked_powu(a / gcd)
+}
 
-    // multiply out and turn to u128
-    let extended = expo * decimal(multiplier, 0);
-    let extended = extended.floor().to_u128().unwrap();
+// s(t) = ln(1 + e^t), computed as max(t, 0) + ln(1 + e^-|t|)
+fn softplus(t: Fixed) -> Result<Fixed, ContractError> {
+    let tail = Fixed::ONE
+        .checked_add((-t.abs()).checked_exp()?)?
+        .checked_ln()?;
+    t.max(Fixed::ZERO).checked_add(tail)
+}
 
-    // take cube root, and build a decimal again
-    let root = extended.nth_root(5);
-    decimal(root, EXTRA_DIGITS / 5)
+// s^-1(u) = ln(e^u - 1), computed as u + ln(1 - e^-u). Only defined for u > 0.
+fn inverse_softplus(u: Fixed) -> Result<Fixed, ContractError> {
+    if u < Fixed::ONE {
+        // 1 - e^-u cancels out most digits for small u
+        return exp_minus_one(u)?.checked_ln();
+    }
+    u.checked_add(Fixed::ONE.checked_sub((-u).checked_exp()?)?.checked_ln()?)
+}
+
+// e^x - 1 for 0 <= x < 1, summing the Taylor series so small x keep their precision
+fn exp_minus_one(x: Fixed) -> Result<Fixed, ContractError> {
+    let mut term = x;
+    let mut sum = x;
+    let mut i = 1u32;
+    while !term.is_zero() {
+        i += 1;
+        term = term.checked_mul(x)?.checked_div(Fixed::from(i))?;
+        sum = sum.checked_add(term)?;
+    }
+    Ok(sum)
 }
 
 /// DecimalPlaces should be passed into curve constructors
@@ -337,7 +540,8 @@ fn fifth_root(expo: RustDecimal) -> RustDecimal {
 pub struct DecimalPlaces {
     /// Number of decimal places for the supply token (this is what was passed in cw20-base instantiate
     pub supply: u32,
-    /// Number of decimal places for the reserve token (eg. 6 for uatom, 9 for nstep, 18 for wei)
+    /// Number of decimal places for the reserve token (eg. 6 for uatom, 9 for nstep, 18 for wei).
+    /// Curve math is exact to the base unit for up to 18 decimal places.
     pub reserve: u32,
 }
 
@@ -349,32 +553,78 @@ impl DecimalPlaces {
         }
     }
 
-    pub fn to_reserve(self, reserve: RustDecimal) -> Uint128 {
-        let factor = decimal(10u128.pow(self.reserve), 0);
-        let out = reserve * factor;
-        // TODO: execute overflow better? Result?
-        out.floor().to_u128().unwrap().into()
+    pub fn to_reserve(&self, reserve: Fixed, rounding: Rounding) -> Result<Uint128, ContractError> {
+        reserve.to_base_units(self.reserve, rounding)
     }
 
-    pub fn to_supply(self, supply: RustDecimal) -> Uint128 {
-        let factor = decimal(10u128.pow(self.supply), 0);
-        let out = supply * factor;
-        // TODO: execute overflow better? Result?
-        out.floor().to_u128().unwrap().into()
+    pub fn to_supply(&self, supply: Fixed, rounding: Rounding) -> Result<Uint128, ContractError> {
+        supply.to_base_units(self.supply, rounding)
     }
 
-    pub fn from_supply(&self, supply: Uint128) -> RustDecimal {
-        decimal(supply, self.supply)
+    pub fn from_supply(&self, supply: Uint128) -> Result<Fixed, ContractError> {
+        checked_decimal(supply, self.supply)
     }
 
-    pub fn from_reserve(&self, reserve: Uint128) -> RustDecimal {
-        decimal(reserve, self.reserve)
+    pub fn from_reserve(&self, reserve: Uint128) -> Result<Fixed, ContractError> {
+        checked_decimal(reserve, self.reserve)
+    }
+}
+
+/// Round trip and monotonicity report of a curve, see `check_invariants`
+#[cw_serde]
+pub struct CurveInvariants {
+    /// number of supplies the curve was evaluated at
+    pub samples: u32,
+    /// largest `|supply(reserve(x)) - x|` over the sampled supplies, in supply base units
+    pub max_round_trip_error: Uint128,
+    /// sampled supply with the largest round trip error
+    pub max_round_trip_error_supply: Uint128,
+    /// number of sampled supplies where the reserve or the spot price is below that of the
+    /// previous sample
+    pub monotonicity_violations: u32,
+}
+
+/// Evaluates `curve` at `samples` evenly spaced supplies up to and including `max_supply`.
+/// Checks that `supply(reserve(x))` round trips to `x` and that the reserve and spot price never
+/// decrease with the supply. Rounds the way a buy does: the reserve up and the supply down.
+pub fn check_invariants(
+    curve: &dyn Curve,
+    max_supply: Uint128,
+    samples: u32,
+) -> Result<CurveInvariants, ContractError> {
+    let mut invariants = CurveInvariants {
+        samples,
+        max_round_trip_error: Uint128::zero(),
+        max_round_trip_error_supply: Uint128::zero(),
+        monotonicity_violations: 0,
+    };
+    let mut previous: Option<(Uint128, StdDecimal)> = None;
+    for i in 1..=samples {
+        let supply = max_supply.multiply_ratio(i, samples);
+        let reserve = curve.reserve(supply, Rounding::Up)?;
+        let spot_price = curve.spot_price(supply)?;
+
+        let round_trip = curve.supply(reserve, Rounding::Down)?;
+        let error = round_trip.max(supply) - round_trip.min(supply);
+        if error > invariants.max_round_trip_error {
+            invariants.max_round_trip_error = error;
+            invariants.max_round_trip_error_supply = supply;
+        }
+
+        if let Some((previous_reserve, previous_price)) = previous {
+            if reserve < previous_reserve || spot_price < previous_price {
+                invariants.monotonicity_violations += 1;
+            }
+        }
+        previous = Some((reserve, spot_price));
     }
+    Ok(invariants)
 }
 
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::Uint512;
+    use std::str::FromStr;
 
     use super::*;
     // TODO: test DecimalPlaces return proper decimals
@@ -389,15 +639,19 @@ mod tests {
         // spot price is always 1.5 ATOM
         assert_eq!(
             StdDecimal::percent(150),
-            curve.spot_price(Uint128::new(123))
+            curve.spot_price(Uint128::new(123)).unwrap()
         );
 
         // if we have 30 STEP, we should have 45 ATOM
-        let reserve = curve.reserve(Uint128::new(30_000_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(30_000_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(45_000_000), reserve);
 
         // if we have 36 ATOM, we should have 24 STEP
-        let supply = curve.supply(Uint128::new(36_000_000));
+        let supply = curve
+            .supply(Uint128::new(36_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(24_000_000_000), supply);
     }
 
@@ -412,33 +666,37 @@ mod tests {
         // spot price is 0.1 with 1 USDT supply
         assert_eq!(
             StdDecimal::permille(100),
-            curve.spot_price(Uint128::new(100))
+            curve.spot_price(Uint128::new(100)).unwrap()
         );
         // spot price is 1.7 with 17 USDT supply
         assert_eq!(
             StdDecimal::permille(1700),
-            curve.spot_price(Uint128::new(1700))
+            curve.spot_price(Uint128::new(1700)).unwrap()
         );
         // spot price is 0.212 with 2.12 USDT supply
         assert_eq!(
             StdDecimal::permille(212),
-            curve.spot_price(Uint128::new(212))
+            curve.spot_price(Uint128::new(212)).unwrap()
         );
 
         // if we have 10 USDT, we should have 5 BTC
-        let reserve = curve.reserve(Uint128::new(1000));
+        let reserve = curve.reserve(Uint128::new(1000), Rounding::Down).unwrap();
         assert_eq!(Uint128::new(500_000_000), reserve);
         // if we have 20 USDT, we should have 20 BTC
-        let reserve = curve.reserve(Uint128::new(2000));
+        let reserve = curve.reserve(Uint128::new(2000), Rounding::Down).unwrap();
         assert_eq!(Uint128::new(2_000_000_000), reserve);
 
         // if we have 1.25 BTC, we should have 5 USDT
-        let supply = curve.supply(Uint128::new(125_000_000));
+        let supply = curve
+            .supply(Uint128::new(125_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(500), supply);
         // test square root rounding
         // TODO: test when supply has many more decimal places than reserve
         // if we have 1.11 BTC, we should have 4.7116875957... USDT
-        let supply = curve.supply(Uint128::new(111_000_000));
+        let supply = curve
+            .supply(Uint128::new(111_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(471), supply);
     }
 
@@ -447,43 +705,49 @@ mod tests {
         // supply is utree (6) reserve is chf (2)
         let normalize = DecimalPlaces::new(6, 2);
         // slope is 0.35 (eg hits 0.35 after 1 chf, 3.5 after 100chf)
-        let curve = SquareRoot::new(decimal(35u128, 2), normalize);
+        let curve = Power::new(decimal(35u128, 2), 1, 2, normalize);
 
         // do some sanity checks....
         // spot price is 0.35 with 1 TREE supply
         assert_eq!(
             StdDecimal::percent(35),
-            curve.spot_price(Uint128::new(1_000_000))
+            curve.spot_price(Uint128::new(1_000_000)).unwrap()
         );
         // spot price is 3.5 with 100 TREE supply
         assert_eq!(
             StdDecimal::percent(350),
-            curve.spot_price(Uint128::new(100_000_000))
+            curve.spot_price(Uint128::new(100_000_000)).unwrap()
         );
         // spot price should be 23.478713763747788 with 4500 TREE supply (test rounding and reporting here)
-        // rounds off around 8-9 sig figs (note diff for last points)
+        // exact up to the 18 decimal places of StdDecimal
         assert_eq!(
-            StdDecimal::from_str("23.4787137634").unwrap(),
-            curve.spot_price(Uint128::new(4_500_000_000))
+            StdDecimal::from_str("23.478713763747791812").unwrap(),
+            curve.spot_price(Uint128::new(4_500_000_000)).unwrap()
         );
 
         // if we have 1 TREE, we should have 0.2333333333333 CHF
-        let reserve = curve.reserve(Uint128::new(1_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(1_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(23), reserve);
         // if we have 100 TREE, we should have 233.333333333 CHF
-        let reserve = curve.reserve(Uint128::new(100_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(100_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(23_333), reserve);
         // test rounding
         // if we have 235 TREE, we should have 840.5790828021146 CHF
-        let reserve = curve.reserve(Uint128::new(235_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(235_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(84_057), reserve); // round down
 
         // // if we have 0.23 CHF, we should have 0.990453 TREE (round down)
-        let supply = curve.supply(Uint128::new(23));
-        assert_eq!(Uint128::new(990_000), supply);
+        let supply = curve.supply(Uint128::new(23), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(990_453), supply);
         // if we have 840.58 CHF, we should have 235.000170 TREE (round down)
-        let supply = curve.supply(Uint128::new(84058));
-        assert_eq!(Uint128::new(235_000_000), supply);
+        let supply = curve.supply(Uint128::new(84058), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(235_000_170), supply);
     }
 
     #[test]
@@ -491,46 +755,52 @@ mod tests {
         // supply is utree (6) reserve is chf (2)
         let normalize = DecimalPlaces::new(6, 2);
         // slope is 0.35 (eg hits 0.35 after 1 chf, 3.5 after 100chf)
-        let curve = CubeRootSquared::new(decimal(35u128, 2), normalize);
+        let curve = Power::new(decimal(35u128, 2), 2, 3, normalize);
 
         // do some sanity checks....
         // spot price is 1.4 with 8 TREE supply
         assert_eq!(
             StdDecimal::percent(1_40),
-            curve.spot_price(Uint128::new(8_000000)),
+            curve.spot_price(Uint128::new(8_000000)).unwrap(),
             "spot price 1"
         );
         // spot price is 5.60 with 64 TREE supply
         assert_eq!(
             StdDecimal::percent(5_60),
-            curve.spot_price(Uint128::new(64_000_000)),
+            curve.spot_price(Uint128::new(64_000_000)).unwrap(),
             "spot price 2"
         );
         // spot price should be 95.3988311237 with 4500 TREE supply (test rounding and reporting here)
-        // rounds off around 8-9 sig figs (note diff for last points)
+        // exact up to the 18 decimal places of StdDecimal
         assert_eq!(
-            StdDecimal::from_ratio(95_39147835u128, 1_00_000_000u128),
-            curve.spot_price(Uint128::new(4_500_000_000)),
+            StdDecimal::from_str("95.398831123687332091").unwrap(),
+            curve.spot_price(Uint128::new(4_500_000_000)).unwrap(),
             "spot price 3"
         );
 
         // if we have 1 TREE, we should have 0.21 CHF
-        let reserve = curve.reserve(Uint128::new(1_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(1_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(21), reserve);
         // if we have 100 TREE, we should have 452.4312849067 CHF
-        let reserve = curve.reserve(Uint128::new(100_000_000));
-        assert_eq!(Uint128::new(452_14), reserve);
+        let reserve = curve
+            .reserve(Uint128::new(100_000_000), Rounding::Down)
+            .unwrap();
+        assert_eq!(Uint128::new(452_43), reserve);
         // test rounding
         // if we have 235 TREE, we should have 1,879.3127716028 CHF (round down)
-        let reserve = curve.reserve(Uint128::new(235_000_000));
-        assert_eq!(Uint128::new(1879_30), reserve); // round down
+        let reserve = curve
+            .reserve(Uint128::new(235_000_000), Rounding::Down)
+            .unwrap();
+        assert_eq!(Uint128::new(1879_31), reserve); // round down
 
         // // if we have 0.23 CHF, we should have 1.0561001998 TREE (round down)
-        let supply = curve.supply(Uint128::new(23));
-        assert_eq!(Uint128::new(1054977), supply);
+        let supply = curve.supply(Uint128::new(23), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(1_056100), supply);
         // if we have 840.58 CHF, we should have 145.0159776173 TREE (round down)
-        let supply = curve.supply(Uint128::new(84058));
-        assert_eq!(Uint128::new(144_951329), supply);
+        let supply = curve.supply(Uint128::new(84058), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(145_015977), supply);
     }
 
     #[test]
@@ -538,47 +808,582 @@ mod tests {
         // supply is utree (6) reserve is chf (2)
         let normalize = DecimalPlaces::new(6, 2);
         // slope is 0.35 (eg hits 0.35 after 1 chf, 3.5 after 100chf)
-        let curve = SquareRootCubed::new(decimal(35u128, 2), normalize);
+        let curve = Power::new(decimal(35u128, 2), 3, 2, normalize);
 
         // do some sanity checks....
         // spot price is 179.2 with 65 TREE supply (rounded)
         assert_eq!(
             StdDecimal::from_ratio(179_2u128, 10u128),
-            curve.spot_price(Uint128::new(64_000000)),
+            curve.spot_price(Uint128::new(64_000000)).unwrap(),
             "spot price 1"
         );
         // spot price is 91,750.4 with 4096 TREE supply
         assert_eq!(
             StdDecimal::from_ratio(91750_4u128, 10u128),
-            curve.spot_price(Uint128::new(4096_000_000)),
+            curve.spot_price(Uint128::new(4096_000_000)).unwrap(),
             "spot price 2"
         );
         // spot price should be 105,654.2119368651 with 4500 TREE supply (test rounding and reporting here)
-        // rounds off around 8-9 sig figs (note diff for last points)
+        // exact up to the 18 decimal places of StdDecimal
         assert_eq!(
-            StdDecimal::from_str("105654.211932169873689402").unwrap(),
-            curve.spot_price(Uint128::new(4_500_000_000)),
+            StdDecimal::from_str("105654.211936865063155333").unwrap(),
+            curve.spot_price(Uint128::new(4_500_000_000)).unwrap(),
             "spot price 3"
         );
 
         // if we have 1 TREE, we should have 0.14 CHF
-        let reserve = curve.reserve(Uint128::new(1_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(1_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(14), reserve);
         // if we have 100 TREE, we should have 14000 CHF
-        let reserve = curve.reserve(Uint128::new(100_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(100_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(14000_00), reserve);
         // test rounding
         // if we have 235 TREE, we should have 118,521.6506750982 CHF (round down)
-        let reserve = curve.reserve(Uint128::new(235_000_000));
+        let reserve = curve
+            .reserve(Uint128::new(235_000_000), Rounding::Down)
+            .unwrap();
         assert_eq!(Uint128::new(118521_65), reserve); // round down
 
         // // if we have 0.23 CHF, we should have 1.2196631994 TREE (round down)
-        let supply = curve.supply(Uint128::new(23));
-        assert_eq!(Uint128::new(1_218816), supply);
+        let supply = curve.supply(Uint128::new(23), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(1_219663), supply);
         // if we have 840.58 CHF, we should have 32.4623837021 TREE (round down)
-        let supply = curve.supply(Uint128::new(84058));
-        assert_eq!(Uint128::new(32_455809), supply);
+        let supply = curve.supply(Uint128::new(84058), Rounding::Down).unwrap();
+        assert_eq!(Uint128::new(32_462383), supply);
     }
 
-    // Idea: generic test that curve.supply(curve.reserve(supply)) == supply (or within some small rounding margin)
+    #[test]
+    fn power_curve() {
+        // supply is utree (6) reserve is uatom (6)
+        let normalize = DecimalPlaces::new(6, 6);
+        // spot price is 2 * x^(5/4)
+        let curve = Power::new(decimal(2u128, 0), 5, 4, normalize);
+
+        // spot price is 64 with 16 TREE supply
+        assert_eq!(
+            StdDecimal::percent(64_00),
+            curve.spot_price(Uint128::new(16_000_000)).unwrap()
+        );
+
+        // reserve is 2 * 4/9 * x^(9/4), or 455.111111 ATOM with 16 TREE
+        let reserve = curve
+            .reserve(Uint128::new(16_000_000), Rounding::Down)
+            .unwrap();
+        assert_eq!(Uint128::new(455_111111), reserve);
+        let reserve = curve
+            .reserve(Uint128::new(7_500_000), Rounding::Down)
+            .unwrap();
+        assert_eq!(Uint128::new(82_743772), reserve);
+
+        // and supply inverts it
+        assert_eq!(
+            Uint128::new(16_000_000),
+            curve
+                .supply(Uint128::new(455_111112), Rounding::Down)
+                .unwrap()
+        );
+        assert_eq!(
+            Uint128::new(7_500_000),
+            curve
+                .supply(Uint128::new(82_743773), Rounding::Down)
+                .unwrap()
+        );
+
+        // a zero exponent is a constant price
+        let curve = Power::new(decimal(15u128, 1), 0, 1, DecimalPlaces::new(0, 6));
+        assert_eq!(
+            StdDecimal::percent(150),
+            curve.spot_price(Uint128::zero()).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(45_000_000),
+            curve.reserve(Uint128::new(30), Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(30),
+            curve
+                .supply(Uint128::new(45_000_000), Rounding::Down)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn piecewise_curve() {
+        // supply is shirts (0), reserve is uosmo (6)
+        let normalize = DecimalPlaces::new(0, 6);
+        // first 100 shirts at 1 OSMO, next 300 at 2 OSMO, the rest at 3 OSMO
+        let curve = Piecewise::new(
+            vec![
+                (
+                    Uint128::zero(),
+                    Box::new(Constant::new(decimal(1u128, 0), normalize.clone())),
+                ),
+                (
+                    Uint128::new(100),
+                    Box::new(Constant::new(decimal(2u128, 0), normalize.clone())),
+                ),
+                (
+                    Uint128::new(400),
+                    Box::new(Constant::new(decimal(3u128, 0), normalize.clone())),
+                ),
+            ],
+            normalize,
+        );
+
+        // the breakpoint is priced by the segment starting there
+        assert_eq!(
+            StdDecimal::one(),
+            curve.spot_price(Uint128::new(99)).unwrap()
+        );
+        assert_eq!(
+            StdDecimal::percent(200),
+            curve.spot_price(Uint128::new(100)).unwrap()
+        );
+        assert_eq!(
+            StdDecimal::percent(300),
+            curve.spot_price(Uint128::new(450)).unwrap()
+        );
+
+        // reserve accumulates across segments
+        assert_eq!(
+            Uint128::new(50_000_000),
+            curve.reserve(Uint128::new(50), Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(100_000_000),
+            curve.reserve(Uint128::new(100), Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(700_000_000),
+            curve.reserve(Uint128::new(400), Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(1_000_000_000),
+            curve.reserve(Uint128::new(500), Rounding::Down).unwrap()
+        );
+
+        // and supply inverts it in every segment
+        assert_eq!(
+            Uint128::new(50),
+            curve
+                .supply(Uint128::new(50_000_000), Rounding::Down)
+                .unwrap()
+        );
+        assert_eq!(
+            Uint128::new(100),
+            curve
+                .supply(Uint128::new(100_000_000), Rounding::Down)
+                .unwrap()
+        );
+        assert_eq!(
+            Uint128::new(250),
+            curve
+                .supply(Uint128::new(400_000_000), Rounding::Down)
+                .unwrap()
+        );
+        assert_eq!(
+            Uint128::new(400),
+            curve
+                .supply(Uint128::new(700_000_000), Rounding::Down)
+                .unwrap()
+        );
+        assert_eq!(
+            Uint128::new(500),
+            curve
+                .supply(Uint128::new(1_000_000_000), Rounding::Down)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn piecewise_curve_is_continuous() {
+        // splitting a curve into segments of itself must not change it
+        let normalize = DecimalPlaces::new(6, 6);
+        let slope = decimal(1u128, 1);
+        let linear = Linear::new(slope, normalize.clone());
+        let curve = Piecewise::new(
+            vec![
+                (
+                    Uint128::zero(),
+                    Box::new(Linear::new(slope, normalize.clone())),
+                ),
+                (
+                    Uint128::new(10_000_000),
+                    Box::new(Linear::new(slope, normalize.clone())),
+                ),
+                (
+                    Uint128::new(25_000_000),
+                    Box::new(Linear::new(slope, normalize.clone())),
+                ),
+            ],
+            normalize,
+        );
+
+        for supply in [
+            0u128, 1, 9_999_999, 10_000_000, 10_000_001, 24_999_999, 25_000_000, 40_000_000,
+        ] {
+            let supply = Uint128::new(supply);
+            assert_eq!(
+                linear.spot_price(supply).unwrap(),
+                curve.spot_price(supply).unwrap()
+            );
+            assert_eq!(
+                linear.reserve(supply, Rounding::Down).unwrap(),
+                curve.reserve(supply, Rounding::Down).unwrap()
+            );
+            let reserve = linear.reserve(supply, Rounding::Down).unwrap();
+            assert_eq!(
+                linear.supply(reserve, Rounding::Down).unwrap(),
+                curve.supply(reserve, Rounding::Down).unwrap()
+            );
+        }
+    }
+
+    /// asserts `actual` is within `tolerance` of `expected`
+    fn assert_within(expected: u128, actual: Uint128, tolerance: u128) {
+        let actual = actual.u128();
+        assert!(
+            actual.abs_diff(expected) <= tolerance,
+            "expected {expected} +/- {tolerance}, got {actual}"
+        );
+    }
+
+    #[test]
+    fn exponential_curve() {
+        // supply is shirts (0), reserve is uosmo (6)
+        let normalize = DecimalPlaces::new(0, 6);
+        // every shirt is 1% more expensive than the last
+        let curve = Exponential::new(decimal(101u128, 2), normalize).unwrap();
+
+        // spot price is 1 OSMO at zero supply and 1.01^100 at 100 shirts
+        assert_eq!(
+            StdDecimal::one(),
+            curve.spot_price(Uint128::zero()).unwrap()
+        );
+        let price = curve.spot_price(Uint128::new(100)).unwrap();
+        let expected = StdDecimal::from_str("2.704813829421526093").unwrap();
+        assert!(
+            price.max(expected) - price.min(expected)
+                < StdDecimal::from_str("0.000000000000001").unwrap()
+        );
+
+        // reserve is (1.01^x - 1) / ln(1.01), rounded down
+        assert_eq!(
+            Uint128::zero(),
+            curve.reserve(Uint128::zero(), Rounding::Down).unwrap()
+        );
+        assert_within(
+            1_004_991,
+            curve.reserve(Uint128::new(1), Rounding::Down).unwrap(),
+            1,
+        );
+        assert_within(
+            171_332_376,
+            curve.reserve(Uint128::new(100), Rounding::Down).unwrap(),
+            1,
+        );
+        assert_within(
+            1_108_722_506,
+            curve.reserve(Uint128::new(250), Rounding::Down).unwrap(),
+            1,
+        );
+
+        // supply inverts reserve up to rounding
+        for supply in [0u128, 1, 100, 250, 1000] {
+            let reserve = curve.reserve(Uint128::new(supply), Rounding::Down).unwrap();
+            assert_within(supply, curve.supply(reserve, Rounding::Down).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn sigmoid_curve() {
+        // supply is shirts (0), reserve is uosmo (6)
+        let normalize = DecimalPlaces::new(0, 6);
+        // price approaches 10 OSMO, passing 5 OSMO at the 500th shirt
+        let curve = Sigmoid::new(
+            decimal(10u128, 0),
+            Uint128::new(500),
+            decimal(1u128, 2),
+            normalize,
+        )
+        .unwrap();
+
+        // half the max price at the midpoint, cheap early and plateauing late
+        assert_eq!(
+            StdDecimal::percent(500),
+            curve.spot_price(Uint128::new(500)).unwrap()
+        );
+        assert!(curve.spot_price(Uint128::zero()).unwrap() < StdDecimal::permille(67));
+        assert!(curve.spot_price(Uint128::new(2000)).unwrap() > StdDecimal::permille(9999));
+        // and symmetric around the midpoint
+        let sum = curve.spot_price(Uint128::new(499)).unwrap()
+            + curve.spot_price(Uint128::new(501)).unwrap();
+        assert!(
+            StdDecimal::percent(1000) - sum < StdDecimal::from_str("0.000000000000001").unwrap()
+        );
+
+        assert_eq!(
+            Uint128::zero(),
+            curve.reserve(Uint128::zero(), Rounding::Down).unwrap()
+        );
+        assert_within(
+            67_262,
+            curve.reserve(Uint128::new(1), Rounding::Down).unwrap(),
+            1,
+        );
+        assert_within(
+            11_434_579,
+            curve.reserve(Uint128::new(100), Rounding::Down).unwrap(),
+            1,
+        );
+        assert_within(
+            686_431_832,
+            curve.reserve(Uint128::new(500), Rounding::Down).unwrap(),
+            1,
+        );
+        // the area under the curve up to twice the midpoint is half the max price per shirt
+        assert_within(
+            5_000_000_000,
+            curve.reserve(Uint128::new(1000), Rounding::Down).unwrap(),
+            1,
+        );
+        assert_within(
+            14_993_284_957,
+            curve.reserve(Uint128::new(2000), Rounding::Down).unwrap(),
+            1,
+        );
+
+        for supply in [0u128, 1, 100, 499, 500, 501, 1000, 2000, 10_000] {
+            let reserve = curve.reserve(Uint128::new(supply), Rounding::Down).unwrap();
+            assert_within(supply, curve.supply(reserve, Rounding::Down).unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn rounding() {
+        // supply is shirts (0), reserve is whole OSMO (0)
+        let curve = Constant::new(decimal(3u128, 1), DecimalPlaces::new(0, 0));
+
+        // a shirt costs 0.3 OSMO
+        let shirt = Uint128::new(1);
+        assert_eq!(
+            Uint128::zero(),
+            curve.reserve(shirt, Rounding::Down).unwrap()
+        );
+        assert_eq!(Uint128::one(), curve.reserve(shirt, Rounding::Up).unwrap());
+
+        // 1 OSMO buys 3.33 shirts
+        let osmo = Uint128::new(1);
+        assert_eq!(Uint128::new(3), curve.supply(osmo, Rounding::Down).unwrap());
+        assert_eq!(Uint128::new(4), curve.supply(osmo, Rounding::Up).unwrap());
+
+        // exact results don't move
+        let shirts = Uint128::new(10);
+        assert_eq!(
+            Uint128::new(3),
+            curve.reserve(shirts, Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(3),
+            curve.reserve(shirts, Rounding::Up).unwrap()
+        );
+    }
+
+    #[test]
+    fn curve_math_errors() {
+        let normalize = DecimalPlaces::new(0, 6);
+
+        // a zero price can't be inverted
+        let curve = Constant::new(Fixed::ZERO, normalize.clone());
+        let err = curve
+            .supply(Uint128::new(1_000_000), Rounding::Down)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::DivisionByZero {}));
+
+        // 2^1000 doesn't fit Fixed
+        let curve = Exponential::new(decimal(2u128, 0), normalize.clone()).unwrap();
+        let err = curve.spot_price(Uint128::new(1000)).unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+
+        // supply^3 outgrows Fixed long before supply does
+        let curve = Power::new(decimal(1u128, 0), 2, 1, normalize.clone());
+        let err = curve
+            .reserve(Uint128::new(10u128.pow(20)), Rounding::Down)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+
+        // reserve fits Fixed, but not a Uint128 once scaled to 18 decimal base units
+        let curve = Constant::new(decimal(1u128, 0), DecimalPlaces::new(0, 18));
+        let err = curve
+            .reserve(Uint128::new(10u128.pow(27)), Rounding::Down)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+
+        // Fixed can't hold more than 36 decimal places
+        let curve = Linear::new(decimal(1u128, 0), DecimalPlaces::new(40, 6));
+        let err = curve.spot_price(Uint128::new(1)).unwrap_err();
+        assert!(matches!(err, ContractError::PrecisionLoss {}));
+    }
+
+    #[test]
+    fn decimal_places_grid() {
+        type CurveFn = Box<dyn Fn(DecimalPlaces) -> Box<dyn Curve>>;
+        let curves: Vec<(&str, CurveFn)> = vec![
+            (
+                "constant",
+                Box::new(|places| Box::new(Constant::new(decimal(15u128, 1), places))),
+            ),
+            (
+                "linear",
+                Box::new(|places| Box::new(Linear::new(decimal(1u128, 1), places))),
+            ),
+            (
+                "square root",
+                Box::new(|places| Box::new(Power::new(decimal(35u128, 2), 1, 2, places))),
+            ),
+            (
+                "square root cubed",
+                Box::new(|places| Box::new(Power::new(decimal(35u128, 2), 3, 2, places))),
+            ),
+            (
+                "exponential",
+                Box::new(|places| {
+                    Box::new(Exponential::new(decimal(1001u128, 3), places).unwrap())
+                }),
+            ),
+            (
+                "sigmoid",
+                Box::new(|places: DecimalPlaces| {
+                    // midpoint at 1000 whole tokens
+                    let midpoint = Uint128::new(1000 * 10u128.pow(places.supply));
+                    Box::new(
+                        Sigmoid::new(decimal(10u128, 0), midpoint, decimal(1u128, 2), places)
+                            .unwrap(),
+                    )
+                }),
+            ),
+        ];
+
+        let places = [0u8, 6, 9, 18];
+        for (name, curve_fn) in curves.iter() {
+            // spot price is in whole tokens, so it doesn't depend on decimal places
+            let price = curve_fn(DecimalPlaces::new(0, 0))
+                .spot_price(Uint128::new(1234))
+                .unwrap();
+
+            for supply_places in places {
+                // 1234 whole tokens and some dust
+                let supply = Uint128::new(1234 * 10u128.pow(supply_places.into()))
+                    + Uint128::new(10u128.pow(supply_places.into()) / 3);
+                let wei = curve_fn(DecimalPlaces::new(supply_places, 18))
+                    .reserve(supply, Rounding::Down)
+                    .unwrap();
+
+                for reserve_places in places {
+                    let curve = curve_fn(DecimalPlaces::new(supply_places, reserve_places));
+                    let whole = curve
+                        .spot_price(Uint128::new(1234 * 10u128.pow(supply_places.into())))
+                        .unwrap();
+                    assert_eq!(price, whole, "{name} {supply_places}/{reserve_places}");
+
+                    // the reserve is exact to the base unit, it only differs in where it is cut off
+                    let reserve = curve.reserve(supply, Rounding::Down).unwrap();
+                    let expected = wei / Uint128::new(10u128.pow(18 - reserve_places as u32));
+                    assert_eq!(expected, reserve, "{name} {supply_places}/{reserve_places}");
+
+                    // supply returns the most base units that reserve pays for
+                    let bought = curve.supply(reserve, Rounding::Down).unwrap();
+                    assert!(
+                        curve.reserve(bought, Rounding::Down).unwrap() <= reserve
+                            && curve
+                                .reserve(bought + Uint128::one(), Rounding::Down)
+                                .unwrap()
+                                >= reserve,
+                        "{name} {supply_places}/{reserve_places}: {reserve} buys {bought}"
+                    );
+                    assert!(bought <= supply);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invariants() {
+        let normalize = DecimalPlaces::new(6, 6);
+        let max_supply = Uint128::new(1_000_000_000_000);
+        // rounding the reserve up by a base unit buys back up to `1 / price` supply base units
+        let curves: Vec<(&str, Box<dyn Curve>, u128)> = vec![
+            (
+                "constant",
+                Box::new(Constant::new(decimal(15u128, 1), normalize.clone())),
+                1,
+            ),
+            (
+                "linear",
+                Box::new(Linear::new(decimal(1u128, 3), normalize.clone())),
+                1,
+            ),
+            (
+                "power",
+                Box::new(Power::new(decimal(2u128, 1), 3, 2, normalize.clone())),
+                1,
+            ),
+            (
+                "sigmoid",
+                Box::new(
+                    Sigmoid::new(
+                        decimal(10u128, 0),
+                        Uint128::new(500_000_000_000),
+                        decimal(1u128, 5),
+                        normalize.clone(),
+                    )
+                    .unwrap(),
+                ),
+                // 0.08 at the first sample
+                13,
+            ),
+        ];
+        for (name, curve, tolerance) in curves {
+            let invariants = check_invariants(curve.as_ref(), max_supply, 100).unwrap();
+            assert_eq!(100, invariants.samples, "{name}");
+            assert!(
+                invariants.max_round_trip_error <= Uint128::new(tolerance),
+                "{name}: {invariants:?}"
+            );
+            assert_eq!(0, invariants.monotonicity_violations, "{name}");
+        }
+
+        // 7 shirts at 0.3 OSMO cost 2.1 OSMO, rounded up to 3 OSMO that buy back 10 shirts
+        let curve = Constant::new(decimal(3u128, 1), DecimalPlaces::new(0, 0));
+        let invariants = check_invariants(&curve, Uint128::new(10), 10).unwrap();
+        assert_eq!(Uint128::new(3), invariants.max_round_trip_error);
+        assert_eq!(Uint128::new(7), invariants.max_round_trip_error_supply);
+
+        // the price drops from 2 to 1 at a supply of 500
+        let curve = Piecewise::new(
+            vec![
+                (
+                    Uint128::zero(),
+                    Box::new(Constant::new(decimal(2u128, 0), DecimalPlaces::new(0, 6))),
+                ),
+                (
+                    Uint128::new(500),
+                    Box::new(Constant::new(decimal(1u128, 0), DecimalPlaces::new(0, 6))),
+                ),
+            ],
+            DecimalPlaces::new(0, 6),
+        );
+        let invariants = check_invariants(&curve, Uint128::new(1000), 10).unwrap();
+        assert_eq!(1, invariants.monotonicity_violations);
+        assert_eq!(Uint128::zero(), invariants.max_round_trip_error);
+
+        // nothing to sample
+        let invariants = check_invariants(&curve, Uint128::new(1000), 0).unwrap();
+        assert_eq!(Uint128::zero(), invariants.max_round_trip_error);
+        assert_eq!(0, invariants.monotonicity_violations);
+    }
 }
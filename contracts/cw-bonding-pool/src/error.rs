@@ -1,4 +1,7 @@
-use cosmwasm_std::{CheckedFromRatioError, OverflowError, StdError};
+use cosmwasm_std::{
+    CheckedFromRatioError, ConversionOverflowError, Decimal, DivideByZeroError, OverflowError,
+    StdError, Timestamp, Uint128,
+};
 use cw_utils::PaymentError;
 use thiserror::Error;
 
@@ -13,9 +16,27 @@ pub enum ContractError {
     #[error("{0}")]
     Overflow(#[from] OverflowError),
 
+    #[error("{0}")]
+    DivideByZero(#[from] DivideByZeroError),
+
+    #[error("{0}")]
+    ConversionOverflow(#[from] ConversionOverflowError),
+
     #[error("Unauthorized")]
     Unauthorized {},
 
+    #[error("No ownership transfer is pending")]
+    NoPendingOwner {},
+
+    #[error("Cannot migrate from contract {actual}, expected {expected}")]
+    InvalidContractName { expected: String, actual: String },
+
+    #[error("Cannot migrate from version {from} to older version {to}")]
+    CannotDowngrade { from: String, to: String },
+
+    #[error("{0}")]
+    SemVer(String),
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
 
@@ -24,6 +45,132 @@ pub enum ContractError {
 
     #[error("Denom not found")]
     TokenFactoryDenomNotFound,
+
+    #[error("Max supply must be greater than zero")]
+    InvalidMaxSupply {},
+
+    #[error("Invalid curve: {reason}")]
+    InvalidCurve { reason: String },
+
+    #[error("Curve math overflowed")]
+    CurveOverflow {},
+
+    #[error("Curve math lost precision")]
+    PrecisionLoss {},
+
+    #[error("Curve math divided by zero")]
+    DivisionByZero {},
+
+    #[error("Samples must be between 1 and {max}")]
+    InvalidSamples { max: u32 },
+
+    #[error("Buying {requested} would exceed the max supply of {max_supply}")]
+    MaxSupplyExceeded {
+        max_supply: Uint128,
+        requested: Uint128,
+    },
+
+    #[error("Max supply of {max_supply} reached, pool is sold out")]
+    SoldOut { max_supply: Uint128 },
+
+    #[error("Swap fee must be less than 1, got {swap_fee}")]
+    InvalidSwapFee { swap_fee: Decimal },
+
+    #[error("Swap fee {swap_fee} is below the pool swap fee of {pool_swap_fee}")]
+    SwapFeeBelowPoolFee {
+        swap_fee: Decimal,
+        pool_swap_fee: Decimal,
+    },
+
+    #[error("Swap deadline {deadline} has passed")]
+    DeadlineExceeded { deadline: Timestamp },
+
+    #[error("Pool only accepts cw20 tokens of its reserve, got {token}")]
+    InvalidCw20Token { token: String },
+
+    #[error("Pool has been retired")]
+    PoolRetired {},
+
+    #[error("Pool has to be retired first")]
+    PoolNotRetired {},
+
+    #[error("Invalid graduation config: {reason}")]
+    InvalidGraduationConfig { reason: String },
+
+    #[error("Invalid crowdfund config: {reason}")]
+    InvalidCrowdfundConfig { reason: String },
+
+    #[error("Crowdfund target has not been reached yet, only buys are allowed")]
+    CrowdfundNotFunded {},
+
+    #[error("Crowdfund missed its target, only refunds are allowed")]
+    CrowdfundFailed {},
+
+    #[error("Refunds are only available once the crowdfund has missed its target")]
+    CrowdfundNotFailed {},
+
+    #[error("Refund exceeds the {contributed} supply tokens bought during the crowdfund")]
+    RefundExceedsContribution { contributed: Uint128 },
+
+    #[error("Invalid sale phases: {reason}")]
+    InvalidSalePhases { reason: String },
+
+    #[error("Sale starts at {start}")]
+    SaleNotStarted { start: Timestamp },
+
+    #[error("Sale phase {phase} is closed for buys")]
+    SaleClosed { phase: String },
+
+    #[error("Sale phase {phase} is only open to the allowlist")]
+    NotAllowlisted { phase: String },
+
+    #[error("Sale phase {phase} allows at most {max_per_wallet} supply tokens per wallet")]
+    PhaseWalletCapExceeded {
+        phase: String,
+        max_per_wallet: Uint128,
+    },
+
+    #[error("Invalid merkle proof")]
+    InvalidMerkleProof {},
+
+    #[error("No merkle root registered")]
+    NoMerkleRoot {},
+
+    #[error("Allowlist allocation of {allocation} supply tokens exceeded")]
+    AllocationExceeded { allocation: Uint128 },
+
+    #[error("Max per address must be greater than zero")]
+    InvalidMaxPerAddress {},
+
+    #[error("Wallets can buy at most {max_per_address} supply tokens, net of their sells")]
+    WalletLimitExceeded { max_per_address: Uint128 },
+
+    #[error("Invalid circuit breaker: {reason}")]
+    InvalidCircuitBreaker { reason: String },
+
+    #[error("Circuit breaker tripped: {reason}. Only the owner can resume the pool")]
+    CircuitBreakerTripped { reason: String },
+
+    #[error("Swap exceeds the circuit breaker: {reason}")]
+    CircuitBreakerLimit { reason: String },
+
+    #[error("Paused: {operation}")]
+    Paused { operation: String },
+
+    #[error("Invalid TWAP range: {reason}")]
+    InvalidTwapRange { reason: String },
+
+    #[error("Invalid trade history config: {reason}")]
+    InvalidTradeHistoryConfig { reason: String },
+
+    #[error("Unknown reply id {id}")]
+    UnknownReplyId { id: u64 },
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
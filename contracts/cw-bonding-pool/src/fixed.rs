@@ -0,0 +1,531 @@
+use std::cmp::Ordering;
+use std::ops::Neg;
+
+use cosmwasm_std::{Decimal as StdDecimal, Uint128, Uint256, Uint512};
+
+use crate::error::ContractError;
+
+/// Direction to round in when converting to whole base units.
+///
+/// The pool always rounds in its own favor: up for what the user pays and down for what the user
+/// receives.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+impl Rounding {
+    pub fn opposite(self) -> Self {
+        match self {
+            Rounding::Down => Rounding::Up,
+            Rounding::Up => Rounding::Down,
+        }
+    }
+}
+
+/// Signed fixed point number with 36 decimal places, backed by a Uint256 magnitude.
+///
+/// This is the numeric core of the curve math. 36 decimal places keep 18 decimal reserve and
+/// supply tokens (eg. wei) exact to the base unit, with 18 more digits to absorb rounding in
+/// roots, `exp` and `ln`. Values range up to ~1.15 * 10^41, products and quotients go through
+/// Uint512 so they only fail when the result itself is out of range.
+///
+/// All arithmetic truncates towards zero and errors instead of panicking.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fixed {
+    negative: bool,
+    atomics: Uint256,
+}
+
+/// 10^36
+const ATOMICS_PER_UNIT: Uint256 =
+    Uint256::from_u128(1_000_000_000_000_000_000_000_000_000_000_000_000);
+/// 10^18, the difference between our decimal places and StdDecimal's
+const STD_DECIMAL_FACTOR: Uint256 = Uint256::from_u128(1_000_000_000_000_000_000);
+/// ln(2), truncated to 36 decimal places
+const LN_2: Fixed = Fixed {
+    negative: false,
+    atomics: Uint256::from_u128(693_147_180_559_945_309_417_232_121_458_176_568),
+};
+
+impl Fixed {
+    pub const DECIMAL_PLACES: u32 = 36;
+    pub const ZERO: Fixed = Fixed {
+        negative: false,
+        atomics: Uint256::zero(),
+    };
+    pub const ONE: Fixed = Fixed {
+        negative: false,
+        atomics: ATOMICS_PER_UNIT,
+    };
+
+    /// Creates `num * 10^-scale`. Errors with `PrecisionLoss` if `scale` is above 36.
+    pub fn from_atomics(num: impl Into<u128>, scale: u32) -> Result<Self, ContractError> {
+        if scale > Self::DECIMAL_PLACES {
+            return Err(ContractError::PrecisionLoss {});
+        }
+        // num * 10^(36 - scale) is below 2^128 * 10^36, which fits Uint256
+        let factor = Uint256::from(10u128).pow(Self::DECIMAL_PLACES - scale);
+        Ok(Self::from_parts(false, Uint256::from(num.into()) * factor))
+    }
+
+    /// Exact conversion, StdDecimal has 18 decimal places
+    pub fn from_std(x: StdDecimal) -> Self {
+        Self::from_parts(false, Uint256::from(x.atomics()) * STD_DECIMAL_FACTOR)
+    }
+
+    /// Truncates to the 18 decimal places of StdDecimal. Errors with `PrecisionLoss` for negative
+    /// numbers and `CurveOverflow` if the value doesn't fit.
+    pub fn to_std(self) -> Result<StdDecimal, ContractError> {
+        if self.negative {
+            return Err(ContractError::PrecisionLoss {});
+        }
+        let atomics = Uint128::try_from(self.atomics / STD_DECIMAL_FACTOR)
+            .map_err(|_| ContractError::CurveOverflow {})?;
+        Ok(StdDecimal::new(atomics))
+    }
+
+    /// `self * 10^places` rounded to a whole number, eg. the amount in base units of a token with
+    /// `places` decimal places. Errors with `PrecisionLoss` for negative numbers and
+    /// `CurveOverflow` if the result doesn't fit a Uint128.
+    pub fn to_base_units(self, places: u32, rounding: Rounding) -> Result<Uint128, ContractError> {
+        if self.negative {
+            return Err(ContractError::PrecisionLoss {});
+        }
+        let units = if places <= Self::DECIMAL_PLACES {
+            let factor = Uint256::from(10u128).pow(Self::DECIMAL_PLACES - places);
+            let units = self.atomics / factor;
+            match rounding {
+                Rounding::Up if !(self.atomics % factor).is_zero() => units + Uint256::from(1u8),
+                _ => units,
+            }
+        } else {
+            let factor = Uint256::from(10u128)
+                .checked_pow(places - Self::DECIMAL_PLACES)
+                .map_err(|_| ContractError::CurveOverflow {})?;
+            self.atomics
+                .checked_mul(factor)
+                .map_err(|_| ContractError::CurveOverflow {})?
+        };
+        Uint128::try_from(units).map_err(|_| ContractError::CurveOverflow {})
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.atomics.is_zero()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    pub fn abs(self) -> Self {
+        Self::from_parts(false, self.atomics)
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, ContractError> {
+        if self.negative == other.negative {
+            let atomics = self
+                .atomics
+                .checked_add(other.atomics)
+                .map_err(|_| ContractError::CurveOverflow {})?;
+            return Ok(Self::from_parts(self.negative, atomics));
+        }
+        // opposite signs, the larger magnitude keeps its sign
+        if self.atomics >= other.atomics {
+            Ok(Self::from_parts(
+                self.negative,
+                self.atomics - other.atomics,
+            ))
+        } else {
+            Ok(Self::from_parts(
+                other.negative,
+                other.atomics - self.atomics,
+            ))
+        }
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, ContractError> {
+        self.checked_add(-other)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self, ContractError> {
+        let product = self.atomics.full_mul(other.atomics) / Uint512::from(ATOMICS_PER_UNIT);
+        Ok(Self::from_parts(
+            self.negative != other.negative,
+            narrow(product)?,
+        ))
+    }
+
+    pub fn checked_div(self, other: Self) -> Result<Self, ContractError> {
+        if other.is_zero() {
+            return Err(ContractError::DivisionByZero {});
+        }
+        let quotient = self.atomics.full_mul(ATOMICS_PER_UNIT) / Uint512::from(other.atomics);
+        Ok(Self::from_parts(
+            self.negative != other.negative,
+            narrow(quotient)?,
+        ))
+    }
+
+    /// `self^exp`, by squaring
+    pub fn checked_powu(self, mut exp: u32) -> Result<Self, ContractError> {
+        let mut base = self;
+        let mut out = Self::ONE;
+        while exp > 0 {
+            if exp & 1 == 1 {
+                out = out.checked_mul(base)?;
+            }
+            exp >>= 1;
+            if exp > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Ok(out)
+    }
+
+    /// `e^self`. Underflows to zero for very negative numbers and errors with `CurveOverflow`
+    /// past ~177.
+    pub fn checked_exp(self) -> Result<Self, ContractError> {
+        // e^x = 2^k * e^r with r = x - k * ln(2) in [0, ln(2))
+        let k = match self.checked_div(LN_2)?.floor_i32() {
+            Some(k) if k < 256 => k,
+            // 2^-256 is below our precision
+            _ if self.negative => return Ok(Self::ZERO),
+            // 2^256 is out of range
+            _ => return Err(ContractError::CurveOverflow {}),
+        };
+        let r = self.checked_sub(LN_2.checked_mul(Self::from_i32(k))?)?;
+
+        // e^r = sum(r^i / i!), terms shrink fast for r < 1
+        let mut term = Self::ONE;
+        let mut sum = Self::ONE;
+        let mut i = 1u128;
+        while !term.is_zero() {
+            term = term.checked_mul(r)?.checked_div(Self::from_u128(i))?;
+            sum = sum.checked_add(term)?;
+            i += 1;
+        }
+
+        let atomics = if k >= 0 {
+            sum.atomics
+                .checked_shl(k as u32)
+                .ok()
+                // shl drops bits past 256 without failing
+                .filter(|shifted| shifted.checked_shr(k as u32).ok() == Some(sum.atomics))
+                .ok_or(ContractError::CurveOverflow {})?
+        } else {
+            sum.atomics
+                .checked_shr(k.unsigned_abs())
+                .unwrap_or_else(|_| Uint256::zero())
+        };
+        Ok(Self::from_parts(false, atomics))
+    }
+
+    /// `ln(self)`. Errors with `PrecisionLoss` for non-positive numbers, which the curves only
+    /// reach through rounding.
+    pub fn checked_ln(self) -> Result<Self, ContractError> {
+        if self.negative || self.is_zero() {
+            return Err(ContractError::PrecisionLoss {});
+        }
+        // ln(x) = k * ln(2) + ln(m) with m = x / 2^k in [1, 2)
+        let two = Self::from_u128(2);
+        let mut m = self;
+        let mut k = 0i32;
+        while m >= two {
+            m = Self::from_parts(false, m.atomics >> 1);
+            k += 1;
+        }
+        while m < Self::ONE {
+            m = Self::from_parts(false, m.atomics << 1);
+            k -= 1;
+        }
+
+        // ln(m) = 2 * atanh(z) = 2 * sum(z^(2i+1) / (2i+1)) with z = (m - 1) / (m + 1) <= 1/3
+        let z = m
+            .checked_sub(Self::ONE)?
+            .checked_div(m.checked_add(Self::ONE)?)?;
+        let z_squared = z.checked_mul(z)?;
+        let mut power = z;
+        let mut sum = Self::ZERO;
+        let mut i = 1u128;
+        while !power.is_zero() {
+            sum = sum.checked_add(power.checked_div(Self::from_u128(i))?)?;
+            power = power.checked_mul(z_squared)?;
+            i += 2;
+        }
+        let ln_m = sum.checked_add(sum)?;
+        LN_2.checked_mul(Self::from_i32(k))?.checked_add(ln_m)
+    }
+
+    /// `self^(1/n)` for non-negative numbers, rounded down.
+    ///
+    /// This is the exact integer root of the scaled atomics whenever they fit Uint512, which is
+    /// the case for square and cube roots of any value. Higher roots drop as many digits as needed
+    /// to fit and refine the result with Newton's method, which can be off by a few atomics.
+    pub fn checked_nth_root(self, n: u32) -> Result<Self, ContractError> {
+        if n == 0 {
+            return Err(ContractError::DivisionByZero {});
+        }
+        if self.negative {
+            return Err(ContractError::PrecisionLoss {});
+        }
+        if n == 1 || self.is_zero() {
+            return Ok(self);
+        }
+
+        // root(a * 10^-36) * 10^36 = root(a * 10^(36 * (n - 1)))
+        // if that doesn't fit, take root(a * 10^(36 * (n - 1) - n * d)) * 10^d instead
+        let atomics = Uint512::from(self.atomics);
+        let target = Self::DECIMAL_PLACES * (n - 1);
+        let (scaled, dropped) = (0..=target / n)
+            .find_map(|dropped| {
+                Uint512::from(10u128)
+                    .checked_pow(target - n * dropped)
+                    .and_then(|factor| atomics.checked_mul(factor))
+                    .ok()
+                    .map(|scaled| (scaled, dropped))
+            })
+            .ok_or(ContractError::CurveOverflow {})?;
+        let root = integer_root(scaled, n)
+            .checked_mul(Uint512::from(10u128).pow(dropped))
+            .map_err(|_| ContractError::CurveOverflow {})?;
+        let mut root = Self::from_parts(false, narrow(root)?);
+        if dropped == 0 {
+            return Ok(root);
+        }
+
+        // win the dropped digits back with Newton's method, r' = ((n - 1) * r + x / r^(n - 1)) / n
+        // the first step lands above the root, from there r decreases until it settles on it
+        let n_minus_one = Self::from(n - 1);
+        for step in 0..100 {
+            let next = root
+                .checked_powu(n - 1)
+                .and_then(|raised| self.checked_div(raised))
+                .and_then(|quotient| n_minus_one.checked_mul(root)?.checked_add(quotient))
+                .and_then(|sum| sum.checked_div(Self::from(n)));
+            match next {
+                Ok(next) if step == 0 || next < root => root = next,
+                _ => break,
+            }
+        }
+        Ok(root)
+    }
+
+    fn from_parts(negative: bool, atomics: Uint256) -> Self {
+        Self {
+            // there is only one zero
+            negative: negative && !atomics.is_zero(),
+            atomics,
+        }
+    }
+
+    fn from_u128(num: u128) -> Self {
+        Self::from_parts(false, Uint256::from(num) * ATOMICS_PER_UNIT)
+    }
+
+    fn from_i32(num: i32) -> Self {
+        Self::from_parts(
+            num < 0,
+            Uint256::from(num.unsigned_abs()) * ATOMICS_PER_UNIT,
+        )
+    }
+
+    /// rounds towards negative infinity, `None` if out of the i32 range
+    fn floor_i32(self) -> Option<i32> {
+        let whole = self.atomics / ATOMICS_PER_UNIT;
+        let whole = i32::try_from(Uint128::try_from(whole).ok()?.u128()).ok()?;
+        if !self.negative {
+            return Some(whole);
+        }
+        let has_fraction = !(self.atomics % ATOMICS_PER_UNIT).is_zero();
+        Some(-whole - has_fraction as i32)
+    }
+}
+
+impl From<Uint128> for Fixed {
+    fn from(num: Uint128) -> Self {
+        Self::from_u128(num.u128())
+    }
+}
+
+impl From<u32> for Fixed {
+    fn from(num: u32) -> Self {
+        Self::from_u128(num.into())
+    }
+}
+
+impl Neg for Fixed {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_parts(!self.negative, self.atomics)
+    }
+}
+
+impl PartialOrd for Fixed {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Fixed {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, false) => self.atomics.cmp(&other.atomics),
+            (true, true) => other.atomics.cmp(&self.atomics),
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+        }
+    }
+}
+
+fn narrow(x: Uint512) -> Result<Uint256, ContractError> {
+    Uint256::try_from(x).map_err(|_| ContractError::CurveOverflow {})
+}
+
+/// `floor(x^(1/n))` with Newton's method, for n >= 2
+fn integer_root(x: Uint512, n: u32) -> Uint512 {
+    // start at a power of two above the root, from there Newton's method decreases
+    // monotonically until it settles on the floor of the root
+    let mut bits = 0u32;
+    while matches!(x.checked_shr(bits), Ok(shifted) if !shifted.is_zero()) {
+        bits += 1;
+    }
+    let mut root = Uint512::from(2u32).pow((bits + n - 1) / n);
+    let n_wide = Uint512::from(n);
+    let n_minus_one = Uint512::from(n - 1);
+    loop {
+        // r' = ((n - 1) * r + x / r^(n - 1)) / n, an overflowing r^(n - 1) is way above x
+        let quotient = root
+            .checked_pow(n - 1)
+            .map(|raised| x / raised)
+            .unwrap_or_else(|_| Uint512::zero());
+        let next = (n_minus_one * root + quotient) / n_wide;
+        if next >= root {
+            return root;
+        }
+        root = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(num: u128, scale: u32) -> Fixed {
+        Fixed::from_atomics(num, scale).unwrap()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let a = fixed(15, 1);
+        let b = fixed(4, 0);
+        assert_eq!(fixed(55, 1), a.checked_add(b).unwrap());
+        assert_eq!(-fixed(25, 1), a.checked_sub(b).unwrap());
+        assert_eq!(fixed(6, 0), a.checked_mul(b).unwrap());
+        assert_eq!(fixed(375, 3), a.checked_div(b).unwrap());
+        assert_eq!(-fixed(6, 0), (-a).checked_mul(b).unwrap());
+        assert_eq!(fixed(50625, 4), a.checked_powu(4).unwrap());
+        assert!(-b < -a && -a < Fixed::ZERO && Fixed::ZERO < a && a < b);
+
+        // 1 / 3 truncates at the 36th decimal place
+        let third = Fixed::ONE.checked_div(fixed(3, 0)).unwrap();
+        assert_eq!(
+            fixed(333_333_333_333_333_333_333_333_333_333_333_333, 36),
+            third
+        );
+
+        let err = a.checked_div(Fixed::ZERO).unwrap_err();
+        assert!(matches!(err, ContractError::DivisionByZero {}));
+        let huge = fixed(u128::MAX, 0);
+        let err = huge.checked_mul(huge).unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+        let err = Fixed::from_atomics(1u128, 37).unwrap_err();
+        assert!(matches!(err, ContractError::PrecisionLoss {}));
+    }
+
+    #[test]
+    fn conversions() {
+        // 18 decimal places survive the round trip through base units
+        let wei = 123_456_789_012_345_678_901_234_567u128;
+        let x = fixed(wei, 18);
+        assert_eq!(
+            Uint128::new(wei),
+            x.to_base_units(18, Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(wei),
+            x.to_base_units(18, Rounding::Up).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(123_456_789),
+            x.to_base_units(0, Rounding::Down).unwrap()
+        );
+        assert_eq!(
+            Uint128::new(123_456_790),
+            x.to_base_units(0, Rounding::Up).unwrap()
+        );
+
+        let std = StdDecimal::percent(12345);
+        assert_eq!(std, Fixed::from_std(std).to_std().unwrap());
+        assert_eq!(
+            StdDecimal::from_ratio(333_999u128, 1_000_000u128),
+            fixed(333_999, 6).to_std().unwrap()
+        );
+        // digits past 18 decimal places are cut off
+        assert_eq!(StdDecimal::zero(), fixed(9, 19).to_std().unwrap());
+
+        let err = (-x).to_base_units(18, Rounding::Down).unwrap_err();
+        assert!(matches!(err, ContractError::PrecisionLoss {}));
+        let err = fixed(u128::MAX, 0)
+            .to_base_units(1, Rounding::Down)
+            .unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+    }
+
+    #[test]
+    fn roots() {
+        assert_eq!(
+            fixed(1_414_213_562_373_095_048_801_688_724_209_698_078, 36),
+            fixed(2, 0).checked_nth_root(2).unwrap()
+        );
+        assert_eq!(
+            fixed(1_389_495_494_373_137_637_129_985_217_353_011_622, 36),
+            fixed(10, 0).checked_nth_root(7).unwrap()
+        );
+        assert_eq!(fixed(3, 0), fixed(243, 0).checked_nth_root(5).unwrap());
+        assert_eq!(fixed(1, 3), fixed(1, 9).checked_nth_root(3).unwrap());
+        assert_eq!(Fixed::ZERO, Fixed::ZERO.checked_nth_root(4).unwrap());
+
+        // large values still get a root, dropping digits past what fits
+        let huge = fixed(u128::MAX, 0).checked_mul(fixed(100, 0)).unwrap();
+        let root = huge.checked_nth_root(9).unwrap();
+        let expected = fixed(31_881_424_364_771_551_914_539, 18);
+        assert!(root.checked_sub(expected).unwrap().abs() < fixed(1, 9));
+    }
+
+    #[test]
+    fn exp_and_ln() {
+        let e = Fixed::ONE.checked_exp().unwrap();
+        let expected = fixed(2_718_281_828_459_045_235_360_287_471_352_662_497, 36);
+        assert!(e.checked_sub(expected).unwrap().abs() <= fixed(100, 36));
+
+        let ln_10 = fixed(10, 0).checked_ln().unwrap();
+        let expected = fixed(2_302_585_092_994_045_684_017_991_454_684_364_207, 36);
+        assert!(ln_10.checked_sub(expected).unwrap().abs() <= fixed(100, 36));
+
+        // negative exponents and logs of values below one
+        let x = -fixed(25, 1);
+        let round_trip = x.checked_exp().unwrap().checked_ln().unwrap();
+        assert!(round_trip.checked_sub(x).unwrap().abs() <= fixed(1, 30));
+
+        assert_eq!(Fixed::ONE, Fixed::ZERO.checked_exp().unwrap());
+        assert_eq!(Fixed::ZERO, Fixed::ONE.checked_ln().unwrap());
+        assert_eq!(Fixed::ZERO, (-fixed(1000, 0)).checked_exp().unwrap());
+        let err = fixed(1000, 0).checked_exp().unwrap_err();
+        assert!(matches!(err, ContractError::CurveOverflow {}));
+        let err = (-Fixed::ONE).checked_ln().unwrap_err();
+        assert!(matches!(err, ContractError::PrecisionLoss {}));
+    }
+}
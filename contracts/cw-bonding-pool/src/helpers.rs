@@ -1,39 +1,70 @@
-use cosmwasm_std::{coins, BankMsg, Coin, CosmosMsg, Uint128};
-use osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgBurn, MsgMint};
+use cosmwasm_std::{
+    coins, to_binary, BankMsg, Coin, CosmosMsg, StdError, StdResult, Uint128, WasmMsg,
+};
+use cw20::Cw20ExecuteMsg;
+use osmosis_std::types::cosmos::bank::v1beta1::{DenomUnit, Metadata};
+use osmosis_std::types::osmosis::gamm::poolmodels::balancer::v1beta1::MsgCreateBalancerPool;
+use osmosis_std::types::osmosis::gamm::v1beta1::{PoolAsset, PoolParams};
+use osmosis_std::types::osmosis::tokenfactory::v1beta1::{MsgBurn, MsgMint, MsgSetDenomMetadata};
+use sha2::{Digest, Sha256};
 
+use crate::state::{DenomMetadata, GraduationPool, ReserveAsset};
+
+/// Mints supply tokens, or sends reserve tokens out of the pool with `send_reserve`.
+/// `spend_denom` is the contract address of a cw20 reserve.
 pub fn mint_or_send(
     supply_denom: String,
     spend_denom: String,
     amount: Uint128,
     sender: String,
     contract: String,
-) -> CosmosMsg {
+    reserve_asset: &ReserveAsset,
+) -> StdResult<CosmosMsg> {
     if spend_denom == supply_denom {
-        MsgMint {
+        Ok(MsgMint {
             amount: osmosis_std::cosmwasm_to_proto_coins(coins(amount.u128(), spend_denom)).pop(),
             mint_to_address: sender,
             sender: contract,
         }
-        .into()
+        .into())
     } else {
-        BankMsg::Send {
-            to_address: sender,
-            amount: coins(amount.u128(), spend_denom),
+        send_reserve(spend_denom, amount, sender, reserve_asset)
+    }
+}
+
+/// Sends reserve tokens out of the pool, with `Cw20ExecuteMsg::Transfer` for a cw20 reserve
+pub fn send_reserve(
+    reserve_denom: String,
+    amount: Uint128,
+    recipient: String,
+    reserve_asset: &ReserveAsset,
+) -> StdResult<CosmosMsg> {
+    match reserve_asset {
+        ReserveAsset::Native => Ok(BankMsg::Send {
+            to_address: recipient,
+            amount: coins(amount.u128(), reserve_denom),
+        }
+        .into()),
+        ReserveAsset::Cw20 => Ok(WasmMsg::Execute {
+            contract_addr: reserve_denom,
+            msg: to_binary(&Cw20ExecuteMsg::Transfer { recipient, amount })?,
+            funds: vec![],
         }
-        .into()
+        .into()),
     }
 }
 
+/// Supply tokens have already been transferred to the contract by the time a sell is executed,
+/// so they are burned from the contract's own balance to keep bank supply in line with the curve.
 pub fn burn_or_receive(
     supply_denom: String,
     received_denom: String,
     amount: Uint128,
-    sender: String,
     contract: String,
 ) -> Vec<CosmosMsg> {
     if received_denom == supply_denom {
         let msg = MsgBurn {
-            burn_from_address: sender,
+            burn_from_address: contract.clone(),
             sender: contract,
             amount: osmosis_std::cosmwasm_to_proto_coins(coins(amount.u128(), received_denom))
                 .pop(),
@@ -50,12 +81,12 @@ pub fn create_coin_io_messages(
     supply_denom: String,
     sender: String,
     contract: String,
-) -> Vec<CosmosMsg> {
+    reserve_asset: &ReserveAsset,
+) -> StdResult<Vec<CosmosMsg>> {
     let mut msgs = burn_or_receive(
         supply_denom.clone(),
         token_in.denom,
         token_in.amount,
-        sender.clone(),
         contract.clone(),
     );
     msgs.push(mint_or_send(
@@ -64,15 +95,124 @@ pub fn create_coin_io_messages(
         token_out.amount,
         sender,
         contract,
-    ));
-    msgs
+        reserve_asset,
+    )?);
+    Ok(msgs)
+}
+
+/// Sets the bank metadata of the supply token. Whole tokens are `decimals` places above the base
+/// denom and display as the symbol.
+pub fn set_denom_metadata_msg(
+    supply_denom: String,
+    decimals: u32,
+    metadata: &DenomMetadata,
+    contract: String,
+) -> CosmosMsg {
+    let subdenom = supply_denom
+        .rsplit('/')
+        .next()
+        .unwrap_or_default()
+        .to_string();
+    let symbol = metadata
+        .symbol
+        .clone()
+        .unwrap_or_else(|| subdenom.to_uppercase());
+    let mut denom_units = vec![DenomUnit {
+        denom: supply_denom.clone(),
+        exponent: 0,
+        aliases: vec![],
+    }];
+    let display = if decimals == 0 {
+        supply_denom.clone()
+    } else {
+        denom_units.push(DenomUnit {
+            denom: symbol.clone(),
+            exponent: decimals,
+            aliases: vec![],
+        });
+        symbol.clone()
+    };
+    MsgSetDenomMetadata {
+        sender: contract,
+        metadata: Some(Metadata {
+            description: metadata.description.clone().unwrap_or_default(),
+            denom_units,
+            base: supply_denom,
+            display,
+            name: metadata.name.clone().unwrap_or(subdenom),
+            symbol,
+        }),
+    }
+    .into()
+}
+
+/// Creates the graduation pool from the contract, seeded with `liquidity`. The gamm module takes the
+/// pool creation fee from the contract balance on top of it.
+pub fn create_graduation_pool_msg(
+    pool: &GraduationPool,
+    liquidity: Vec<Coin>,
+    contract: String,
+) -> CosmosMsg {
+    match pool {
+        GraduationPool::Balancer { swap_fee } => MsgCreateBalancerPool {
+            sender: contract,
+            pool_params: Some(PoolParams {
+                // sdk.Dec is encoded as its 18 decimal places integer
+                swap_fee: swap_fee.atomics().to_string(),
+                exit_fee: "0".to_string(),
+                smooth_weight_change_params: None,
+            }),
+            pool_assets: liquidity
+                .into_iter()
+                .map(|token| PoolAsset {
+                    token: Some(token.into()),
+                    weight: "1".to_string(),
+                })
+                .collect(),
+            future_pool_governor: "".to_string(),
+        }
+        .into(),
+    }
+}
+
+/// Whether `proof` proves `allocation` for `address` under `merkle_root`. Leaves are
+/// `sha256(address + allocation)` and pairs are hashed in sorted order, as in cw20-merkle-airdrop.
+pub fn verify_merkle_proof(
+    merkle_root: &str,
+    address: &str,
+    allocation: Uint128,
+    proof: &[String],
+) -> StdResult<bool> {
+    let mut hash: [u8; 32] = Sha256::digest(format!("{}{}", address, allocation)).into();
+    for step in proof {
+        let step = decode_hash(step)?;
+        let (first, second) = if hash < step {
+            (hash, step)
+        } else {
+            (step, hash)
+        };
+        hash = Sha256::new()
+            .chain_update(first)
+            .chain_update(second)
+            .finalize()
+            .into();
+    }
+    Ok(hash == decode_hash(merkle_root)?)
+}
+
+/// Decodes a hex sha256 hash
+pub fn decode_hash(hash: &str) -> StdResult<[u8; 32]> {
+    let mut bytes = [0u8; 32];
+    hex::decode_to_slice(hash, &mut bytes)
+        .map_err(|_| StdError::generic_err(format!("invalid sha256 hash {}", hash)))?;
+    Ok(bytes)
 }
 
 #[cfg(test)]
 pub mod tests {
     
 
-    use cosmwasm_std::{coin};
+    use cosmwasm_std::{coin, Decimal};
 
     use super::*;
 
@@ -94,7 +234,9 @@ pub mod tests {
             supply_denom,
             sender.clone(),
             sender.clone(),
-        );
+            &ReserveAsset::Native,
+        )
+        .unwrap();
         println!("{:?}", msgs);
         assert_eq!(msgs.len(), 1);
         assert_eq!(
@@ -112,4 +254,187 @@ pub mod tests {
             _ => panic!("Unexpected message type"),
         }
     }
+
+    #[test]
+    fn test_create_coin_io_messages_burns_supply_in() {
+        let token_in = coin(1000000u128, "ushirt");
+        let token_out = coin(1000000u128, "uosmo");
+        let msgs = create_coin_io_messages(
+            token_in,
+            token_out,
+            "ushirt".to_string(),
+            "sender".to_string(),
+            "contract".to_string(),
+            &ReserveAsset::Native,
+        )
+        .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(
+            msgs[0],
+            MsgBurn {
+                amount: Some(coin(1000000u128, "ushirt").into()),
+                burn_from_address: "contract".to_string(),
+                sender: "contract".to_string(),
+            }
+            .into()
+        );
+        assert_eq!(
+            msgs[1],
+            BankMsg::Send {
+                to_address: "sender".to_string(),
+                amount: coins(1000000u128, "uosmo"),
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_create_coin_io_messages_transfers_cw20_reserve_out() {
+        let token_in = coin(1000000u128, "ushirt");
+        let token_out = coin(1000000u128, "cw20reserve");
+        let msgs = create_coin_io_messages(
+            token_in,
+            token_out,
+            "ushirt".to_string(),
+            "sender".to_string(),
+            "contract".to_string(),
+            &ReserveAsset::Cw20,
+        )
+        .unwrap();
+        assert_eq!(msgs.len(), 2);
+        assert_eq!(
+            msgs[1],
+            WasmMsg::Execute {
+                contract_addr: "cw20reserve".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: "sender".to_string(),
+                    amount: Uint128::new(1000000),
+                })
+                .unwrap(),
+                funds: vec![],
+            }
+            .into()
+        );
+    }
+
+    #[test]
+    fn test_set_denom_metadata_msg() {
+        let metadata = DenomMetadata {
+            name: Some("Suit Shirt".to_string()),
+            symbol: Some("SHIRT".to_string()),
+            ..DenomMetadata::default()
+        };
+        let msg = set_denom_metadata_msg(
+            "factory/contract/ushirt".to_string(),
+            6,
+            &metadata,
+            "contract".to_string(),
+        );
+        assert_eq!(
+            msg,
+            MsgSetDenomMetadata {
+                sender: "contract".to_string(),
+                metadata: Some(Metadata {
+                    description: "".to_string(),
+                    denom_units: vec![
+                        DenomUnit {
+                            denom: "factory/contract/ushirt".to_string(),
+                            exponent: 0,
+                            aliases: vec![],
+                        },
+                        DenomUnit {
+                            denom: "SHIRT".to_string(),
+                            exponent: 6,
+                            aliases: vec![],
+                        },
+                    ],
+                    base: "factory/contract/ushirt".to_string(),
+                    display: "SHIRT".to_string(),
+                    name: "Suit Shirt".to_string(),
+                    symbol: "SHIRT".to_string(),
+                }),
+            }
+            .into()
+        );
+
+        // without decimals the base denom is the display unit
+        let msg = set_denom_metadata_msg(
+            "factory/contract/shirt".to_string(),
+            0,
+            &DenomMetadata::default(),
+            "contract".to_string(),
+        );
+        let expected: CosmosMsg = MsgSetDenomMetadata {
+            sender: "contract".to_string(),
+            metadata: Some(Metadata {
+                description: "".to_string(),
+                denom_units: vec![DenomUnit {
+                    denom: "factory/contract/shirt".to_string(),
+                    exponent: 0,
+                    aliases: vec![],
+                }],
+                base: "factory/contract/shirt".to_string(),
+                display: "factory/contract/shirt".to_string(),
+                name: "shirt".to_string(),
+                symbol: "SHIRT".to_string(),
+            }),
+        }
+        .into();
+        assert_eq!(msg, expected);
+    }
+
+    #[test]
+    fn test_verify_merkle_proof() {
+        // first entry of scripts/test-airdrop-proofs.json,
+        // under the root of scripts/instantiate_airdrop.sh
+        let merkle_root = "50a053def35526a1e0e00d990653dc02dde14ae4e58b003d66ee25481470be0c";
+        let address = "osmo1phaxpevm5wecex2jyaqty2a4v02qj7qmlmzk5a";
+        let proof = [
+            "d364020188ddb8ae2122a9c94e5a2499418dedc8d2697cabe62fe7e48a8d75a4",
+            "dbbaa79e030b8a00c2cdcf60ea2c85316b266dd3592f13061aa4d6299bfaf7a5",
+            "108f643e19afb49e20dbb3cd2cd8b3cf53e272452ba45e7317aecaaca9d5a4b7",
+        ]
+        .map(str::to_string);
+        let allocation = Uint128::new(1000000);
+        assert!(verify_merkle_proof(merkle_root, address, allocation, &proof).unwrap());
+        let other_allocation = allocation + Uint128::one();
+        assert!(!verify_merkle_proof(merkle_root, address, other_allocation, &proof).unwrap());
+        assert!(!verify_merkle_proof(merkle_root, "osmo1other", allocation, &proof).unwrap());
+        let bad_proof = ["nothex".to_string()];
+        assert!(verify_merkle_proof(merkle_root, address, allocation, &bad_proof).is_err());
+    }
+
+    #[test]
+    fn test_create_graduation_pool_msg() {
+        let msg = create_graduation_pool_msg(
+            &GraduationPool::Balancer {
+                swap_fee: Decimal::permille(3),
+            },
+            vec![coin(100, "uosmo"), coin(50, "ushirt")],
+            "contract".to_string(),
+        );
+        assert_eq!(
+            msg,
+            MsgCreateBalancerPool {
+                sender: "contract".to_string(),
+                pool_params: Some(PoolParams {
+                    swap_fee: "3000000000000000".to_string(),
+                    exit_fee: "0".to_string(),
+                    smooth_weight_change_params: None,
+                }),
+                pool_assets: vec![
+                    PoolAsset {
+                        token: Some(coin(100, "uosmo").into()),
+                        weight: "1".to_string(),
+                    },
+                    PoolAsset {
+                        token: Some(coin(50, "ushirt").into()),
+                        weight: "1".to_string(),
+                    },
+                ],
+                future_pool_governor: "".to_string(),
+            }
+            .into()
+        );
+    }
 }
@@ -1,11 +1,13 @@
 pub mod contract;
 pub mod curves;
 mod error;
+pub mod fixed;
 pub mod msg;
 pub mod state;
 pub use crate::error::ContractError;
 pub mod calc;
 pub mod helpers;
+pub mod twap;
 
 #[cfg(feature = "interface")]
 mod interface;
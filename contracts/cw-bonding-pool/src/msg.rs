@@ -1,12 +1,19 @@
 use cosmwasm_schema::{cw_serde, QueryResponses};
-use cosmwasm_std::{Coin, Decimal, Uint128};
+use cosmwasm_std::{Coin, Decimal, Timestamp, Uint128};
+use cw20::Cw20ReceiveMsg;
 
 use crate::{
     curves::{
-        decimal, Constant, CubeRootSquared, Curve, DecimalPlaces, Linear, SquareRoot,
-        SquareRootCubed,
+        checked_decimal, std_to_decimal, Constant, Curve, CurveInvariants, DecimalPlaces,
+        Exponential, Linear, Piecewise, Power, Sigmoid,
+    },
+    error::ContractError,
+    state::{
+        BreakerWindowState, CircuitBreakerConfig, CircuitBreakerTrip, Contribution,
+        CrowdfundConfig, CrowdfundStatus, CurveState, DenomMetadata, FeeConfig, Graduation,
+        GraduationConfig, Ownership, PauseState, ReserveAsset, RetirePolicy, SalePhase, Trade,
+        TradeHistoryConfig,
     },
-    state::CurveState,
 };
 
 #[cw_serde]
@@ -16,11 +23,19 @@ pub struct InstantiateMsg {
     /// If it is eg. BTC, where a balance of 10^8 means 1 BTC, then use 8 here.
     pub supply_decimals: u8,
 
+    /// bank metadata of the supply token, so wallets show a name, symbol and whole tokens
+    pub name: Option<String>,
+    pub symbol: Option<String>,
+    pub description: Option<String>,
+    pub uri: Option<String>,
+
     // maximum supply of the token
     pub max_supply: Uint128,
 
-    /// this is the reserve token denom (only support native for now)
+    /// this is the reserve token denom, or the token contract address for a cw20 reserve
     pub reserve_denom: String,
+    /// whether the reserve token is native or cw20. Defaults to native.
+    pub reserve_asset: Option<ReserveAsset>,
     /// number of decimal places for the reserve token, needed for proper curve math.
     /// Same format as decimals above, eg. if it is uatom, where 1 unit is 10^-6 ATOM, use 6 here
     pub reserve_decimals: u8,
@@ -31,6 +46,39 @@ pub struct InstantiateMsg {
     /// with your custom curve as a parameter (and same with `query` -> `do_query`)
     pub curve_type: CurveType,
 
+    /// fraction of every swap kept as fee, charged in the reserve token. Defaults to zero.
+    pub swap_fee: Option<Decimal>,
+
+    /// address that receives swap fees. Defaults to the instantiator.
+    pub fee_collector: Option<String>,
+
+    /// address allowed to run admin actions. Defaults to the instantiator.
+    pub owner: Option<String>,
+
+    /// what happens to the reserve when the pool is retired. Defaults to keeping it.
+    pub retire_policy: Option<RetirePolicy>,
+
+    /// seeds a standard Osmosis pool from the reserve once enough supply has been sold.
+    /// Requires a native reserve. Disabled by default.
+    pub graduation: Option<GraduationConfig>,
+
+    /// only produce the supply if a target is bought by a deadline, refunding buyers otherwise.
+    /// Disabled by default.
+    pub crowdfund: Option<CrowdfundConfig>,
+
+    /// schedule of sale phases, ordered by start. Buys are always open without one.
+    pub sale_phases: Option<Vec<SalePhase>>,
+
+    /// most supply one wallet can buy, net of its sells. Not capped by default.
+    pub max_per_address: Option<Uint128>,
+
+    /// pauses the pool instead of swaps that move the price or reserve too far within a window.
+    /// Disabled by default.
+    pub circuit_breaker: Option<CircuitBreakerConfig>,
+
+    /// bounds of the stored trade history. Every trade is kept by default.
+    pub trade_history: Option<TradeHistoryConfig>,
+
     /// Enable if you want to test the contract without cosmwasmpool
     pub test_mode: Option<bool>,
 
@@ -43,6 +91,137 @@ pub enum ExecuteMsg {
     Dissolve {},
     Sudo(SudoMsg),
     Simulate(SimulationMsg),
+
+    /// Buys supply tokens with the reserve tokens sent along, for trading without the
+    /// cosmwasmpool module. Charges the pool's swap fee.
+    Buy {
+        /// minimum amount of supply tokens to receive
+        min_out: Uint128,
+        /// receives the supply tokens, and the reserve tokens left unspent by a buy capped by
+        /// `max_supply`. Defaults to the sender.
+        recipient: Option<String>,
+        /// the buy fails if it is executed after this time
+        deadline: Option<Timestamp>,
+        /// proves the sender's allocation for allowlist phases. Once proven, later buys of
+        /// the sender can leave it out, including those through the cosmwasmpool module.
+        allowlist_proof: Option<AllowlistProof>,
+    },
+    /// Sells the supply tokens sent along for reserve tokens, for trading without the
+    /// cosmwasmpool module. Charges the pool's swap fee.
+    Sell {
+        /// minimum amount of reserve tokens to receive, after the swap fee
+        min_out: Uint128,
+        /// receives the reserve tokens. Defaults to the sender.
+        recipient: Option<String>,
+        /// the sell fails if it is executed after this time
+        deadline: Option<Timestamp>,
+    },
+    /// Buys with a cw20 reserve, sent with `Cw20ExecuteMsg::Send` and a `ReceiveMsg` hook
+    Receive(Cw20ReceiveMsg),
+
+    /// Proposes a new owner, who has to send `AcceptOwnership` to take over. Owner only.
+    ProposeNewOwner {
+        new_owner: String,
+    },
+    /// Accepts a pending ownership proposal. Pending owner only.
+    AcceptOwnership {},
+    /// Permanently gives up ownership, disabling every owner-gated action. Owner only.
+    RenounceOwnership {},
+
+    /// Pauses or resumes buys and sells together, also after the circuit breaker tripped.
+    /// Owner only.
+    SetActive {
+        is_active: bool,
+    },
+    /// Updates the swap fee and/or the fee collector. Owner only.
+    UpdateFeeConfig {
+        swap_fee: Option<Decimal>,
+        fee_collector: Option<String>,
+    },
+    /// Hands the tokenfactory admin of the supply denom to `new_admin`, eg. a DAO.
+    /// The pool can't mint or burn afterwards, so it has to be retired first. Owner only.
+    TransferDenomAdmin {
+        new_admin: String,
+    },
+    /// Permanently stops swaps and settles the reserve according to the retire policy.
+    /// Optionally hands the supply denom admin to `denom_admin` as well. Owner only.
+    RetirePool {
+        denom_admin: Option<String>,
+    },
+    /// Burns the supply tokens sent along and refunds what was paid for them, swap fee included.
+    /// Only after the crowdfund missed its target, for supply bought by the sender.
+    ClaimRefund {},
+    /// Replaces the sale schedule, an empty list lifts it. Wallet caps are counted per phase
+    /// start, so phases keeping their start keep their counts. Owner only.
+    UpdateSalePhases {
+        phases: Vec<SalePhase>,
+    },
+    /// Registers the hex sha256 root of a merkle tree of allowlisted addresses and their
+    /// allocations, replacing allocations proven under an earlier root. Owner only.
+    RegisterMerkleRoot {
+        merkle_root: String,
+    },
+    /// Adds and removes addresses of the allowlist used by `Allowlist` sale phases. Owner only.
+    UpdateAllowlist {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Pauses or resumes buys, sells and dissolves separately, leaving switches that are not
+    /// given as they are. Owner only.
+    UpdatePauseState {
+        buys: Option<bool>,
+        sells: Option<bool>,
+        dissolve: Option<bool>,
+    },
+    /// Replaces the circuit breaker, `None` removes it. Owner only.
+    UpdateCircuitBreaker {
+        circuit_breaker: Option<CircuitBreakerConfig>,
+    },
+    /// Sets or lifts the cap on what one wallet can buy, net of its sells. Owner only.
+    UpdateMaxPerAddress {
+        max_per_address: Option<Uint128>,
+    },
+    /// Exempts wallets such as the redeem contract or a treasury from `max_per_address`, or
+    /// lifts their exemption. Owner only.
+    UpdateWalletExemptions {
+        add: Vec<String>,
+        remove: Vec<String>,
+    },
+    /// Replaces the bounds of the stored trade history, `None` keeps every trade. Trades past the
+    /// new bounds are pruned as new trades are recorded. Owner only.
+    UpdateTradeHistory {
+        trade_history: Option<TradeHistoryConfig>,
+    },
+    /// Updates the given fields of the supply token metadata. Owner only.
+    UpdateDenomMetadata {
+        name: Option<String>,
+        symbol: Option<String>,
+        description: Option<String>,
+        uri: Option<String>,
+    },
+}
+
+/// Hook of a cw20 reserve `Send` to the pool
+#[cw_serde]
+pub enum ReceiveMsg {
+    /// Same as `ExecuteMsg::Buy`, spending the cw20 tokens sent. Sells stay `ExecuteMsg::Sell`
+    /// as the supply token is native.
+    Buy {
+        min_out: Uint128,
+        /// defaults to the cw20 sender
+        recipient: Option<String>,
+        deadline: Option<Timestamp>,
+        allowlist_proof: Option<AllowlistProof>,
+    },
+}
+
+/// Merkle proof of an allowlist allocation
+#[cw_serde]
+pub struct AllowlistProof {
+    /// most supply tokens the address can buy during allowlist phases
+    pub allocation: Uint128,
+    /// hex sibling hashes from the leaf up to the root
+    pub proof: Vec<String>,
 }
 
 #[cw_serde]
@@ -102,6 +281,128 @@ pub enum QueryMsg {
     // Non cosmwasmpool queries
     #[returns(BondingPoolState)]
     BondingPoolState {},
+
+    /// Returns how many supply tokens can still be bought before `max_supply` is reached
+    #[returns(RemainingSupplyResponse)]
+    RemainingSupply {},
+
+    /// Returns the swap fee and the address collecting it
+    #[returns(FeeConfig)]
+    FeeConfig {},
+
+    /// Compares the curve supply with the bank supply of the supply denom
+    #[returns(SupplyReconciliationResponse)]
+    SupplyReconciliation {},
+
+    /// Returns the current and pending owner
+    #[returns(Ownership)]
+    Ownership {},
+
+    /// Returns the metadata of the supply token
+    #[returns(DenomMetadata)]
+    DenomMetadata {},
+
+    /// Returns the retire policy and whether the pool has been retired
+    #[returns(RetirementResponse)]
+    Retirement {},
+
+    /// Returns the graduation config and, once graduated, the liquidity moved to the new pool
+    #[returns(GraduationResponse)]
+    Graduation {},
+
+    /// Returns the sale schedule
+    #[returns(Vec<SalePhase>)]
+    SalePhases {},
+
+    /// Returns the sale phase at the current block time and when the next one starts
+    #[returns(CurrentPhaseResponse)]
+    CurrentPhase {},
+
+    /// Returns the spot price of the supply token in the reserve token, averaged over time between
+    /// `start_time` and `end_time`. Prices are kept for at least 48 hours, observed at most every
+    /// 10 minutes and interpolated in between.
+    #[returns(TwapResponse)]
+    Twap {
+        start_time: Timestamp,
+        end_time: Timestamp,
+    },
+
+    /// Returns the time-weighted average spot price from `start_time` to the current block time
+    #[returns(TwapResponse)]
+    ArithmeticTwapToNow { start_time: Timestamp },
+
+    /// Returns the registered merkle root of the allowlist
+    #[returns(MerkleRootResponse)]
+    MerkleRoot {},
+
+    /// Returns how much of its allowlist allocation `address` can still buy. Uses the allocation
+    /// proven by `proof`, or else by an earlier buy.
+    #[returns(RemainingAllocationResponse)]
+    RemainingAllocation {
+        address: String,
+        proof: Option<AllowlistProof>,
+    },
+
+    /// Returns which operations are paused
+    #[returns(PauseState)]
+    PauseState {},
+
+    /// Returns the circuit breaker config, its current window and why it last tripped
+    #[returns(CircuitBreakerResponse)]
+    CircuitBreaker {},
+
+    /// Returns what `address` bought net of its sells, and how much more it can buy
+    #[returns(WalletPurchasesResponse)]
+    WalletPurchases { address: String },
+
+    /// Returns the wallets exempt from `max_per_address`
+    #[returns(ExemptWalletsResponse)]
+    ExemptWallets {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the stored trades, oldest first
+    #[returns(TradesResponse)]
+    Trades {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the stored trades of `trader`, oldest first
+    #[returns(TradesResponse)]
+    TradesByTrader {
+        trader: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the stored trades from `start_time` up to and including `end_time`, oldest first
+    #[returns(TradesResponse)]
+    TradesInRange {
+        start_time: Timestamp,
+        end_time: Timestamp,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the bounds of the stored trade history, `None` if every trade is kept
+    #[returns(Option<TradeHistoryConfig>)]
+    TradeHistory {},
+
+    /// Returns the crowdfund config and status
+    #[returns(CrowdfundResponse)]
+    Crowdfund {},
+
+    /// Returns what was bought for `address` while the crowdfund was open, less refunds
+    #[returns(Contribution)]
+    Contribution { address: String },
+
+    /// Evaluates the curve at `samples` evenly spaced supplies up to the max supply (or the
+    /// current supply if uncapped), reporting round trip errors and monotonicity violations,
+    /// and checks that the reserve covers the current supply. At most 1000 samples.
+    #[returns(CheckCurveInvariantsResponse)]
+    CheckCurveInvariants { samples: u32 },
 }
 
 #[cw_serde]
@@ -110,6 +411,117 @@ pub struct BondingPoolState {
     pub dissolved_curve_state: CurveState,
     pub curve_type: CurveType,
     pub is_active: bool,
+    /// native if left out, like pools created before cw20 reserves were supported
+    #[serde(default)]
+    pub reserve_asset: ReserveAsset,
+}
+
+#[cw_serde]
+pub struct RemainingSupplyResponse {
+    pub supply: Uint128,
+    /// `None` if the pool has no supply cap
+    pub max_supply: Option<Uint128>,
+    /// `None` if the pool has no supply cap
+    pub remaining_supply: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct SupplyReconciliationResponse {
+    /// `CURVE_STATE.supply`
+    pub curve_supply: Uint128,
+    /// total supply of the supply denom reported by the bank module. Includes the supply
+    /// minted into the graduation pool, which is not part of the curve.
+    pub bank_supply: Uint128,
+    pub is_reconciled: bool,
+}
+
+#[cw_serde]
+pub struct CheckCurveInvariantsResponse {
+    pub invariants: CurveInvariants,
+    /// `CURVE_STATE.reserve`
+    pub reserve: Uint128,
+    /// `curve.reserve(CURVE_STATE.supply)`, rounded up
+    pub curve_reserve: Uint128,
+    /// whether `reserve` covers `curve_reserve`. Trades round in the pool's favor, so the
+    /// reserve may hold a few base units more than the curve asks for, but never less.
+    pub is_reserve_backed: bool,
+}
+
+#[cw_serde]
+pub struct RetirementResponse {
+    pub retire_policy: RetirePolicy,
+    pub is_retired: bool,
+}
+
+#[cw_serde]
+pub struct GraduationResponse {
+    /// `None` if the pool doesn't graduate
+    pub config: Option<GraduationConfig>,
+    /// `None` until the pool has graduated
+    pub graduation: Option<Graduation>,
+}
+
+#[cw_serde]
+pub struct TwapResponse {
+    pub twap: Decimal,
+}
+
+#[cw_serde]
+pub struct CurrentPhaseResponse {
+    /// `None` before the first phase, or if the pool has no sale schedule
+    pub phase: Option<SalePhase>,
+    /// `None` during the last phase
+    pub next_phase_start: Option<Timestamp>,
+}
+
+#[cw_serde]
+pub struct MerkleRootResponse {
+    pub merkle_root: Option<String>,
+}
+
+#[cw_serde]
+pub struct RemainingAllocationResponse {
+    /// `None` if the address has no proven allocation
+    pub allocation: Option<Uint128>,
+    /// supply bought during allowlist phases
+    pub purchased: Uint128,
+    pub remaining: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct CircuitBreakerResponse {
+    pub config: Option<CircuitBreakerConfig>,
+    pub window: Option<BreakerWindowState>,
+    /// set while the pool is paused by the circuit breaker
+    pub trip: Option<CircuitBreakerTrip>,
+}
+
+#[cw_serde]
+pub struct WalletPurchasesResponse {
+    pub net_purchased: Uint128,
+    pub exempt: bool,
+    pub max_per_address: Option<Uint128>,
+    /// `None` if the wallet is not capped
+    pub remaining: Option<Uint128>,
+}
+
+#[cw_serde]
+pub struct ExemptWalletsResponse {
+    pub wallets: Vec<String>,
+}
+
+#[cw_serde]
+pub struct TradesResponse {
+    pub trades: Vec<Trade>,
+}
+
+#[cw_serde]
+pub struct CrowdfundResponse {
+    /// `None` if the pool has no crowdfund, in which case `status` is `None` too
+    pub config: Option<CrowdfundConfig>,
+    pub status: Option<CrowdfundStatus>,
+    /// swap fees held for refunds
+    pub held_fees: Uint128,
 }
 
 #[cw_serde]
@@ -119,6 +531,7 @@ pub struct GetSwapFeeResponse {
 
 #[cw_serde]
 pub struct IsActiveResponse {
+    /// true unless both buys and sells are paused
     pub is_active: bool,
 }
 
@@ -164,7 +577,8 @@ pub struct GetTotalPoolLiquidityResponse {
 
 #[cw_serde]
 pub enum SudoMsg {
-    /// SetActive sets the active status of the pool.
+    /// SetActive pauses or resumes both buys and sells. A pool paused by the circuit breaker can
+    /// only be resumed by the owner.
     SetActive { is_active: bool },
     /// SwapExactAmountIn swaps an exact amount of tokens in for as many tokens out as possible.
     /// The amount of tokens out is determined by the current exchange rate and the swap fee.
@@ -234,9 +648,22 @@ pub struct SwapExactAmountOutResponseData {
 }
 
 #[cw_serde]
-pub enum MigrateMsg {}
+pub enum MigrateMsg {
+    /// Upgrades the pool to the current code version. State introduced since the stored version
+    /// is filled with defaults: no owner, no swap fee and no supply cap.
+    Upgrade {},
+    /// Same as `Upgrade`, but state introduced since the stored version is filled with the
+    /// given values instead. Values for state the pool already has are ignored.
+    UpgradeWithConfig {
+        owner: Option<String>,
+        swap_fee: Option<Decimal>,
+        /// defaults to the owner, or the pool itself if there is no owner
+        fee_collector: Option<String>,
+        max_supply: Option<Uint128>,
+    },
+}
 
-pub type CurveFn = Box<dyn Fn(DecimalPlaces) -> Box<dyn Curve>>;
+pub type CurveFn = Box<dyn Fn(DecimalPlaces) -> Result<Box<dyn Curve>, ContractError>>;
 
 #[cw_serde]
 pub enum CurveType {
@@ -244,47 +671,191 @@ pub enum CurveType {
     Constant { value: Uint128, scale: u32 },
     /// Linear returns `slope * 10^-scale * supply` as spot price
     Linear { slope: Uint128, scale: u32 },
-    /// SquareRoot returns `slope * 10^-scale * supply^0.5` as spot price
+    /// SquareRoot returns `slope * 10^-scale * supply^0.5` as spot price.
+    /// Alias for `Power` with an exponent of 1/2.
     SquareRoot { slope: Uint128, scale: u32 },
-    /// SquareRootCubed returns `f(x) = slope * ((x * 10^-scale)^(1/2))^3`
+    /// SquareRootCubed returns `f(x) = slope * ((x * 10^-scale)^(1/2))^3`.
+    /// Alias for `Power` with an exponent of 3/2.
     SquareRootCubed { slope: Uint128, scale: u32 },
-    /// CubeRootSquared returns `f(x) = slope * ((x * 10^-scale)^(1/3))^2`
+    /// CubeRootSquared returns `f(x) = slope * ((x * 10^-scale)^(1/3))^2`.
+    /// Alias for `Power` with an exponent of 2/3.
     CubeRootSquared { slope: Uint128, scale: u32 },
+    /// Power returns `slope * 10^-scale * supply^(numerator / denominator)` as spot price
+    Power {
+        slope: Uint128,
+        scale: u32,
+        numerator: u32,
+        denominator: u32,
+    },
+    /// Exponential returns `(base * 10^-scale)^supply` as spot price. The base must be greater
+    /// than one.
+    Exponential { base: Uint128, scale: u32 },
+    /// Sigmoid returns `max_price / (1 + e^(-steepness * (supply - midpoint)))` as spot price:
+    /// cheap early, steep around `midpoint` (in supply base units) and flattening out towards
+    /// `max_price`.
+    Sigmoid {
+        max_price: Decimal,
+        midpoint: Uint128,
+        steepness: Decimal,
+    },
+    /// Piecewise prices each supply range with its own curve. The first segment must start at
+    /// zero and starts must be strictly increasing.
+    Piecewise { segments: Vec<CurveSegment> },
+}
+
+/// A segment of a `CurveType::Piecewise` curve
+#[cw_serde]
+pub struct CurveSegment {
+    /// supply (in base units of the supply token) this segment starts at
+    pub start: Uint128,
+    /// curve pricing the segment, evaluated at the absolute supply
+    pub curve: CurveType,
 }
 
 impl CurveType {
     pub fn to_curve_fn(&self) -> CurveFn {
         match self.clone() {
             CurveType::Constant { value, scale } => {
-                let calc = move |places| -> Box<dyn Curve> {
-                    Box::new(Constant::new(decimal(value, scale), places))
+                let calc = move |places| -> Result<Box<dyn Curve>, ContractError> {
+                    Ok(Box::new(Constant::new(
+                        checked_decimal(value, scale)?,
+                        places,
+                    )))
                 };
                 Box::new(calc)
             }
             CurveType::Linear { slope, scale } => {
-                let calc = move |places| -> Box<dyn Curve> {
-                    Box::new(Linear::new(decimal(slope, scale), places))
+                let calc = move |places| -> Result<Box<dyn Curve>, ContractError> {
+                    Ok(Box::new(Linear::new(
+                        checked_decimal(slope, scale)?,
+                        places,
+                    )))
+                };
+                Box::new(calc)
+            }
+            CurveType::SquareRoot { slope, scale } => CurveType::Power {
+                slope,
+                scale,
+                numerator: 1,
+                denominator: 2,
+            }
+            .to_curve_fn(),
+            CurveType::SquareRootCubed { slope, scale } => CurveType::Power {
+                slope,
+                scale,
+                numerator: 3,
+                denominator: 2,
+            }
+            .to_curve_fn(),
+            CurveType::CubeRootSquared { slope, scale } => CurveType::Power {
+                slope,
+                scale,
+                numerator: 2,
+                denominator: 3,
+            }
+            .to_curve_fn(),
+            CurveType::Power {
+                slope,
+                scale,
+                numerator,
+                denominator,
+            } => {
+                let calc = move |places| -> Result<Box<dyn Curve>, ContractError> {
+                    Ok(Box::new(Power::new(
+                        checked_decimal(slope, scale)?,
+                        numerator,
+                        denominator,
+                        places,
+                    )))
                 };
                 Box::new(calc)
             }
-            CurveType::SquareRoot { slope, scale } => {
-                let calc = move |places| -> Box<dyn Curve> {
-                    Box::new(SquareRoot::new(decimal(slope, scale), places))
+            CurveType::Exponential { base, scale } => {
+                let calc = move |places| -> Result<Box<dyn Curve>, ContractError> {
+                    Ok(Box::new(Exponential::new(
+                        checked_decimal(base, scale)?,
+                        places,
+                    )?))
                 };
                 Box::new(calc)
             }
-            CurveType::SquareRootCubed { slope, scale } => {
-                let calc = move |places| -> Box<dyn Curve> {
-                    Box::new(SquareRootCubed::new(decimal(slope, scale), places))
+            CurveType::Sigmoid {
+                max_price,
+                midpoint,
+                steepness,
+            } => {
+                let calc = move |places| -> Result<Box<dyn Curve>, ContractError> {
+                    Ok(Box::new(Sigmoid::new(
+                        std_to_decimal(max_price),
+                        midpoint,
+                        std_to_decimal(steepness),
+                        places,
+                    )?))
                 };
                 Box::new(calc)
             }
-            CurveType::CubeRootSquared { slope, scale } => {
-                let calc = move |places| -> Box<dyn Curve> {
-                    Box::new(CubeRootSquared::new(decimal(slope, scale), places))
+            CurveType::Piecewise { segments } => {
+                let curve_fns: Vec<(Uint128, CurveFn)> = segments
+                    .iter()
+                    .map(|segment| (segment.start, segment.curve.to_curve_fn()))
+                    .collect();
+                let calc = move |places: DecimalPlaces| -> Result<Box<dyn Curve>, ContractError> {
+                    let segments = curve_fns
+                        .iter()
+                        .map(|(start, curve_fn)| Ok((*start, curve_fn(places.clone())?)))
+                        .collect::<Result<_, ContractError>>()?;
+                    Ok(Box::new(Piecewise::new(segments, places)))
                 };
                 Box::new(calc)
             }
         }
     }
+
+    /// Checks the curve parameters can be turned into a curve
+    pub fn validate(&self) -> Result<(), ContractError> {
+        let invalid = |reason: &str| ContractError::InvalidCurve {
+            reason: reason.to_string(),
+        };
+        match self {
+            CurveType::Power { denominator: 0, .. } => {
+                return Err(invalid("power exponent denominator must not be zero"));
+            }
+            // base * 10^-scale <= 1, a scale past u128 leaves any base below one
+            CurveType::Exponential { base, scale }
+                if 10u128
+                    .checked_pow(*scale)
+                    .filter(|one| base.u128() > *one)
+                    .is_none() =>
+            {
+                return Err(invalid("exponential base must be greater than one"));
+            }
+            CurveType::Sigmoid {
+                max_price,
+                steepness,
+                ..
+            } if max_price.is_zero() || steepness.is_zero() => {
+                return Err(invalid("sigmoid max price and steepness must not be zero"));
+            }
+            CurveType::Piecewise { segments } => {
+                match segments.first() {
+                    None => return Err(invalid("no segments")),
+                    Some(first) if !first.start.is_zero() => {
+                        return Err(invalid("first segment must start at zero"))
+                    }
+                    _ => {}
+                }
+                if segments
+                    .windows(2)
+                    .any(|pair| pair[0].start >= pair[1].start)
+                {
+                    return Err(invalid("segment starts must be strictly increasing"));
+                }
+                for segment in segments {
+                    segment.curve.validate()?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
 }
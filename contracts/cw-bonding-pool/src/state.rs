@@ -1,7 +1,8 @@
 use cosmwasm_schema::cw_serde;
 
-use cosmwasm_std::Uint128;
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Coin, Decimal, Empty, StdResult, Storage, Timestamp, Uint128, Uint256};
+use cw_storage_macro::index_list;
+use cw_storage_plus::{IndexedMap, Item, Map, MultiIndex};
 
 use crate::curves::DecimalPlaces;
 use crate::msg::CurveType;
@@ -16,33 +17,487 @@ pub struct CurveState {
 
     pub supply_denom: String,
 
-    // the denom of the reserve token
+    // the denom of the reserve token, or its contract address for a cw20 reserve
     pub reserve_denom: String,
 
     // how to normalize reserve and supply
     pub decimals: DecimalPlaces,
+
+    /// the maximum number of supply tokens that can ever be issued.
+    /// `None` for pools created before the cap was enforced.
+    pub max_supply: Option<Uint128>,
 }
 
 impl CurveState {
-    pub fn new(reserve_denom: String, supply_denom: String, decimals: DecimalPlaces) -> Self {
+    pub fn new(
+        reserve_denom: String,
+        supply_denom: String,
+        decimals: DecimalPlaces,
+        max_supply: Option<Uint128>,
+    ) -> Self {
         CurveState {
             reserve: Uint128::zero(),
             supply: Uint128::zero(),
             decimals,
             supply_denom,
             reserve_denom,
+            max_supply,
+        }
+    }
+
+    /// Returns how many supply tokens can still be minted before hitting `max_supply`,
+    /// or `None` if the supply is uncapped.
+    pub fn remaining_supply(&self) -> Option<Uint128> {
+        self.max_supply
+            .map(|max_supply| max_supply.saturating_sub(self.supply))
+    }
+}
+
+/// Kind of token the curve is priced in, identified by `CurveState.reserve_denom`
+#[cw_serde]
+#[derive(Default)]
+pub enum ReserveAsset {
+    /// native or tokenfactory denom, paid in with the message funds
+    #[default]
+    Native,
+    /// cw20 token contract, paid in through a `Receive` hook and paid out with `Transfer`
+    Cw20,
+}
+
+/// What `RetirePool` does with the reserve left in the pool
+#[cw_serde]
+#[derive(Default)]
+pub enum RetirePolicy {
+    /// leave the reserve in the pool to pay out `Dissolve` redemptions
+    #[default]
+    KeepReserve,
+    /// send the reserve to `recipient`, eg. the project treasury
+    SendReserve { recipient: Addr },
+}
+
+/// Standard Osmosis pool seeded when the pool graduates
+#[cw_serde]
+pub enum GraduationPool {
+    /// gamm balancer pool with equal weights and `swap_fee`
+    Balancer { swap_fee: Decimal },
+}
+
+/// Moves liquidity to a standard Osmosis pool once the curve has sold enough supply
+#[cw_serde]
+pub struct GraduationConfig {
+    /// supply at which the pool graduates, at most `max_supply`
+    pub supply_threshold: Uint128,
+    /// fraction of the reserve seeding the new pool, the rest is kept for `Dissolve` redemptions
+    pub reserve_ratio: Decimal,
+    pub pool: GraduationPool,
+    /// receives the LP shares of the new pool
+    pub lp_recipient: Addr,
+    /// fee charged by the gamm module for creating the pool. The part in the reserve denom is held
+    /// back from the reserve, other coins have to be sent to the contract before it can graduate.
+    #[serde(default)]
+    pub pool_creation_fee: Vec<Coin>,
+}
+
+/// Liquidity moved to the graduation pool
+#[cw_serde]
+pub struct Graduation {
+    /// supply tokens minted into the new pool on top of the curve supply
+    pub supply: Uint128,
+    /// reserve tokens taken from the curve reserve, on top of the pool creation fee
+    pub reserve: Uint128,
+    /// set once the pool has been created
+    pub pool_id: Option<u64>,
+}
+
+/// Only produce the supply if `target_supply` is bought by `deadline`
+#[cw_serde]
+pub struct CrowdfundConfig {
+    pub target_supply: Uint128,
+    pub deadline: Timestamp,
+}
+
+#[cw_serde]
+pub enum CrowdfundStatus {
+    /// only buys are allowed, and they are recorded per buyer
+    Open,
+    /// the target was reached, the pool trades as a normal bonding curve
+    Funded,
+    /// the deadline passed without reaching the target, buyers can claim refunds
+    Failed,
+}
+
+#[cw_serde]
+#[derive(Default)]
+pub struct CrowdfundState {
+    /// set once the target has been reached
+    pub funded: bool,
+    /// swap fees of open crowdfund buys, forwarded to the fee collector once funded
+    pub held_fees: Uint128,
+}
+
+/// Buys of one buyer while the crowdfund is open, refunded if it fails
+#[cw_serde]
+#[derive(Default)]
+pub struct Contribution {
+    /// supply tokens bought
+    pub supply: Uint128,
+    /// reserve tokens added to the curve
+    pub reserve: Uint128,
+    /// swap fees paid on top
+    pub fee: Uint128,
+}
+
+/// Who can buy during a sale phase
+#[cw_serde]
+pub enum SaleAccess {
+    /// only addresses on the allowlist
+    Allowlist,
+    Public,
+    /// no buys, holders can still sell
+    Closed,
+}
+
+/// Part of the sale schedule, lasting from `start` until the next phase starts
+#[cw_serde]
+pub struct SalePhase {
+    pub name: String,
+    pub start: Timestamp,
+    pub access: SaleAccess,
+    /// most supply tokens one wallet can buy during the phase
+    pub max_per_wallet: Option<Uint128>,
+    /// multiplies the curve price of buys, eg. 1.1 for a 10% premium. At least 1, so the curve
+    /// stays fully backed. The premium goes to the fee collector along with the swap fee.
+    pub price_multiplier: Option<Decimal>,
+}
+
+/// Allocation proven for an address, valid while `merkle_root` is the registered root
+#[cw_serde]
+pub struct MerkleAllocation {
+    pub merkle_root: String,
+    /// most supply tokens the address can buy during allowlist phases
+    pub allocation: Uint128,
+}
+
+/// Operations paused by the owner. Retiring, graduating and tripping the circuit breaker also
+/// pause swaps.
+#[cw_serde]
+#[derive(Default)]
+pub struct PauseState {
+    pub buys: bool,
+    pub sells: bool,
+    pub dissolve: bool,
+}
+
+impl PauseState {
+    /// The cosmwasmpool module sees the pool as active while it swaps in either direction
+    pub fn is_active(&self) -> bool {
+        !(self.buys && self.sells)
+    }
+}
+
+/// Limits on how far swaps can move the pool within a window. Exceeding one pauses the pool
+/// until the owner resumes it.
+#[cw_serde]
+pub struct CircuitBreakerConfig {
+    pub window: BreakerWindow,
+    /// largest change of the spot price from the start of the window, eg. 0.5 for 50%
+    pub max_price_change: Option<Decimal>,
+    /// most reserve bought into the curve within a window
+    pub max_reserve_inflow: Option<Uint128>,
+    /// most reserve sold out of the curve within a window
+    pub max_reserve_outflow: Option<Uint128>,
+}
+
+#[cw_serde]
+pub enum BreakerWindow {
+    Blocks { blocks: u64 },
+    Seconds { seconds: u64 },
+}
+
+/// Swaps of the current circuit breaker window
+#[cw_serde]
+pub struct BreakerWindowState {
+    pub start_height: u64,
+    pub start_time: Timestamp,
+    /// spot price before the first swap of the window
+    pub start_price: Decimal,
+    pub reserve_inflow: Uint128,
+    pub reserve_outflow: Uint128,
+}
+
+#[cw_serde]
+pub struct CircuitBreakerTrip {
+    pub reason: String,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+/// Cumulative spot price of the supply token at `time`, as in Uniswap v2
+#[cw_serde]
+pub struct PriceObservation {
+    pub time: Timestamp,
+    /// sum of the spot price atomics times the seconds each price held, up to `time`
+    pub cumulative_price: Uint256,
+    /// spot price from `time` on
+    pub price: Decimal,
+}
+
+/// Position in the ring buffer of price observations
+#[cw_serde]
+pub struct TwapBuffer {
+    /// slot of the newest observation
+    pub newest: u32,
+    pub len: u32,
+}
+
+/// Supply a wallet bought, net of what it sold
+#[cw_serde]
+pub struct WalletPurchase {
+    pub address: Addr,
+    /// never below zero, sells of supply bought elsewhere don't count
+    pub net_purchased: Uint128,
+    /// exempt wallets are not capped by `max_per_address`
+    pub exempt: bool,
+}
+
+#[index_list(WalletPurchase)]
+pub struct WalletPurchaseIndexes<'a> {
+    pub exempt: MultiIndex<'a, u8, WalletPurchase, Addr>,
+}
+
+pub struct WalletPurchaseState<'a> {
+    pub purchases: IndexedMap<'a, &'a Addr, WalletPurchase, WalletPurchaseIndexes<'a>>,
+}
+
+impl Default for WalletPurchaseState<'_> {
+    fn default() -> Self {
+        Self {
+            purchases: IndexedMap::new(
+                "wallet_purchases",
+                WalletPurchaseIndexes {
+                    exempt: MultiIndex::new(
+                        |purchase| purchase.exempt as u8,
+                        "wallet_purchases",
+                        "wallet_purchases__exempt",
+                    ),
+                },
+            ),
+        }
+    }
+}
+
+#[cw_serde]
+pub enum TradeDirection {
+    Buy,
+    Sell,
+    Dissolve,
+}
+
+/// A buy, sell or dissolve. Amounts are in the token paid and the token received, prices are
+/// spot prices of the supply token in the reserve token. Dissolve prices are those of the
+/// dissolved curve.
+#[cw_serde]
+pub struct Trade {
+    pub id: u64,
+    pub trader: Addr,
+    pub direction: TradeDirection,
+    pub amount_in: Uint128,
+    pub amount_out: Uint128,
+    pub price_before: Decimal,
+    pub price_after: Decimal,
+    pub height: u64,
+    pub time: Timestamp,
+}
+
+#[index_list(Trade)]
+pub struct TradeIndexes<'a> {
+    pub trader: MultiIndex<'a, Addr, Trade, u64>,
+    pub time: MultiIndex<'a, u64, Trade, u64>,
+}
+
+pub struct TradeState<'a> {
+    pub trades: IndexedMap<'a, u64, Trade, TradeIndexes<'a>>,
+}
+
+impl Default for TradeState<'_> {
+    fn default() -> Self {
+        Self {
+            trades: IndexedMap::new(
+                "trades",
+                TradeIndexes {
+                    trader: MultiIndex::new(
+                        |trade| trade.trader.clone(),
+                        "trades",
+                        "trades__trader",
+                    ),
+                    time: MultiIndex::new(|trade| trade.time.seconds(), "trades", "trades__time"),
+                },
+            ),
         }
     }
 }
 
+/// Trade ids are sequential and pruned from the oldest, so the ids still stored are
+/// `oldest..next`
+#[cw_serde]
+#[derive(Default)]
+pub struct TradeIds {
+    pub oldest: u64,
+    pub next: u64,
+}
+
+/// Bounds of the stored trade history. Trades beyond either bound are pruned from the oldest as
+/// new trades are recorded.
+#[cw_serde]
+pub struct TradeHistoryConfig {
+    /// most trades kept
+    pub max_trades: Option<u64>,
+    /// trades older than this many seconds are pruned
+    pub max_age: Option<u64>,
+}
+
+/// Display metadata of the supply token, set in the bank module through tokenfactory
+#[cw_serde]
+#[derive(Default)]
+pub struct DenomMetadata {
+    /// defaults to the supply subdenom
+    pub name: Option<String>,
+    /// also names the display unit. Defaults to the supply subdenom in upper case.
+    pub symbol: Option<String>,
+    pub description: Option<String>,
+    /// the bank metadata of the supported Osmosis version has no URI, so it is only kept here
+    pub uri: Option<String>,
+}
+
+/// Swap fee charged on buys and sells, and where it is sent
+#[cw_serde]
+pub struct FeeConfig {
+    /// fraction of each swap taken as fee, eg. 0.003 for 0.3%. Charged in the reserve token.
+    pub swap_fee: Decimal,
+    /// receives every collected fee as part of the swap
+    pub fee_collector: Addr,
+}
+
+/// Owner of the pool, allowed to run admin actions such as pausing and config updates.
+/// Ownership is transferred in two steps: the owner proposes a new owner, who then accepts.
+#[cw_serde]
+pub struct Ownership {
+    /// `None` once ownership has been renounced
+    pub owner: Option<Addr>,
+    /// proposed owner that has not accepted yet
+    pub pending_owner: Option<Addr>,
+}
+
 pub const CURVE_STATE: Item<CurveState> = Item::new("curve_state");
 
 pub const CURVE_TYPE: Item<CurveType> = Item::new("curve_type");
 
+/// Replaced by `PAUSE_STATE`, only read for pools that never saved one
 pub const IS_ACTIVE: Item<bool> = Item::new("is_active");
 
+pub const PAUSE_STATE: Item<PauseState> = Item::new("pause_state");
+
+pub const FEE_CONFIG: Item<FeeConfig> = Item::new("fee_config");
+
+pub const OWNERSHIP: Item<Ownership> = Item::new("ownership");
+
+/// Not set for pools created before denom metadata was supported
+pub const DENOM_METADATA: Item<DenomMetadata> = Item::new("denom_metadata");
+
+/// Not set for pools created before retirement was supported, which keep their reserve
+pub const RETIRE_POLICY: Item<RetirePolicy> = Item::new("retire_policy");
+
+/// Set once the pool is retired, swaps can't be resumed afterwards
+pub const IS_RETIRED: Item<bool> = Item::new("is_retired");
+
+/// Not set for pools that don't graduate
+pub const GRADUATION_CONFIG: Item<GraduationConfig> = Item::new("graduation_config");
+
+/// Set once the pool has graduated
+pub const GRADUATION: Item<Graduation> = Item::new("graduation");
+
+/// Not set for pools without a crowdfund
+pub const CROWDFUND_CONFIG: Item<CrowdfundConfig> = Item::new("crowdfund_config");
+
+pub const CROWDFUND_STATE: Item<CrowdfundState> = Item::new("crowdfund_state");
+
+pub const CONTRIBUTIONS: Map<&Addr, Contribution> = Map::new("contributions");
+
+/// Ring buffer of price observations, see `twap`
+pub const TWAP_OBSERVATIONS: Map<u32, PriceObservation> = Map::new("twap_observations");
+
+/// Not set for pools that did not swap since prices are observed
+pub const TWAP_BUFFER: Item<TwapBuffer> = Item::new("twap_buffer");
+
+pub const TRADE_IDS: Item<TradeIds> = Item::new("trade_ids");
+
+/// Not set for pools that keep every trade
+pub const TRADE_HISTORY_CONFIG: Item<TradeHistoryConfig> = Item::new("trade_history_config");
+
+/// Not set for pools without a circuit breaker
+pub const CIRCUIT_BREAKER_CONFIG: Item<CircuitBreakerConfig> = Item::new("circuit_breaker_config");
+
+/// Not set before the first swap, or after the owner resumes a tripped pool
+pub const BREAKER_WINDOW: Item<BreakerWindowState> = Item::new("breaker_window");
+
+/// Set while the pool is paused by the circuit breaker
+pub const BREAKER_TRIP: Item<CircuitBreakerTrip> = Item::new("breaker_trip");
+
+/// Sale phases ordered by start. Without phases buys are always open.
+pub const SALE_PHASES: Item<Vec<SalePhase>> = Item::new("sale_phases");
+
+pub const ALLOWLIST: Map<&Addr, Empty> = Map::new("allowlist");
+
+/// Most supply one wallet can hold net of its sells. Wallets are not capped if unset.
+pub const MAX_PER_ADDRESS: Item<Uint128> = Item::new("max_per_address");
+
+/// Hex sha256 root of the allowlist tree. Built like cw20-merkle-airdrop trees, with
+/// `sha256(address + allocation)` leaves.
+pub const MERKLE_ROOT: Item<String> = Item::new("merkle_root");
+
+pub const MERKLE_ALLOCATIONS: Map<&Addr, MerkleAllocation> = Map::new("merkle_allocations");
+
+/// Supply bought during allowlist phases by addresses with a merkle allocation
+pub const ALLOWLIST_PURCHASES: Map<&Addr, Uint128> = Map::new("allowlist_purchases");
+
+/// Supply bought per wallet during the sale phase starting at the given seconds
+pub const PHASE_PURCHASES: Map<(u64, &Addr), Uint128> = Map::new("phase_purchases");
+
+/// Not set for pools created before cw20 reserves were supported, see `load_reserve_asset`
+pub const RESERVE_ASSET: Item<ReserveAsset> = Item::new("reserve_asset");
+
 pub const DISSOLVED_CURVE_STATE: Item<CurveState> = Item::new("dissolved_curve_state");
 
 pub const IS_TEST_MODE: Item<bool> = Item::new("is_test_mode");
 
 pub const IS_SIMULATION_MODE: Item<bool> = Item::new("is_simulation_mode");
+
+/// Pools created before cw20 reserves were supported always have a native reserve
+pub fn load_reserve_asset(storage: &dyn Storage) -> StdResult<ReserveAsset> {
+    Ok(RESERVE_ASSET.may_load(storage)?.unwrap_or_default())
+}
+
+pub fn load_pause_state(storage: &dyn Storage) -> StdResult<PauseState> {
+    if let Some(pause_state) = PAUSE_STATE.may_load(storage)? {
+        return Ok(pause_state);
+    }
+    let is_active = IS_ACTIVE.may_load(storage)?.unwrap_or(true);
+    Ok(PauseState {
+        buys: !is_active,
+        sells: !is_active,
+        dissolve: false,
+    })
+}
+
+/// The merkle allocation of `address`, if proven under the registered root
+pub fn load_merkle_allocation(storage: &dyn Storage, address: &Addr) -> StdResult<Option<Uint128>> {
+    let merkle_root = MERKLE_ROOT.may_load(storage)?;
+    Ok(MERKLE_ALLOCATIONS
+        .may_load(storage, address)?
+        .filter(|allocation| Some(&allocation.merkle_root) == merkle_root.as_ref())
+        .map(|allocation| allocation.allocation))
+}
+
+pub fn is_retired(storage: &dyn Storage) -> StdResult<bool> {
+    Ok(IS_RETIRED.may_load(storage)?.unwrap_or(false))
+}
@@ -0,0 +1,220 @@
+use cosmwasm_std::{ensure, Decimal, Env, Storage, Timestamp, Uint128, Uint256};
+
+use crate::calc::calc_spot_price;
+use crate::error::ContractError;
+use crate::state::{
+    CurveState, PriceObservation, TwapBuffer, CURVE_TYPE, TWAP_BUFFER, TWAP_OBSERVATIONS,
+};
+
+/// Slots of the observation ring buffer
+pub const TWAP_SLOTS: u32 = 300;
+
+/// The newest observation takes a new slot once it is this many seconds after the one before it.
+/// Full buffers then span at least `(TWAP_SLOTS - 2) * TWAP_SPACING` seconds, over 49 hours.
+pub const TWAP_SPACING: u64 = 600;
+
+/// Spot price of the supply token in the reserve token
+pub fn spot_price(
+    storage: &dyn Storage,
+    curve_state: &CurveState,
+) -> Result<Decimal, ContractError> {
+    calc_spot_price(
+        curve_state.reserve_denom.clone(),
+        curve_state.supply_denom.clone(),
+        curve_state.clone(),
+        CURVE_TYPE.load(storage)?,
+    )
+}
+
+/// Accumulates the spot price held since the newest observation, and records the spot price of
+/// `curve_state` from now on. Called whenever the curve supply changes, returns the recorded price.
+pub fn record_price(
+    storage: &mut dyn Storage,
+    env: &Env,
+    curve_state: &CurveState,
+) -> Result<Decimal, ContractError> {
+    let price = spot_price(storage, curve_state)?;
+    let time = env.block.time;
+    let buffer = match TWAP_BUFFER.may_load(storage)? {
+        Some(buffer) => buffer,
+        None => {
+            let observation = PriceObservation {
+                time,
+                cumulative_price: Uint256::zero(),
+                price,
+            };
+            TWAP_OBSERVATIONS.save(storage, 0, &observation)?;
+            TWAP_BUFFER.save(storage, &TwapBuffer { newest: 0, len: 1 })?;
+            return Ok(price);
+        }
+    };
+
+    let newest = TWAP_OBSERVATIONS.load(storage, buffer.newest)?;
+    let observation = PriceObservation {
+        time,
+        cumulative_price: cumulative_price_since(&newest, time.seconds())?,
+        price,
+    };
+    // the newest observation moves up to now until it is spaced from the one before it
+    let previous = match buffer.len {
+        1 => None,
+        len => Some(load_observation(storage, &buffer, len - 2)?),
+    };
+    let same_slot = time.seconds() == newest.time.seconds()
+        || matches!(previous, Some(previous)
+            if newest.time.seconds() - previous.time.seconds() < TWAP_SPACING);
+    let buffer = if same_slot {
+        buffer
+    } else {
+        TwapBuffer {
+            newest: (buffer.newest + 1) % TWAP_SLOTS,
+            len: (buffer.len + 1).min(TWAP_SLOTS),
+        }
+    };
+    TWAP_OBSERVATIONS.save(storage, buffer.newest, &observation)?;
+    TWAP_BUFFER.save(storage, &buffer)?;
+    Ok(price)
+}
+
+/// Arithmetic mean of the spot price between `start_time` and `end_time`, weighted by time
+pub fn arithmetic_twap(
+    storage: &dyn Storage,
+    env: &Env,
+    start_time: Timestamp,
+    end_time: Timestamp,
+) -> Result<Decimal, ContractError> {
+    let (start, end) = (start_time.seconds(), end_time.seconds());
+    let invalid = |reason: String| ContractError::InvalidTwapRange { reason };
+    ensure!(
+        start < end,
+        invalid("start time must be at least a second before end time".to_string())
+    );
+    ensure!(
+        end <= env.block.time.seconds(),
+        invalid("end time must not be in the future".to_string())
+    );
+    let buffer = TWAP_BUFFER
+        .may_load(storage)?
+        .ok_or_else(|| invalid("no prices recorded yet".to_string()))?;
+    let oldest = load_observation(storage, &buffer, 0)?;
+    ensure!(
+        start >= oldest.time.seconds(),
+        invalid(format!(
+            "start time must not be before the oldest price, recorded at {}",
+            oldest.time
+        ))
+    );
+
+    let cumulative_price = cumulative_price_at(storage, &buffer, end)?
+        .checked_sub(cumulative_price_at(storage, &buffer, start)?)?;
+    let twap = cumulative_price.checked_div(Uint256::from(end - start))?;
+    Ok(Decimal::new(Uint128::try_from(twap)?))
+}
+
+/// The `index`th observation from the oldest one
+fn load_observation(
+    storage: &dyn Storage,
+    buffer: &TwapBuffer,
+    index: u32,
+) -> Result<PriceObservation, ContractError> {
+    let slot = (buffer.newest + TWAP_SLOTS + 1 - buffer.len + index) % TWAP_SLOTS;
+    Ok(TWAP_OBSERVATIONS.load(storage, slot)?)
+}
+
+fn cumulative_price_since(
+    observation: &PriceObservation,
+    time: u64,
+) -> Result<Uint256, ContractError> {
+    let elapsed = time.saturating_sub(observation.time.seconds());
+    let accumulated = Uint256::from(observation.price.atomics()).checked_mul(elapsed.into())?;
+    Ok(observation.cumulative_price.checked_add(accumulated)?)
+}
+
+/// Cumulative price at `time`, which must not be before the oldest observation. Interpolated
+/// between the observations around it.
+fn cumulative_price_at(
+    storage: &dyn Storage,
+    buffer: &TwapBuffer,
+    time: u64,
+) -> Result<Uint256, ContractError> {
+    let newest = TWAP_OBSERVATIONS.load(storage, buffer.newest)?;
+    if time >= newest.time.seconds() {
+        return cumulative_price_since(&newest, time);
+    }
+
+    // observation `low` is at or before `time`, observation `high` after it
+    let (mut low, mut high) = (0, buffer.len - 1);
+    while high - low > 1 {
+        let mid = (low + high) / 2;
+        if load_observation(storage, buffer, mid)?.time.seconds() <= time {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    let before = load_observation(storage, buffer, low)?;
+    let after = load_observation(storage, buffer, high)?;
+    let accumulated = after
+        .cumulative_price
+        .checked_sub(before.cumulative_price)?
+        .multiply_ratio(
+            time - before.time.seconds(),
+            after.time.seconds() - before.time.seconds(),
+        );
+    Ok(before.cumulative_price.checked_add(accumulated)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_env, MockStorage};
+
+    use super::*;
+    use crate::curves::DecimalPlaces;
+    use crate::msg::CurveType;
+
+    #[test]
+    fn observations_cover_48_hours() {
+        let mut storage = MockStorage::new();
+        CURVE_TYPE
+            .save(
+                &mut storage,
+                &CurveType::Constant {
+                    value: 15u128.into(),
+                    scale: 1,
+                },
+            )
+            .unwrap();
+        let curve_state = CurveState::new(
+            "uosmo".to_string(),
+            "ushirt".to_string(),
+            DecimalPlaces::new(0, 6),
+            None,
+        );
+        let start = mock_env().block.time;
+        let at = |seconds| {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(seconds);
+            env
+        };
+
+        // a swap every minute for 60 hours
+        for minute in 0..3600 {
+            record_price(&mut storage, &at(minute * 60), &curve_state).unwrap();
+        }
+        let buffer = TWAP_BUFFER.load(&storage).unwrap();
+        assert_eq!(buffer.len, TWAP_SLOTS);
+        let now = at(3599 * 60);
+        let hours_48 = 48 * 60 * 60;
+        let twap = arithmetic_twap(
+            &storage,
+            &now,
+            now.block.time.minus_seconds(hours_48),
+            now.block.time,
+        )
+        .unwrap();
+        assert_eq!(twap, Decimal::percent(150));
+
+        let err = arithmetic_twap(&storage, &now, start, now.block.time).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidTwapRange { .. }));
+    }
+}
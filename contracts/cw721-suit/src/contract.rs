@@ -1,7 +1,8 @@
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::entry_point;
-use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult};
-
+use cosmwasm_std::{Binary, Deps, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdResult};
+use cw2::{get_contract_version, set_contract_version};
+use semver::Version;
 
 use crate::error::ContractError;
 use crate::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
@@ -13,12 +14,15 @@ const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
 /// Handling contract instantiation
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
 ) -> Result<Response, cosmwasm_std::StdError> {
-    cw721_base::entry::instantiate(deps, env, info, msg)
+    let res = cw721_base::entry::instantiate(deps.branch(), env, info, msg)?;
+    // cw721-base records its own version, overwrite it so migrations can tell this contract apart
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(res)
 }
 
 /// Handling contract migration
@@ -27,13 +31,46 @@ pub fn instantiate(
 /// - only contract admin can migrate, so admin has to be set at contract initiation time
 /// Handling contract execution
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(mut deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored = get_contract_version(deps.storage)?;
+
     match msg {
-        // Find matched incoming message variant and execute them with your custom logic.
-        //
-        // With `Response` type, it is possible to dispatch message to invoke external logic.
-        // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+        MigrateMsg::Upgrade {} => {}
     }
+
+    // collections instantiated before this contract recorded its own version carry the
+    // cw721-base version info
+    if stored.contract == cw721_base::CONTRACT_NAME {
+        ensure_not_newer(&stored.version, cw721_base::CONTRACT_VERSION)?;
+        if stored.version != cw721_base::CONTRACT_VERSION {
+            cw721_base::entry::migrate(deps.branch(), env, Empty {})?;
+        }
+    } else if stored.contract == CONTRACT_NAME {
+        ensure_not_newer(&stored.version, CONTRACT_VERSION)?;
+    } else {
+        return Err(ContractError::InvalidContractName {
+            expected: CONTRACT_NAME.to_string(),
+            actual: stored.contract,
+        });
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_contract", stored.contract)
+        .add_attribute("from_version", stored.version)
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+fn ensure_not_newer(stored_version: &str, version: &str) -> Result<(), ContractError> {
+    if stored_version.parse::<Version>()? > version.parse::<Version>()? {
+        return Err(ContractError::CannotDowngrade {
+            from: stored_version.to_string(),
+            to: version.to_string(),
+        });
+    }
+    Ok(())
 }
 
 /// Handling contract execution
@@ -62,3 +99,81 @@ pub fn reply(_deps: DepsMut, _env: Env, _msg: Reply) -> Result<Response, Contrac
 
     Ok(Response::new())
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+
+    use super::*;
+
+    fn instantiate_msg() -> InstantiateMsg {
+        InstantiateMsg {
+            name: "Suit".to_string(),
+            symbol: "SUIT".to_string(),
+            minter: "minter".to_string(),
+        }
+    }
+
+    #[test]
+    fn instantiate_records_own_version() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            instantiate_msg(),
+        )
+        .unwrap();
+
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, CONTRACT_VERSION);
+    }
+
+    #[test]
+    fn migrate_checks_contract_name_and_version() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            instantiate_msg(),
+        )
+        .unwrap();
+
+        set_contract_version(&mut deps.storage, "crates.io:suitdrop-redeem", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidContractName { .. }));
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDowngrade { .. }));
+
+        set_contract_version(&mut deps.storage, cw721_base::CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDowngrade { .. }));
+    }
+
+    #[test]
+    fn migrate_from_cw721_base_version() {
+        let mut deps = mock_dependencies();
+        instantiate(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("creator", &[]),
+            instantiate_msg(),
+        )
+        .unwrap();
+        set_contract_version(
+            &mut deps.storage,
+            cw721_base::CONTRACT_NAME,
+            cw721_base::CONTRACT_VERSION,
+        )
+        .unwrap();
+
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap();
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(version.contract, CONTRACT_NAME);
+        assert_eq!(version.version, CONTRACT_VERSION);
+    }
+}
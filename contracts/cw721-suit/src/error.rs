@@ -11,6 +11,24 @@ pub enum ContractError {
 
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
+
+    #[error("{0}")]
+    Cw721Base(#[from] cw721_base::ContractError),
+
+    #[error("Cannot migrate from contract {actual}, expected {expected}")]
+    InvalidContractName { expected: String, actual: String },
+
+    #[error("Cannot migrate from version {from} to older version {to}")]
+    CannotDowngrade { from: String, to: String },
+
+    #[error("{0}")]
+    SemVer(String),
     // Add any other custom errors you like here.
     // Look at https://docs.rs/thiserror/1.0.21/thiserror/ for details.
 }
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
+}
@@ -11,7 +11,12 @@ pub type ExecuteMsg = cw721_base::ExecuteMsg<cw721_base::Extension, Empty>;
 
 /// Message type for `migrate` entry_point
 #[cw_serde]
-pub enum MigrateMsg {}
+pub enum MigrateMsg {
+    /// Upgrades the contract to the current code version. Collections instantiated before
+    /// cw721-suit recorded its own version are also migrated, running the cw721-base migration
+    /// first where one is needed.
+    Upgrade {},
+}
 
 /// Message type for `query` entry_point
 pub type QueryMsg = cw721_base::QueryMsg<Empty>;
@@ -2,11 +2,12 @@
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{
     coins, to_binary, Binary, CosmosMsg, Deps, DepsMut, Empty, Env, MessageInfo, Order, Reply,
-    Response, StdError, StdResult, SubMsg, Uint64, WasmMsg,
+    Response, StdError, StdResult, Storage, SubMsg, Uint64, WasmMsg,
 };
-use cw2::set_contract_version;
+use cw2::{get_contract_version, set_contract_version};
 use cw_storage_plus::Bound;
 use cw_utils::{must_pay, one_coin, parse_reply_instantiate_data};
+use semver::Version;
 
 use crate::error::ContractError;
 use crate::msg::{
@@ -69,13 +70,40 @@ pub fn instantiate(
 /// - only contract admin can migrate, so admin has to be set at contract initiation time
 /// Handling contract execution
 #[cfg_attr(not(feature = "library"), entry_point)]
-pub fn migrate(_deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+pub fn migrate(deps: DepsMut, _env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+    let stored_version = ensure_from_older_version(deps.storage)?;
+
     match msg {
-        // Find matched incoming message variant and execute them with your custom logic.
-        //
-        // With `Response` type, it is possible to dispatch message to invoke external logic.
-        // See: https://github.com/CosmWasm/cosmwasm/blob/main/SEMANTICS.md#dispatching-messages
+        MigrateMsg::Upgrade {} => {}
+    }
+
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    Ok(Response::new()
+        .add_attribute("method", "migrate")
+        .add_attribute("from_version", stored_version.to_string())
+        .add_attribute("to_version", CONTRACT_VERSION))
+}
+
+/// Errors unless the stored cw2 info belongs to this contract and is not newer than this code.
+/// Returns the stored version.
+fn ensure_from_older_version(storage: &dyn Storage) -> Result<Version, ContractError> {
+    let stored = get_contract_version(storage)?;
+    if stored.contract != CONTRACT_NAME {
+        return Err(ContractError::InvalidContractName {
+            expected: CONTRACT_NAME.to_string(),
+            actual: stored.contract,
+        });
+    }
+    let stored_version: Version = stored.version.parse()?;
+    let version: Version = CONTRACT_VERSION.parse()?;
+    if stored_version > version {
+        return Err(ContractError::CannotDowngrade {
+            from: stored.version,
+            to: CONTRACT_VERSION.to_string(),
+        });
     }
+    Ok(stored_version)
 }
 
 /// Handling contract execution
@@ -241,3 +269,30 @@ pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractEr
         _ => Err(ContractError::InvalidReplyId {}),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cosmwasm_std::testing::{mock_dependencies, mock_env};
+
+    use super::*;
+
+    #[test]
+    fn migrate_checks_contract_name_and_version() {
+        let mut deps = mock_dependencies();
+
+        set_contract_version(&mut deps.storage, "crates.io:cw721-suit", "0.1.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::InvalidContractName { .. }));
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let err = migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap_err();
+        assert!(matches!(err, ContractError::CannotDowngrade { .. }));
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "0.0.1").unwrap();
+        migrate(deps.as_mut(), mock_env(), MigrateMsg::Upgrade {}).unwrap();
+        assert_eq!(
+            get_contract_version(&deps.storage).unwrap().version,
+            CONTRACT_VERSION
+        );
+    }
+}
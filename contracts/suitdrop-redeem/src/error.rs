@@ -30,4 +30,19 @@ pub enum ContractError {
     // overflow
     #[error("Overflow error")]
     OverflowError(#[from] OverflowError),
+
+    #[error("Cannot migrate from contract {actual}, expected {expected}")]
+    InvalidContractName { expected: String, actual: String },
+
+    #[error("Cannot migrate from version {from} to older version {to}")]
+    CannotDowngrade { from: String, to: String },
+
+    #[error("{0}")]
+    SemVer(String),
+}
+
+impl From<semver::Error> for ContractError {
+    fn from(err: semver::Error) -> Self {
+        Self::SemVer(err.to_string())
+    }
 }
@@ -23,7 +23,10 @@ pub enum ExecuteMsg {
 
 /// Message type for `migrate` entry_point
 #[cw_serde]
-pub enum MigrateMsg {}
+pub enum MigrateMsg {
+    /// Upgrades the contract to the current code version
+    Upgrade {},
+}
 
 /// Message type for `query` entry_point
 #[cw_serde]
@@ -55,13 +55,28 @@ fn test_integration() {
             &cw_bonding_pool::msg::InstantiateMsg {
                 supply_subdenom: "ushirt".to_string(),
                 supply_decimals: 6,
+                name: None,
+                symbol: None,
+                description: None,
+                uri: None,
                 max_supply: 500_000_000u128.into(),
                 reserve_denom: "uosmo".to_string(),
+                reserve_asset: None,
                 reserve_decimals: 6,
                 curve_type: CurveType::Linear {
                     slope: 1u128.into(),
                     scale: 1u32,
                 },
+                swap_fee: None,
+                fee_collector: None,
+                owner: None,
+                retire_policy: None,
+                graduation: None,
+                crowdfund: None,
+                sale_phases: None,
+                max_per_address: None,
+                circuit_breaker: None,
+                trade_history: None,
                 test_mode: None,
                 simulation_mode: None,
             },